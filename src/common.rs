@@ -2,8 +2,12 @@
 //!
 //! **Not for user code!**
 
+pub(crate) mod compressor;
 pub(crate) mod constant;
+pub(crate) mod hasher;
 pub(crate) mod macros;
 
+pub(crate) use compressor::*;
 pub(crate) use constant::*;
+pub(crate) use hasher::*;
 pub(crate) use macros::*;
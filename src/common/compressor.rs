@@ -0,0 +1,132 @@
+//! Compressor for every [`Compression`]
+
+use crate::core::Compression;
+use std::{io, path::Path};
+
+/// Files smaller than it are sent without compression
+pub(crate) const MIN_SIZE_FOR_COMPRESSION: u64 = 1024;
+
+/// Level of zstd. Fast, but with good ratio
+#[cfg(feature = "zstd")]
+const ZSTD_LEVEL: i32 = 3;
+
+/// Extensions of already compressed files. They are sent without compression
+const COMPRESSED_EXTENSIONS: &[&str] = &[
+    "7z", "avi", "br", "bz2", "docx", "flac", "gif", "gz", "heic", "jpeg", "jpg", "lz4", "lzma",
+    "mkv", "mov", "mp3", "mp4", "ogg", "png", "rar", "tgz", "webm", "webp", "xlsx", "xz", "zip",
+    "zst",
+];
+
+/// [`Compression`] for the file. [`Compression::None`] for small and already compressed files
+pub(crate) fn get_compression_for_file(
+    compression: Compression,
+    path: &Path,
+    size: u64,
+) -> Compression {
+    let is_compressed = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(|extension| COMPRESSED_EXTENSIONS.contains(&extension.to_lowercase().as_str()))
+        .unwrap_or(false);
+
+    if size < MIN_SIZE_FOR_COMPRESSION || is_compressed || !compression.is_supported() {
+        return Compression::None;
+    }
+
+    compression
+}
+
+/// Compress one block. `None` if it doesn't get smaller: then it is sent as is
+pub(crate) fn compress_block(
+    compression: Compression,
+    block: &[u8],
+) -> io::Result<Option<Vec<u8>>> {
+    let compressed: Option<Vec<u8>> = match compression {
+        #[cfg(feature = "zstd")]
+        Compression::Zstd => Some(zstd::bulk::compress(block, ZSTD_LEVEL)?),
+        #[cfg(feature = "lz4")]
+        Compression::Lz4 => Some(lz4_flex::block::compress(block)),
+        _ => None,
+    };
+
+    Ok(compressed.filter(|compressed| compressed.len() < block.len()))
+}
+
+/// Decompress the block of [`compress_block`] to `output`. Returns size of the data
+///
+/// Error, if the data is bigger than `output`
+#[cfg_attr(not(any(feature = "zstd", feature = "lz4")), allow(unused_variables))]
+pub(crate) fn decompress_block(
+    compression: Compression,
+    block: &[u8],
+    output: &mut [u8],
+) -> io::Result<usize> {
+    match compression {
+        #[cfg(feature = "zstd")]
+        Compression::Zstd => zstd::bulk::decompress_to_buffer(block, output),
+        #[cfg(feature = "lz4")]
+        Compression::Lz4 => lz4_flex::block::decompress_into(block, output)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+        _ => Err(io::ErrorKind::Unsupported.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compress_and_decompress() {
+        let data = b"2023-05-01 12:00:00 INFO request done\n".repeat(100);
+
+        for compression in Compression::supported() {
+            if compression == Compression::None {
+                continue;
+            }
+
+            let compressed = compress_block(compression, &data).unwrap().unwrap();
+            assert!(compressed.len() < data.len() / 10);
+
+            let mut output = vec![0u8; data.len()];
+            let len = decompress_block(compression, &compressed, &mut output).unwrap();
+            assert_eq!(&output[..len], data.as_slice());
+
+            // Output is too small
+            let mut output = vec![0u8; data.len() / 2];
+            assert!(decompress_block(compression, &compressed, &mut output).is_err());
+
+            // Random data doesn't get smaller
+            let mut state = 0x2545_f491u32;
+            let random: Vec<u8> = (0..4096)
+                .map(|_| {
+                    state ^= state << 13;
+                    state ^= state >> 17;
+                    state ^= state << 5;
+                    state as u8
+                })
+                .collect();
+            assert_eq!(compress_block(compression, &random).unwrap(), None);
+        }
+
+        assert_eq!(compress_block(Compression::None, &data).unwrap(), None);
+    }
+
+    #[test]
+    fn skip_compression() {
+        let compression = *Compression::supported().first().unwrap();
+        let path = Path::new("file.log");
+
+        assert_eq!(
+            get_compression_for_file(compression, path, 1024 * 1024),
+            compression
+        );
+        assert_eq!(
+            get_compression_for_file(compression, path, 100),
+            Compression::None
+        );
+        assert_eq!(
+            get_compression_for_file(compression, Path::new("photo.JPG"), 1024 * 1024),
+            Compression::None
+        );
+    }
+}
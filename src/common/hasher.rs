@@ -0,0 +1,93 @@
+//! Hasher for every [`HashAlgorithm`]
+
+use super::get_hasher;
+use crate::core::HashAlgorithm;
+use blake2::{Blake2b512, Digest};
+use sha2::Sha256;
+use xxhash_rust::xxh3::Xxh3;
+
+/// Hasher of the data. Hash is a hex string
+#[derive(Clone)]
+pub(crate) enum Hasher {
+    Blake2b(Blake2b512),
+    Blake3(Box<blake3::Hasher>),
+    Sha256(Sha256),
+    Xxh3(Box<Xxh3>),
+    None,
+}
+
+impl Hasher {
+    pub(crate) fn new(algorithm: HashAlgorithm) -> Self {
+        match algorithm {
+            HashAlgorithm::Blake2b => Self::Blake2b(get_hasher()),
+            HashAlgorithm::Blake3 => Self::Blake3(Box::default()),
+            HashAlgorithm::Sha256 => Self::Sha256(Sha256::new()),
+            HashAlgorithm::Xxh3 => Self::Xxh3(Box::default()),
+            HashAlgorithm::None => Self::None,
+        }
+    }
+
+    pub(crate) fn update(&mut self, data: &[u8]) {
+        match self {
+            Self::Blake2b(hasher) => hasher.update(data),
+            Self::Blake3(hasher) => {
+                hasher.update(data);
+            }
+            Self::Sha256(hasher) => hasher.update(data),
+            Self::Xxh3(hasher) => hasher.update(data),
+            Self::None => (),
+        }
+    }
+
+    /// Hex string of the hash. Empty for [`HashAlgorithm::None`]
+    pub(crate) fn finalize(self) -> String {
+        match self {
+            Self::Blake2b(hasher) => format!("{:x}", hasher.finalize()),
+            Self::Blake3(hasher) => hasher.finalize().to_hex().to_string(),
+            Self::Sha256(hasher) => format!("{:x}", hasher.finalize()),
+            Self::Xxh3(hasher) => format!("{:032x}", hasher.digest128()),
+            Self::None => String::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hash(algorithm: HashAlgorithm, data: &[u8]) -> String {
+        let mut hasher = Hasher::new(algorithm);
+        hasher.update(data);
+        hasher.finalize()
+    }
+
+    #[test]
+    fn known_hashes() {
+        assert_eq!(
+            hash(HashAlgorithm::Sha256, b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(
+            hash(HashAlgorithm::Blake3, b""),
+            "af1349b9f5f9a1a6a0404dea36dcc9499bcb25c9adc112b7cc9a93cae41f3262"
+        );
+        assert_eq!(hash(HashAlgorithm::None, b"abc"), "");
+    }
+
+    #[test]
+    fn update_by_parts() {
+        for algorithm in [
+            HashAlgorithm::Blake2b,
+            HashAlgorithm::Blake3,
+            HashAlgorithm::Sha256,
+            HashAlgorithm::Xxh3,
+        ] {
+            let mut hasher = Hasher::new(algorithm);
+            hasher.update(b"test ");
+            hasher.update(b"data");
+
+            assert_eq!(hasher.finalize(), hash(algorithm, b"test data"));
+            assert_ne!(hash(algorithm, b"test data"), hash(algorithm, b"other"));
+        }
+    }
+}
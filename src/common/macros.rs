@@ -31,8 +31,9 @@ macro_rules! generate_config {
             #[doc = "Port for sending files. Uses this port only [`crate::protocol`]"]
             pub(crate) port_for_send_files: u16,
 
-            #[doc = "Handshake port. The [`crate::protocol`] does not use it"]
-            pub(crate) port_for_handshake: u16,
+            #[doc = "Handshake port. The [`crate::protocol`] does not use it\n\n"]
+            #[doc = "If `None`, handshake is sent over the connection for sending files"]
+            pub(crate) port_for_handshake: Option<u16>,
 
             #[doc = "Timeout for getting error"]
             pub(crate) timeout: std::time::Duration,
@@ -40,6 +41,62 @@ macro_rules! generate_config {
             #[doc = "Callback to check the progress of the operation\n\n"]
             #[doc = "To change it, you need to call set_progress_fn"]
             pub(crate) progress_fn: Option<crate::core::ProgressFn<'a>>,
+
+            #[doc = "Callback to accept or reject incoming files. Only for [`Recipient`](crate::recipient::Recipient)\n\n"]
+            #[doc = "To change it, you need to call set_decision_fn"]
+            pub(crate) decision_fn: Option<crate::core::DecisionFn<'a>>,
+
+            #[doc = "Algorithm for checking files\n\n"]
+            #[doc = "To change it, you need to call set_hash_algorithm"]
+            pub(crate) hash_algorithm: crate::core::HashAlgorithm,
+
+            #[doc = "Max size of one chunk of data. It is advertised to the other side\n\n"]
+            #[doc = "To change it, you need to call set_max_chunk_size"]
+            pub(crate) max_chunk_size: usize,
+
+            #[doc = "Codec for data of files. It is advertised to the other side\n\n"]
+            #[doc = "To change it, you need to call set_compression. Only for [`Sender`](crate::sender::Sender)"]
+            pub(crate) compression: crate::core::Compression,
+
+            #[doc = "What to do with unsafe file names. Only for [`Recipient`](crate::recipient::Recipient)\n\n"]
+            #[doc = "To change it, you need to call set_file_name_policy"]
+            pub(crate) file_name_policy: crate::protocol::handshake::FileNamePolicy,
+
+            #[doc = "Which name to use, if the original one is not UTF-8. Only for [`Recipient`](crate::recipient::Recipient)\n\n"]
+            #[doc = "To change it, you need to call set_file_name_encoding"]
+            pub(crate) file_name_encoding: crate::protocol::handshake::FileNameEncoding,
+
+            #[doc = "Which metadata of received files to apply. Only for [`Recipient`](crate::recipient::Recipient)\n\n"]
+            #[doc = "To change it, you need to call set_preserve_metadata. Set by apply_capabilities: which metadata is sent"]
+            pub(crate) preserve_metadata: crate::core::PreserveMetadata,
+
+            #[doc = "Shared secret for authentication of the other side. See [`auth`](crate::protocol::auth)\n\n"]
+            #[doc = "To change it, you need to call set_auth_key"]
+            pub(crate) auth_key: Option<Vec<u8>>,
+
+            #[doc = "Key for signing files. Only for [`Sender`](crate::sender::Sender)\n\n"]
+            #[doc = "To change it, you need to call set_signing_key"]
+            pub(crate) signing_key: Option<crate::core::SigningKey>,
+
+            #[doc = "Public keys of trusted signers. Only for [`Recipient`](crate::recipient::Recipient)\n\n"]
+            #[doc = "If it is empty, all files are accepted. To change it, you need to call add_trusted_signer"]
+            pub(crate) trusted_signers: Vec<crate::core::VerifyingKey>,
+
+            #[doc = "Random nonce of the negotiated session. Set by apply_capabilities"]
+            pub(crate) session_nonce: Vec<u8>,
+
+            #[cfg(feature = "encryption")]
+            #[doc = "Encryption of the connection for sending files\n\n"]
+            #[doc = "To change it, you need to call set_encryption"]
+            pub(crate) encryption: crate::core::Encryption,
+
+            #[cfg(feature = "tls")]
+            #[doc = "TLS settings for TCP connections. See [`tls`](crate::protocol::tls)"]
+            pub(crate) tls: crate::protocol::tls::TlsConfig,
+
+            #[cfg(feature = "quic")]
+            #[doc = "TLS settings for [`quic`](crate::protocol::quic)"]
+            pub(crate) quic_tls: crate::protocol::quic::QuicTlsConfig,
         }
 
         impl std::fmt::Debug for $name<'_> {
@@ -51,6 +108,16 @@ macro_rules! generate_config {
                     .field("port_for_handshake", &self.port_for_handshake)
                     .field("timeout", &self.timeout)
                     .field("progress_fn.is_none()", &self.progress_fn.is_none())
+                    .field("decision_fn.is_none()", &self.decision_fn.is_none())
+                    .field("hash_algorithm", &self.hash_algorithm)
+                    .field("max_chunk_size", &self.max_chunk_size)
+                    .field("compression", &self.compression)
+                    .field("file_name_policy", &self.file_name_policy)
+                    .field("file_name_encoding", &self.file_name_encoding)
+                    .field("preserve_metadata", &self.preserve_metadata)
+                    .field("auth_key.is_some()", &self.auth_key.is_some())
+                    .field("signing_key.is_some()", &self.signing_key.is_some())
+                    .field("trusted_signers.len()", &self.trusted_signers.len())
                     .finish()
             }
         }
@@ -64,7 +131,7 @@ macro_rules! generate_config {
                 self.port_for_send_files
             }
 
-            fn get_port_for_handshake(&self) -> u16 {
+            fn get_port_for_handshake(&self) -> Option<u16> {
                 self.port_for_handshake
             }
 
@@ -72,12 +139,60 @@ macro_rules! generate_config {
                 self.timeout
             }
 
+            fn get_hash_algorithm(&self) -> crate::core::HashAlgorithm {
+                self.hash_algorithm
+            }
+
+            fn get_max_chunk_size(&self) -> usize {
+                self.max_chunk_size
+            }
+
+            fn get_compression(&self) -> crate::core::Compression {
+                self.compression
+            }
+
+            fn get_auth_key(&self) -> Option<&[u8]> {
+                self.auth_key.as_deref()
+            }
+
+            fn get_preserve_metadata(&self) -> crate::core::PreserveMetadata {
+                self.preserve_metadata
+            }
+
+            fn get_session_nonce(&self) -> &[u8] {
+                &self.session_nonce
+            }
+
+            #[cfg(feature = "encryption")]
+            fn get_encryption(&self) -> &crate::core::Encryption {
+                &self.encryption
+            }
+
+            #[cfg(feature = "tls")]
+            fn get_tls(&self) -> &crate::protocol::tls::TlsConfig {
+                &self.tls
+            }
+
             fn run_progress_fn(&self, progressing: Progressing) {
                 if let Some(progress_fn) = self.progress_fn.clone() {
                     progress_fn.lock().unwrap()(progressing);
                 }
             }
         }
+
+        impl $name<'_> {
+            #[doc = "Use what was agreed with the other side"]
+            pub(crate) fn apply_capabilities(
+                &mut self,
+                capabilities: &crate::core::NegotiatedCapabilities,
+            ) {
+                self.hash_algorithm = capabilities.hash_algorithm;
+                self.max_chunk_size = capabilities.max_chunk_size;
+                self.compression = capabilities.compression;
+                self.preserve_metadata = capabilities.preserve_metadata;
+                self.session_nonce = capabilities.nonce.clone();
+            }
+        }
     };
 }
 
@@ -85,7 +200,7 @@ pub(crate) use generate_config;
 
 /// Generate new implementation for [`generate_config`]
 macro_rules! generate_new_for_config {
-    ($name_config:ident) => {
+    ($name_config:ident $(, $field:ident: $value:expr)*) => {
         #[doc = "New for [`"]
         #[doc = stringify!(Self)]
         #[doc = "`]\n"]
@@ -98,6 +213,26 @@ macro_rules! generate_new_for_config {
             addr: std::net::IpAddr,
             port_for_send_files: u16,
             port_for_handshake: u16
+        ) -> Self {
+            Self::new_with_optional_handshake_port(addr, port_for_send_files, Some(port_for_handshake))
+        }
+
+        #[doc = "New for [`"]
+        #[doc = stringify!(Self)]
+        #[doc = "`] that uses only one port\n"]
+        #[doc = "Handshake is sent over the connection for sending files.\n"]
+        #[doc = "* `addr` - IP address for bind or connect.\n"]
+        #[doc = "* `port_for_send_files` - port for sending files and handshake.\n"]
+        #[doc = "# Warning!\n"]
+        #[doc = "**Generate by macros**"]
+        pub fn new_single_port(addr: std::net::IpAddr, port_for_send_files: u16) -> Self {
+            Self::new_with_optional_handshake_port(addr, port_for_send_files, None)
+        }
+
+        fn new_with_optional_handshake_port(
+            addr: std::net::IpAddr,
+            port_for_send_files: u16,
+            port_for_handshake: Option<u16>
         ) -> Self {
             Self {
                 config: $name_config {
@@ -105,8 +240,27 @@ macro_rules! generate_new_for_config {
                     port_for_send_files,
                     port_for_handshake,
                     timeout: crate::common::DEFAULT_TIMEOUT,
-                    progress_fn: None
+                    progress_fn: None,
+                    decision_fn: None,
+                    hash_algorithm: Default::default(),
+                    max_chunk_size: crate::common::DEFAULT_BUFFER_SIZE_FOR_NETWORK,
+                    compression: Default::default(),
+                    file_name_policy: Default::default(),
+                    file_name_encoding: Default::default(),
+                    preserve_metadata: Default::default(),
+                    auth_key: None,
+                    signing_key: None,
+                    trusted_signers: Vec::new(),
+                    session_nonce: Vec::new(),
+                    #[cfg(feature = "encryption")]
+                    encryption: Default::default(),
+                    #[cfg(feature = "tls")]
+                    tls: Default::default(),
+                    #[cfg(feature = "quic")]
+                    quic_tls: Default::default(),
                 },
+                negotiated_capabilities: None,
+                $($field: $value,)*
             }
         }
     };
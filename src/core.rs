@@ -1,7 +1,21 @@
 //! Module for **core** object
 
+pub mod capabilities;
+pub mod decision;
+#[cfg(feature = "encryption")]
+pub mod encryption;
+pub mod hash;
+pub mod metadata;
 pub mod progress;
+pub mod signing;
 pub mod traits;
 
+pub use capabilities::*;
+pub use decision::*;
+#[cfg(feature = "encryption")]
+pub use encryption::*;
+pub use hash::*;
+pub use metadata::*;
 pub use progress::*;
+pub use signing::*;
 pub use traits::*;
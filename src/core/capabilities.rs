@@ -0,0 +1,118 @@
+use super::{HashAlgorithm, PreserveMetadata};
+use serde::{Deserialize, Serialize};
+
+/// Codec for compressing data of files
+///
+/// Chosen by [`Sender`](crate::sender::Sender). Hash and [`Progressing`](super::Progressing)
+/// are for the uncompressed data. Small and already compressed files (`.zip`, `.jpg`, ...)
+/// are sent without compression.
+///
+/// Codecs need the features of the same names. Without them, data is sent as is.
+///
+/// # Example
+///
+/// ```
+/// # use snwf::prelude::*;
+/// # use snwf::core::Compression;
+/// #
+/// let mut sender = Sender::new("127.0.0.1".parse().unwrap(), 4324, 6343);
+/// sender.set_compression(Compression::Zstd);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Compression {
+    /// Data is sent as is
+    #[default]
+    None,
+
+    /// [zstd](https://github.com/facebook/zstd). Good ratio. Feature `zstd`
+    Zstd,
+
+    /// [lz4](https://github.com/lz4/lz4). Very fast. Feature `lz4`
+    Lz4,
+}
+
+impl Compression {
+    /// Id of the codec in the binary handshake
+    pub(crate) fn to_id(self) -> u8 {
+        match self {
+            Self::None => 0,
+            Self::Zstd => 1,
+            Self::Lz4 => 2,
+        }
+    }
+
+    /// Codec by id from the binary handshake
+    pub(crate) fn from_id(id: u8) -> Option<Self> {
+        match id {
+            0 => Some(Self::None),
+            1 => Some(Self::Zstd),
+            2 => Some(Self::Lz4),
+            _ => None,
+        }
+    }
+
+    /// Is the feature of the codec enabled?
+    pub(crate) fn is_supported(self) -> bool {
+        match self {
+            Self::None => true,
+            Self::Zstd => cfg!(feature = "zstd"),
+            Self::Lz4 => cfg!(feature = "lz4"),
+        }
+    }
+
+    /// All codecs with enabled features
+    pub(crate) fn supported() -> Vec<Self> {
+        [Self::Zstd, Self::Lz4, Self::None]
+            .into_iter()
+            .filter(|compression| compression.is_supported())
+            .collect()
+    }
+}
+
+/// What [`Sender`](crate::sender::Sender) and [`Recipient`](crate::recipient::Recipient)
+/// agreed on before sending data
+///
+/// Each side advertises what it supports, [`Recipient`](crate::recipient::Recipient)
+/// chooses from the intersection. If there is nothing in common, the transfer is refused.
+///
+/// # Example
+///
+/// ```no_run
+/// # use snwf::prelude::*;
+/// # use std::path::Path;
+/// #
+/// #[tokio::main]
+/// async fn main() {
+///     let mut sender = Sender::new("127.0.0.1".parse().unwrap(), 4324, 6343);
+///
+///     sender.udt_send_file(Path::new("file.txt")).await.unwrap();
+///     println!("{:?}", sender.get_negotiated_capabilities());
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NegotiatedCapabilities {
+    /// Codec for data of files
+    pub compression: Compression,
+
+    /// Algorithm for checking files
+    pub hash_algorithm: HashAlgorithm,
+
+    /// Can an interrupted transfer be resumed?
+    pub resume: bool,
+
+    /// Is the data encrypted?
+    pub encryption: bool,
+
+    /// Are the sides authenticated? See [`auth`](crate::protocol::auth)
+    pub authentication: bool,
+
+    /// Max size of one chunk of data
+    pub max_chunk_size: usize,
+
+    /// Which metadata of files is sent. See [`PreserveMetadata`]
+    pub preserve_metadata: PreserveMetadata,
+
+    /// Random nonce of the session. Chosen by [`Recipient`](crate::recipient::Recipient).
+    /// Signed files are bound to it, so they can't be sent again in other sessions
+    pub nonce: Vec<u8>,
+}
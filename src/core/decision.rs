@@ -0,0 +1,54 @@
+use super::{HashAlgorithm, Signer};
+use std::{cell::RefCell, net::SocketAddr, path::PathBuf, rc::Rc};
+
+/// Info about the file that [`Sender`](crate::sender::Sender) wants to send
+///
+/// The hash is not known yet: it is computed while sending
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IncomingFile {
+    /// Original name of the file. For directories: the path relative to the root, separator `/`
+    pub file_name: String,
+
+    /// Size of the file. For streams: the size hint or `0`
+    pub size: u64,
+
+    /// Algorithm for checking the file
+    pub hash_algorithm: HashAlgorithm,
+
+    /// Address of [`Sender`](crate::sender::Sender)
+    pub peer_addr: SocketAddr,
+
+    /// Verified [`Signer`]. `None` if the file isn't signed
+    pub signer: Option<Signer>,
+}
+
+/// What to do with [`IncomingFile`]
+///
+/// # Example
+///
+/// ```
+/// # use snwf::prelude::*;
+/// # use snwf::core::Decision;
+/// #
+/// let mut recipient = Recipient::new("::0".parse().unwrap(), 4324, 6343);
+/// recipient.set_decision_fn(Some(|incoming: &snwf::core::IncomingFile| {
+///     match incoming.size > 1024 * 1024 * 1024 {
+///         true => Decision::Reject("quota exceeded".to_string()),
+///         false => Decision::Accept,
+///     }
+/// }));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Decision {
+    /// Receive the file
+    Accept,
+
+    /// Don't receive the file. The reason is sent to [`Sender`](crate::sender::Sender)
+    Reject(String),
+
+    /// Receive the file, but save it to other path. Streams can't be redirected: they are rejected
+    Redirect(PathBuf),
+}
+
+/// Alias for simple use FnMut(&[`IncomingFile`]) -> [`Decision`] for struct
+pub type DecisionFn<'a> = Rc<RefCell<Box<dyn FnMut(&IncomingFile) -> Decision + 'a>>>;
@@ -0,0 +1,41 @@
+/// Encryption of the connection for sending files. Feature `encryption`
+///
+/// Data and messages are encrypted with [ChaCha20-Poly1305](https://en.wikipedia.org/wiki/ChaCha20-Poly1305).
+/// The key is made by [X25519](https://en.wikipedia.org/wiki/Curve25519) key exchange for every transfer.
+/// With encryption, the handshake port is not used: the handshake is sent over the encrypted connection.
+///
+/// If the keys of both sides are different, the transfer fails with
+/// [`ProtocolError::AuthenticationFailed`](crate::protocol::error::ProtocolError::AuthenticationFailed)
+///
+/// # Example
+///
+/// ```
+/// # use snwf::prelude::*;
+/// # use snwf::core::Encryption;
+/// #
+/// let mut sender = Sender::new("127.0.0.1".parse().unwrap(), 4324, 6343);
+/// sender.set_encryption(Encryption::PreSharedKey(b"secret key".to_vec()));
+/// ```
+#[derive(Clone, Default, PartialEq, Eq)]
+pub enum Encryption {
+    /// Data is sent as is
+    #[default]
+    None,
+
+    /// Only key exchange. Protects from listening, but not from the man in the middle
+    Ephemeral,
+
+    /// Key exchange with the pre-shared key. Both sides must have the same key
+    PreSharedKey(Vec<u8>),
+}
+
+impl std::fmt::Debug for Encryption {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::None => write!(f, "None"),
+            Self::Ephemeral => write!(f, "Ephemeral"),
+            // Don't print the key
+            Self::PreSharedKey(_) => write!(f, "PreSharedKey(..)"),
+        }
+    }
+}
@@ -0,0 +1,62 @@
+use serde::{Deserialize, Serialize};
+
+/// Algorithm for checking files
+///
+/// [`Sender`](crate::sender::Sender) hashes the file with it and sends the name of the algorithm
+/// in the handshake. So [`Recipient`](crate::recipient::Recipient) verifies with the same one.
+///
+/// # Example
+///
+/// ```
+/// # use snwf::prelude::*;
+/// # use snwf::core::HashAlgorithm;
+/// #
+/// let mut sender = Sender::new("127.0.0.1".parse().unwrap(), 4324, 6343);
+/// sender.set_hash_algorithm(HashAlgorithm::Blake3);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum HashAlgorithm {
+    /// [BLAKE2b-512](https://www.blake2.net/)
+    #[default]
+    Blake2b,
+
+    /// [BLAKE3](https://github.com/BLAKE3-team/BLAKE3). Much faster than [`HashAlgorithm::Blake2b`]
+    Blake3,
+
+    /// [SHA-256](https://en.wikipedia.org/wiki/SHA-2)
+    Sha256,
+
+    /// [xxh3](https://github.com/Cyan4973/xxHash) (128 bit). **Not cryptographic!**
+    /// Only for random errors
+    Xxh3,
+
+    /// Don't check files. Only for trusted networks!
+    ///
+    /// [`Recipient`](crate::recipient::Recipient) accepts it only if it also uses `None`
+    None,
+}
+
+impl HashAlgorithm {
+    /// Id of the algorithm in the binary handshake
+    pub(crate) fn to_id(self) -> u8 {
+        match self {
+            Self::Blake2b => 0,
+            Self::Blake3 => 1,
+            Self::Sha256 => 2,
+            Self::Xxh3 => 3,
+            Self::None => 255,
+        }
+    }
+
+    /// Algorithm by id from the binary handshake
+    pub(crate) fn from_id(id: u8) -> Option<Self> {
+        match id {
+            0 => Some(Self::Blake2b),
+            1 => Some(Self::Blake3),
+            2 => Some(Self::Sha256),
+            3 => Some(Self::Xxh3),
+            255 => Some(Self::None),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,63 @@
+use serde::{Deserialize, Serialize};
+
+/// Which metadata of the received file [`Recipient`](crate::recipient::Recipient) applies
+///
+/// [`Recipient`](crate::recipient::Recipient) asks for it at the negotiation and
+/// [`Sender`](crate::sender::Sender) sends only these fields of the file in the handshake:
+/// Unix mode, times of the last modification and access, owner and group
+/// (numbers and names). It is applied only after the hash check passes.
+/// By default, nothing is applied: the file has default permissions and the current time.
+///
+/// Only for files sent one by one or by the batch. Metadata of other platforms
+/// is ignored: for example, Windows has no mode and owners.
+///
+/// # Example
+///
+/// ```
+/// # use snwf::prelude::*;
+/// # use snwf::core::PreserveMetadata;
+/// #
+/// let mut recipient = Recipient::new("::0".parse().unwrap(), 4324, 6343);
+/// recipient.set_preserve_metadata(PreserveMetadata {
+///     mode: true,
+///     times: true,
+///     ..Default::default()
+/// });
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct PreserveMetadata {
+    /// Permissions (`rwxrwxrwx`). setuid, setgid and sticky bits are never applied
+    pub mode: bool,
+
+    /// Times of the last modification and access
+    pub times: bool,
+
+    /// Owner and group. Names are used, if the recipient has them, otherwise numbers.
+    /// Usually it needs the rights of root
+    pub ownership: bool,
+}
+
+impl PreserveMetadata {
+    /// Apply all metadata
+    pub fn all() -> Self {
+        Self {
+            mode: true,
+            times: true,
+            ownership: true,
+        }
+    }
+
+    /// Is anything applied?
+    pub fn is_any(&self) -> bool {
+        self.mode || self.times || self.ownership
+    }
+
+    /// Fields chosen by both
+    pub(crate) fn intersection(&self, other: &Self) -> Self {
+        Self {
+            mode: self.mode && other.mode,
+            times: self.times && other.times,
+            ownership: self.ownership && other.ownership,
+        }
+    }
+}
@@ -0,0 +1,40 @@
+pub use ed25519_dalek::{SigningKey, VerifyingKey};
+use std::time::SystemTime;
+
+/// [`Sender`](crate::sender::Sender) that signed the received file with
+/// [Ed25519](https://en.wikipedia.org/wiki/EdDSA#Ed25519)
+///
+/// The handshake is signed: hash, size and name of the file and the time of signing.
+/// [`Recipient`](crate::recipient::Recipient) checks the signature before the data and the hash
+/// after it. If [`Recipient`](crate::recipient::Recipient) has trusted signers,
+/// unsigned files and files of other signers are rejected with
+/// [`ProtocolError::Untrusted`](crate::protocol::error::ProtocolError::Untrusted)
+///
+/// Only files sent one by one or by the batch are signed. Directories, streams and rsync
+/// are not signed: with trusted signers, they are rejected.
+///
+/// # Example
+///
+/// ```no_run
+/// # use snwf::prelude::*;
+/// # use snwf::core::SigningKey;
+/// # use std::path::Path;
+/// #
+/// #[tokio::main]
+/// async fn main() {
+///     let signing_key = SigningKey::from_bytes(&[7; 32]);
+///     let mut recipient = Recipient::new("::0".parse().unwrap(), 4324, 6343);
+///     recipient.add_trusted_signer(signing_key.verifying_key());
+///
+///     recipient.udt_recv_file(Path::new("file.txt")).await.unwrap();
+///     println!("{:?}", recipient.get_signer());
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Signer {
+    /// Public key of [`Sender`](crate::sender::Sender)
+    pub public_key: VerifyingKey,
+
+    /// When the handshake was signed. Precision: seconds
+    pub timestamp: SystemTime,
+}
@@ -1,4 +1,4 @@
-use super::Progressing;
+use super::{Compression, HashAlgorithm, PreserveMetadata, Progressing};
 use std::{net::IpAddr, time::Duration};
 
 /// Trait for config
@@ -12,11 +12,39 @@ pub trait CoreConfig {
     fn get_port_for_send_files(&self) -> u16;
 
     /// Get handshake port. The [`crate::protocol`] does not use it
-    fn get_port_for_handshake(&self) -> u16;
+    ///
+    /// If `None`, handshake is sent over the connection for sending files
+    fn get_port_for_handshake(&self) -> Option<u16>;
 
     /// Get timeout for getting error
     fn get_timeout(&self) -> Duration;
 
+    /// Get algorithm for checking files
+    fn get_hash_algorithm(&self) -> HashAlgorithm;
+
+    /// Get max size of one chunk of data
+    fn get_max_chunk_size(&self) -> usize;
+
+    /// Get codec for data of files
+    fn get_compression(&self) -> Compression;
+
+    /// Get shared secret for authentication of the other side
+    fn get_auth_key(&self) -> Option<&[u8]>;
+
+    /// Get which metadata of files is applied. After the negotiation: which metadata is sent
+    fn get_preserve_metadata(&self) -> PreserveMetadata;
+
+    /// Get random nonce of the negotiated session. Empty before the negotiation
+    fn get_session_nonce(&self) -> &[u8];
+
+    /// Get encryption of the connection
+    #[cfg(feature = "encryption")]
+    fn get_encryption(&self) -> &super::Encryption;
+
+    /// Get TLS settings for TCP connections
+    #[cfg(feature = "tls")]
+    fn get_tls(&self) -> &crate::protocol::tls::TlsConfig;
+
     /// Run callback
     ///
     /// Callback to check the progress of the operation
@@ -11,7 +11,12 @@
 //! # Features
 //!
 //! * **udt** - [udt](crate::protocol::udt) protocol
+//! * **tcp** - [tcp](crate::protocol::tcp) protocol
+//! * **quic** - [quic](crate::protocol::quic) protocol
 //! * **rsync** - [rsync](crate::protocol::rsync) for sync files
+//! * **zstd**, **lz4** - [compression](crate::core::Compression) of data
+//! * **encryption** - [encryption](crate::core::Encryption) of the connection
+//! * **tls** - [TLS](crate::protocol::tls) for TCP connections
 //! * [Callback function](crate::core::Progressing)
 //! * Use `#![forbid(unsafe_code)]`
 //!
@@ -1,10 +1,20 @@
 //! Prelude
 
+pub use crate::protocol::control::ControlRecipient;
 pub use crate::recipient::*;
 pub use crate::sender::*;
 
 #[cfg(feature = "udt")]
 pub use crate::protocol::udt::{UdtRecipient, UdtSender};
 
+#[cfg(feature = "tcp")]
+pub use crate::protocol::tcp::{TcpRecipient, TcpSender};
+
+#[cfg(feature = "quic")]
+pub use crate::protocol::quic::{QuicRecipient, QuicSender};
+
+#[cfg(feature = "tls")]
+pub use crate::protocol::tls::{TlsRecipient, TlsSender};
+
 #[cfg(feature = "rsync")]
 pub use crate::protocol::rsync::{RSyncRecipient, RSyncSender};
@@ -1,10 +1,28 @@
 //! Implementation of all protocols
 
+pub mod auth;
+pub(crate) mod connection;
+pub mod control;
+#[cfg(feature = "encryption")]
+pub(crate) mod encryption;
 pub mod error;
 pub mod handshake;
+pub(crate) mod manifest;
+pub(crate) mod metadata;
+pub(crate) mod raw;
+pub(crate) mod signing;
 
 #[cfg(feature = "udt")]
 pub mod udt;
 
+#[cfg(feature = "tcp")]
+pub mod tcp;
+
+#[cfg(feature = "quic")]
+pub mod quic;
+
 #[cfg(feature = "rsync")]
 pub mod rsync;
+
+#[cfg(feature = "tls")]
+pub mod tls;
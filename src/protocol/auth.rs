@@ -0,0 +1,206 @@
+//! Authentication - check that the other side knows the shared secret
+//!
+//! # Description
+//!
+//! Without it, anybody who can connect to [`Recipient`](crate::recipient::Recipient)
+//! can send a file to it. If any side has the secret (see `set_auth_key` of
+//! [`CoreSender`](crate::sender::CoreSender) and [`CoreRecipient`](crate::recipient::CoreRecipient)),
+//! after the negotiation (and the key exchange, if the connection is encrypted):
+//!
+//! 1. Each side sends `AuthChallenge`: random nonce (32 bytes)
+//! 2. [`Sender`](crate::sender::Sender) sends `AuthResponse`: HMAC-SHA256 of both nonces
+//!    and the negotiated capabilities
+//! 3. [`Recipient`](crate::recipient::Recipient) checks it and answers with `AuthAnswer`:
+//!    rejected or accepted with its own HMAC, which [`Sender`](crate::sender::Sender) checks
+//!
+//! If the secret is different or one side doesn't have it, both sides get
+//! [`ProtocolError::Unauthorized`]. The secret itself is never sent.
+//!
+//! Then the handshake and all next messages are sent over the connection for sending files,
+//! also if there is a port for the handshake: anybody can connect to it.
+//!
+//! **Data is not protected!** To protect it on the way, use encryption too.
+//!
+//! # Example
+//!
+//! ```no_run
+//! # use snwf::prelude::*;
+//! # use std::path::Path;
+//! #
+//! #[tokio::main]
+//! async fn main() {
+//!     let mut recipient = Recipient::new("::0".parse().unwrap(), 4324, 6343);
+//!     recipient.set_auth_key(Some(b"shared secret".to_vec()));
+//!
+//!     recipient.udt_recv_file(Path::new("file.txt")).await.unwrap();
+//! }
+//! ```
+
+use super::{
+    connection::DataConnection,
+    error::ProtocolError,
+    handshake::{recv_message, send_message, HandshakeError},
+};
+use crate::{common::timeout, core::NegotiatedCapabilities};
+use hmac::{Hmac, Mac};
+use log::debug;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::time::Duration;
+
+/// Size of the random nonce of [`AuthChallenge`]
+const NONCE_SIZE: usize = 32;
+
+/// Label of HMAC of [`Sender`](crate::sender::Sender)
+const SENDER_LABEL: &[u8] = b"snwf auth sender";
+
+/// Label of HMAC of [`Recipient`](crate::recipient::Recipient)
+const RECIPIENT_LABEL: &[u8] = b"snwf auth recipient";
+
+/// Random nonce. The other side must prove the secret with it
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub(crate) struct AuthChallenge {
+    pub(crate) nonce: Vec<u8>,
+}
+
+/// HMAC of [`Sender`](crate::sender::Sender). Empty, if it has no secret
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub(crate) struct AuthResponse {
+    pub(crate) mac: Vec<u8>,
+}
+
+/// Answer of [`Recipient`](crate::recipient::Recipient) for [`AuthResponse`]
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub(crate) enum AuthAnswer {
+    /// HMAC of [`Recipient`](crate::recipient::Recipient)
+    Accepted(Vec<u8>),
+
+    Rejected,
+}
+
+/// HMAC for `label` over the nonce of the checking side, the nonce of the proving side
+/// and the negotiated capabilities
+fn get_mac(
+    key: &[u8],
+    label: &[u8],
+    checking_nonce: &[u8],
+    proving_nonce: &[u8],
+    negotiated: &NegotiatedCapabilities,
+) -> Result<Hmac<Sha256>, ProtocolError> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC takes key of any size");
+    mac.update(label);
+    mac.update(checking_nonce);
+    mac.update(proving_nonce);
+    mac.update(&serde_json::to_vec(negotiated).map_err(HandshakeError::from)?);
+
+    Ok(mac)
+}
+
+/// Send [`AuthChallenge`] and receive the challenge of the other side
+///
+/// Returns both nonces: own and of the other side
+async fn exchange_challenges(
+    connection: &mut impl DataConnection,
+    timeout: Duration,
+) -> Result<(Vec<u8>, Vec<u8>), ProtocolError> {
+    let mut nonce = vec![0u8; NONCE_SIZE];
+    getrandom::getrandom(&mut nonce).map_err(|e| ProtocolError::FileIO(e.into()))?;
+
+    send_message(
+        &AuthChallenge {
+            nonce: nonce.clone(),
+        },
+        connection,
+    )
+    .await?;
+
+    let challenge: AuthChallenge = timeout!(
+        recv_message(connection),
+        |_| { ProtocolError::TimeoutExpired },
+        timeout
+    )??;
+    Ok((nonce, challenge.nonce))
+}
+
+/// Prove the secret to [`Recipient`](crate::recipient::Recipient) and check its answer
+pub(crate) async fn authenticate_for_sender(
+    connection: &mut impl DataConnection,
+    key: Option<&[u8]>,
+    negotiated: &NegotiatedCapabilities,
+    timeout: Duration,
+) -> Result<(), ProtocolError> {
+    let (nonce, recipient_nonce) = exchange_challenges(connection, timeout).await?;
+
+    let mac = match key {
+        Some(key) => get_mac(key, SENDER_LABEL, &recipient_nonce, &nonce, negotiated)?
+            .finalize()
+            .into_bytes()
+            .to_vec(),
+        None => Vec::new(),
+    };
+    send_message(&AuthResponse { mac }, connection).await?;
+
+    let answer: AuthAnswer = timeout!(
+        recv_message(connection),
+        |_| { ProtocolError::TimeoutExpired },
+        timeout
+    )??;
+
+    match (answer, key) {
+        (AuthAnswer::Accepted(recipient_mac), Some(key)) => {
+            get_mac(key, RECIPIENT_LABEL, &nonce, &recipient_nonce, negotiated)?
+                .verify_slice(&recipient_mac)
+                .map_err(|_| {
+                    debug!("recipient has other secret");
+                    ProtocolError::Unauthorized
+                })?;
+            debug!("done authentication");
+
+            Ok(())
+        }
+        (answer, _) => {
+            debug!("unauthorized. Answer of recipient: {:?}", answer);
+            Err(ProtocolError::Unauthorized)
+        }
+    }
+}
+
+/// Check the secret of [`Sender`](crate::sender::Sender) and prove own secret
+pub(crate) async fn authenticate_for_recipient(
+    connection: &mut impl DataConnection,
+    key: Option<&[u8]>,
+    negotiated: &NegotiatedCapabilities,
+    timeout: Duration,
+) -> Result<(), ProtocolError> {
+    let (nonce, sender_nonce) = exchange_challenges(connection, timeout).await?;
+
+    let response: AuthResponse = timeout!(
+        recv_message(connection),
+        |_| { ProtocolError::TimeoutExpired },
+        timeout
+    )??;
+
+    let key = match key {
+        Some(key)
+            if get_mac(key, SENDER_LABEL, &nonce, &sender_nonce, negotiated)?
+                .verify_slice(&response.mac)
+                .is_ok() =>
+        {
+            key
+        }
+        _ => {
+            debug!("unauthorized sender. Have secret: {}", key.is_some());
+            send_message(&AuthAnswer::Rejected, connection).await?;
+            return Err(ProtocolError::Unauthorized);
+        }
+    };
+
+    let mac = get_mac(key, RECIPIENT_LABEL, &sender_nonce, &nonce, negotiated)?
+        .finalize()
+        .into_bytes()
+        .to_vec();
+    send_message(&AuthAnswer::Accepted(mac), connection).await?;
+    debug!("done authentication");
+
+    Ok(())
+}
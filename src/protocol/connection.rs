@@ -0,0 +1,314 @@
+//! Connection for sending data in [`crate::protocol`]
+
+#[cfg(feature = "encryption")]
+use super::encryption::EncryptedConnection;
+use super::error::ProtocolError;
+use crate::core::CoreConfig;
+use async_trait::async_trait;
+#[cfg(feature = "tls")]
+use std::future::Future;
+use std::{
+    io,
+    net::SocketAddr,
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadBuf},
+    net::{TcpListener, TcpStream, ToSocketAddrs},
+};
+
+/// Connection that transfers file data
+///
+/// Lets [`raw`](crate::protocol::raw) work the same way for every transport
+#[async_trait(?Send)]
+pub(crate) trait DataConnection {
+    /// Send all bytes from `buf`
+    async fn send_data(&mut self, buf: &[u8]) -> std::io::Result<()>;
+
+    /// Receive bytes to `buf`. Returns `0` if the connection is closed
+    async fn recv_data(&mut self, buf: &mut [u8]) -> std::io::Result<usize>;
+
+    /// Receive exactly `buf.len()` bytes
+    async fn recv_exact(&mut self, buf: &mut [u8]) -> std::io::Result<()> {
+        let mut done_bytes = 0;
+
+        while done_bytes < buf.len() {
+            let len = self.recv_data(&mut buf[done_bytes..]).await?;
+
+            if len == 0 {
+                return Err(std::io::ErrorKind::UnexpectedEof.into());
+            }
+
+            done_bytes += len;
+        }
+
+        Ok(())
+    }
+
+    /// Is the data encrypted or the other side authenticated? Then the handshake port is not used:
+    /// anybody can connect to it
+    fn is_secure(&self) -> bool {
+        false
+    }
+}
+
+#[async_trait(?Send)]
+impl<T: DataConnection + ?Sized> DataConnection for &mut T {
+    async fn send_data(&mut self, buf: &[u8]) -> std::io::Result<()> {
+        (**self).send_data(buf).await
+    }
+
+    async fn recv_data(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        (**self).recv_data(buf).await
+    }
+
+    async fn recv_exact(&mut self, buf: &mut [u8]) -> std::io::Result<()> {
+        (**self).recv_exact(buf).await
+    }
+
+    fn is_secure(&self) -> bool {
+        (**self).is_secure()
+    }
+}
+
+/// Connection after the negotiation. Encrypted and authenticated, if it is agreed
+pub(crate) enum SecureConnection<C> {
+    Plain(C),
+
+    /// Not encrypted, but the other side is authenticated. See [`auth`](super::auth)
+    Authenticated(C),
+
+    #[cfg(feature = "encryption")]
+    Encrypted(Box<EncryptedConnection<C>>),
+}
+
+impl<C> SecureConnection<C> {
+    /// Mark that the other side is authenticated
+    pub(crate) fn into_authenticated(self) -> Self {
+        match self {
+            Self::Plain(connection) => Self::Authenticated(connection),
+            connection => connection,
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl<C: DataConnection> DataConnection for SecureConnection<C> {
+    async fn send_data(&mut self, buf: &[u8]) -> std::io::Result<()> {
+        match self {
+            Self::Plain(connection) | Self::Authenticated(connection) => {
+                connection.send_data(buf).await
+            }
+            #[cfg(feature = "encryption")]
+            Self::Encrypted(connection) => connection.send_data(buf).await,
+        }
+    }
+
+    async fn recv_data(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            Self::Plain(connection) | Self::Authenticated(connection) => {
+                connection.recv_data(buf).await
+            }
+            #[cfg(feature = "encryption")]
+            Self::Encrypted(connection) => connection.recv_data(buf).await,
+        }
+    }
+
+    fn is_secure(&self) -> bool {
+        match self {
+            Self::Plain(connection) => connection.is_secure(),
+            Self::Authenticated(_) => true,
+            #[cfg(feature = "encryption")]
+            Self::Encrypted(_) => true,
+        }
+    }
+}
+
+/// TCP connection. Over TLS, if it is set in the config (see [`tls`](crate::protocol::tls))
+///
+/// The TLS handshake is done at the first reading or writing, so the other side
+/// may accept the connection later. Its errors are errors of reading or writing
+pub(crate) enum TcpConnection {
+    Plain(TcpStream),
+
+    #[cfg(feature = "tls")]
+    Connecting(Pin<Box<tokio_rustls::Connect<TcpStream>>>),
+
+    #[cfg(feature = "tls")]
+    Accepting(Pin<Box<tokio_rustls::Accept<TcpStream>>>),
+
+    #[cfg(feature = "tls")]
+    Tls(Box<tokio_rustls::TlsStream<TcpStream>>),
+}
+
+/// [`AsyncRead`] + [`AsyncWrite`]
+trait AsyncStream: AsyncRead + AsyncWrite + Unpin {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin> AsyncStream for T {}
+
+impl TcpConnection {
+    /// Connect to `addr`
+    #[cfg_attr(not(feature = "tls"), allow(unused_variables))]
+    pub(crate) async fn connect(
+        addr: impl ToSocketAddrs,
+        config: &impl CoreConfig,
+    ) -> Result<Self, ProtocolError> {
+        let stream = TcpStream::connect(addr)
+            .await
+            .map_err(ProtocolError::Connect)?;
+
+        #[cfg(feature = "tls")]
+        if let Some((connector, server_name)) = super::tls::get_connector(config.get_tls())? {
+            return Ok(Self::Connecting(Box::pin(
+                connector.connect(server_name, stream),
+            )));
+        }
+
+        Ok(Self::Plain(stream))
+    }
+
+    /// Finish the TLS handshake now, not at the first reading or writing.
+    /// Then the side that listens knows the result before the transfer
+    #[cfg(feature = "udt")]
+    pub(crate) async fn finish_tls_handshake(&mut self) -> io::Result<()> {
+        std::future::poll_fn(|cx| self.poll_stream(cx).map_ok(|_| ())).await
+    }
+
+    /// Stream for reading and writing. Finishes the TLS handshake first
+    #[cfg_attr(not(feature = "tls"), allow(unused_variables))]
+    fn poll_stream(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<Pin<&mut dyn AsyncStream>>> {
+        #[cfg(feature = "tls")]
+        {
+            let stream: Option<tokio_rustls::TlsStream<TcpStream>> = match self {
+                Self::Connecting(connect) => Some(ready!(connect.as_mut().poll(cx))?.into()),
+                Self::Accepting(accept) => Some(ready!(accept.as_mut().poll(cx))?.into()),
+                _ => None,
+            };
+
+            if let Some(stream) = stream {
+                *self = Self::Tls(Box::new(stream));
+            }
+        }
+
+        let stream: Pin<&mut dyn AsyncStream> = match self {
+            Self::Plain(stream) => Pin::new(stream),
+            #[cfg(feature = "tls")]
+            Self::Tls(stream) => Pin::new(stream.as_mut()),
+            #[cfg(feature = "tls")]
+            _ => unreachable!("the TLS handshake is done"),
+        };
+
+        Poll::Ready(Ok(stream))
+    }
+}
+
+impl AsyncRead for TcpConnection {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        ready!(self.get_mut().poll_stream(cx))?.poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for TcpConnection {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        ready!(self.get_mut().poll_stream(cx))?.poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        ready!(self.get_mut().poll_stream(cx))?.poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        ready!(self.get_mut().poll_stream(cx))?.poll_shutdown(cx)
+    }
+}
+
+#[async_trait(?Send)]
+impl DataConnection for TcpConnection {
+    async fn send_data(&mut self, buf: &[u8]) -> std::io::Result<()> {
+        self.write_all(buf).await?;
+        // TLS buffers the data
+        self.flush().await
+    }
+
+    async fn recv_data(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.read(buf).await
+    }
+}
+
+/// Listener of [`TcpConnection`]. Accepts TLS, if there is a certificate in the config
+pub(crate) struct TcpConnectionListener {
+    listener: TcpListener,
+
+    #[cfg(feature = "tls")]
+    acceptor: Option<tokio_rustls::TlsAcceptor>,
+}
+
+impl TcpConnectionListener {
+    #[cfg_attr(not(feature = "tls"), allow(unused_variables))]
+    pub(crate) async fn bind(
+        addr: impl ToSocketAddrs,
+        config: &impl CoreConfig,
+    ) -> Result<Self, ProtocolError> {
+        Ok(Self {
+            listener: TcpListener::bind(addr).await.map_err(ProtocolError::Bind)?,
+            #[cfg(feature = "tls")]
+            acceptor: super::tls::get_acceptor(config.get_tls())?,
+        })
+    }
+
+    pub(crate) async fn accept(&self) -> io::Result<(TcpConnection, SocketAddr)> {
+        let (stream, addr) = self.listener.accept().await?;
+
+        #[cfg(feature = "tls")]
+        if let Some(acceptor) = self.acceptor.as_ref() {
+            let accept = Box::pin(acceptor.accept(stream));
+            return Ok((TcpConnection::Accepting(accept), addr));
+        }
+
+        Ok((TcpConnection::Plain(stream), addr))
+    }
+}
+
+impl From<TcpListener> for TcpConnectionListener {
+    /// Listener without TLS
+    fn from(listener: TcpListener) -> Self {
+        Self {
+            listener,
+            #[cfg(feature = "tls")]
+            acceptor: None,
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl DataConnection for TcpStream {
+    async fn send_data(&mut self, buf: &[u8]) -> std::io::Result<()> {
+        self.write_all(buf).await
+    }
+
+    async fn recv_data(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.read(buf).await
+    }
+}
+
+#[cfg(feature = "udt")]
+#[async_trait(?Send)]
+impl DataConnection for tokio_udt::UdtConnection {
+    async fn send_data(&mut self, buf: &[u8]) -> std::io::Result<()> {
+        self.send(buf).await?;
+        Ok(())
+    }
+
+    async fn recv_data(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.recv(buf).await
+    }
+}
@@ -0,0 +1,623 @@
+//! Control - ask what is on the other side without sending files
+//!
+//! # Description
+//!
+//! [`ControlServer`] answers requests about files of the exported root:
+//!
+//! * `list_dir` - entries of the directory
+//! * `stat` - info about the file or directory
+//! * `hash` - info about the file with the hash of its data
+//!
+//! Requests are sent over TCP to the port for handshakes. If there is no port for handshakes,
+//! TCP port with the number of the port for sending files is used.
+//! Many requests can be sent over one connection.
+//!
+//! The connection is secured like the transfer of files: TLS of the config, then the negotiation
+//! with encryption and authentication (see [`auth`](crate::protocol::auth)), if the config has them.
+//! The server closes the connection if there is no request during the timeout of its config.
+//!
+//! * Format: [json](https://github.com/serde-rs/json)
+//! * Sent as: size of json (u32 big endian) + json
+//! * Answer: [`Entry`] for every entry, then `Done`. Or `Failed` with the reason
+//!
+//! Paths are relative to the root. Separator: `/`. Empty path - the root.
+//!
+//! # Example
+//!
+//! ```no_run
+//! # use snwf::prelude::*;
+//! #
+//! #[tokio::main]
+//! async fn main() {
+//!     let mut recipient = Recipient::new_single_port("127.0.0.1".parse().unwrap(), 4324);
+//!
+//!     for entry in recipient.list_dir("backups").await.unwrap() {
+//!         println!("{}: {} bytes", entry.name, entry.size);
+//!     }
+//! }
+//! ```
+
+use super::{
+    connection::{TcpConnection, TcpConnectionListener},
+    error::ProtocolError,
+    handshake::{get_hash_of_file, recv_message, send_message, HandshakeError},
+    manifest::get_exported_path,
+    raw::{negotiate_for_recipient, negotiate_for_sender},
+};
+use crate::{
+    common::timeout,
+    core::{CoreConfig, HashAlgorithm},
+    prelude::*,
+};
+use async_trait::async_trait;
+use log::debug;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::Metadata,
+    future::Future,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+use tokio::{
+    fs::{metadata, read_dir, symlink_metadata},
+    task::{JoinSet, LocalSet},
+};
+
+/// Type of [`Entry`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EntryType {
+    File,
+    Dir,
+
+    /// Symbolic link. Only in [`ControlRecipient::list_dir`]: it is not followed
+    Symlink,
+
+    /// Socket, pipe, device and etc.
+    Other,
+}
+
+/// Info about a file or directory of [`ControlServer`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Entry {
+    /// Name without the path. Empty for the root
+    pub name: String,
+
+    pub entry_type: EntryType,
+
+    /// Size in bytes
+    pub size: u64,
+
+    /// Time of the last modification
+    pub mtime: SystemTime,
+
+    /// Hash of the data. Only from [`ControlRecipient::hash`]
+    ///
+    /// Algorithm is [`hash_algorithm`](Entry::hash_algorithm)
+    pub hash: Option<String>,
+
+    /// Algorithm of [`hash`](Entry::hash). Set by the config of [`ControlServer`]
+    pub hash_algorithm: HashAlgorithm,
+}
+
+/// Request to [`ControlServer`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) enum ControlRequest {
+    ListDir(String),
+    Stat(String),
+    Hash(String),
+}
+
+/// Answer of [`ControlServer`]. One request can get many answers
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) enum ControlAnswer {
+    Entry(Entry),
+
+    /// All entries are sent
+    Done,
+
+    Failed(String),
+}
+
+/// Port for control requests. See [module](crate::protocol::control)
+fn get_control_port(config: &impl CoreConfig) -> u16 {
+    config
+        .get_port_for_handshake()
+        .unwrap_or(config.get_port_for_send_files())
+}
+
+/// Answers control requests about files of the exported root until shutdown
+///
+/// Does not send files. For it, use [`SenderServer`](crate::protocol::udt::SenderServer)
+/// with the same root. Connections are served concurrently on local tokio tasks,
+/// so [`ControlServer::run`] must be awaited, not spawned.
+///
+/// # Example
+/// ```no_run
+/// # use snwf::prelude::*;
+/// # use snwf::protocol::control::ControlServer;
+/// #
+/// #[tokio::main]
+/// async fn main() {
+///     let sender = Sender::new_single_port("::0".parse().unwrap(), 4324);
+///     let server = ControlServer::new(sender.get_config(), "/home/gladi/Documents");
+///
+///     let shutdown = async {
+///         tokio::signal::ctrl_c().await.unwrap();
+///     };
+///
+///     server.run(shutdown).await.unwrap();
+/// }
+/// ```
+pub struct ControlServer {
+    config: ConfigSender<'static>,
+    root: PathBuf,
+}
+
+impl ControlServer {
+    /// New server
+    ///
+    /// * `config` - config of [`Sender`](crate::sender::Sender). Address and port for bind, hash algorithm,
+    ///   TLS, encryption, the key of authentication and the timeout for idle connections.
+    /// * `root` - exported folder.
+    pub fn new<P: AsRef<Path>>(config: ConfigSender<'static>, root: P) -> Self {
+        Self {
+            config,
+            root: root.as_ref().to_path_buf(),
+        }
+    }
+
+    /// Answer requests until `shutdown` is done
+    ///
+    /// After `shutdown`, new connections are not accepted, but the open connections are served
+    pub async fn run<F>(self, shutdown: F) -> Result<(), ProtocolError>
+    where
+        F: Future<Output = ()>,
+    {
+        debug!("running ControlServer; config: {:?}", self.config);
+
+        // Sync, so the socket is bound at the first poll
+        let root = std::fs::canonicalize(&self.root).map_err(ProtocolError::FileIO)?;
+        let listener = TcpConnectionListener::bind(
+            (self.config.addr, get_control_port(&self.config)),
+            &self.config,
+        )
+        .await?;
+
+        LocalSet::new()
+            .run_until(async {
+                let mut connections = JoinSet::new();
+                tokio::pin!(shutdown);
+
+                loop {
+                    let (socket, addr) = tokio::select! {
+                        _ = &mut shutdown => break,
+                        accepted = listener.accept() => accepted.map_err(ProtocolError::Accept)?,
+                    };
+                    debug!("accepted control connection from {}", addr);
+
+                    let root = root.clone();
+                    let config = self.config.clone();
+
+                    connections.spawn_local(async move {
+                        if let Err(error) = serve_connection(socket, config, &root).await {
+                            debug!("control connection from {} failed: {:?}", addr, error);
+                        }
+                    });
+
+                    // Forget closed connections
+                    while connections.try_join_next().is_some() {}
+                }
+
+                debug!("shutdown. Waiting for {} connections", connections.len());
+                while connections.join_next().await.is_some() {}
+
+                Ok(())
+            })
+            .await
+    }
+}
+
+/// Answer requests until the other side closes the connection or is idle during the timeout
+async fn serve_connection(
+    socket: TcpConnection,
+    mut config: ConfigSender<'_>,
+    root: &Path,
+) -> Result<(), ProtocolError> {
+    let (_, mut connection) = negotiate_for_sender(socket, &mut config).await?;
+    let hash_algorithm = config.hash_algorithm;
+
+    loop {
+        let request: ControlRequest = match timeout!(
+            recv_message(&mut connection),
+            |_| ProtocolError::TimeoutExpired,
+            config.timeout
+        )? {
+            Ok(request) => request,
+            Err(HandshakeError::IO(e)) if e.kind() == ErrorKind::UnexpectedEof => return Ok(()),
+            Err(e) => return Err(e.into()),
+        };
+        debug!("control request: {:?}", request);
+
+        let answers = match get_answers(request, root, hash_algorithm).await {
+            Ok(answers) => answers,
+            Err(reason) => vec![ControlAnswer::Failed(reason)],
+        };
+
+        for answer in answers.iter() {
+            send_message(answer, &mut connection).await?;
+        }
+    }
+}
+
+async fn get_answers(
+    request: ControlRequest,
+    root: &Path,
+    hash_algorithm: HashAlgorithm,
+) -> Result<Vec<ControlAnswer>, String> {
+    let mut answers = Vec::new();
+
+    match request {
+        ControlRequest::ListDir(remote_path) => {
+            let path = get_exported_path(root, &remote_path).await?;
+            let mut reader = read_dir(&path)
+                .await
+                .map_err(|_| format!("not a directory: {}", remote_path))?;
+
+            while let Some(entry) = reader.next_entry().await.map_err(|e| e.to_string())? {
+                let metadata = symlink_metadata(entry.path())
+                    .await
+                    .map_err(|e| e.to_string())?;
+                let name = entry.file_name().to_string_lossy().to_string();
+
+                answers.push(ControlAnswer::Entry(get_entry(
+                    name,
+                    &metadata,
+                    hash_algorithm,
+                )?));
+            }
+        }
+        ControlRequest::Stat(remote_path) => {
+            let (path, name) = get_path_and_name(root, &remote_path).await?;
+            let metadata = metadata(&path).await.map_err(|e| e.to_string())?;
+
+            answers.push(ControlAnswer::Entry(get_entry(
+                name,
+                &metadata,
+                hash_algorithm,
+            )?));
+        }
+        ControlRequest::Hash(remote_path) => {
+            let (path, name) = get_path_and_name(root, &remote_path).await?;
+            let metadata = metadata(&path).await.map_err(|e| e.to_string())?;
+
+            if !metadata.is_file() {
+                return Err(format!("not a file: {}", remote_path));
+            }
+
+            let mut entry = get_entry(name, &metadata, hash_algorithm)?;
+            entry.hash = Some(
+                get_hash_of_file(&path, hash_algorithm)
+                    .await
+                    .map_err(|e| e.to_string())?,
+            );
+
+            answers.push(ControlAnswer::Entry(entry));
+        }
+    }
+
+    answers.push(ControlAnswer::Done);
+    Ok(answers)
+}
+
+/// Exported path and name of `remote_path`
+async fn get_path_and_name(root: &Path, remote_path: &str) -> Result<(PathBuf, String), String> {
+    let path = get_exported_path(root, remote_path).await?;
+    let name = remote_path
+        .rsplit('/')
+        .next()
+        .unwrap_or_default()
+        .to_string();
+
+    Ok((path, name))
+}
+
+fn get_entry(
+    name: String,
+    metadata: &Metadata,
+    hash_algorithm: HashAlgorithm,
+) -> Result<Entry, String> {
+    let file_type = metadata.file_type();
+    let entry_type = if file_type.is_symlink() {
+        EntryType::Symlink
+    } else if file_type.is_dir() {
+        EntryType::Dir
+    } else if file_type.is_file() {
+        EntryType::File
+    } else {
+        EntryType::Other
+    };
+
+    Ok(Entry {
+        name,
+        entry_type,
+        size: metadata.len(),
+        mtime: metadata.modified().map_err(|e| e.to_string())?,
+        hash: None,
+        hash_algorithm,
+    })
+}
+
+/// Control requests to [`ControlServer`] for [`CoreRecipient`]
+///
+/// Every request uses a new connection. Errors of the other side are [`ProtocolError::Rejected`]
+#[async_trait(?Send)]
+pub trait ControlRecipient<'a>: CoreRecipient<'a> {
+    /// Entries of the directory `path`. Without `.` and `..`, in any order
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use snwf::prelude::*;
+    /// #
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut recipient = Recipient::new("127.0.0.1".parse().unwrap(), 4324, 6343);
+    ///
+    ///     let entries = recipient.list_dir("").await.unwrap();
+    /// }
+    /// ```
+    async fn list_dir(&mut self, path: &str) -> Result<Vec<Entry>, ProtocolError>;
+
+    /// Info about the file or directory `path`. Symbolic links are followed
+    async fn stat(&mut self, path: &str) -> Result<Entry, ProtocolError>;
+
+    /// Info about the file `path` with the hash of its data
+    ///
+    /// The file is read fully by the other side. For big files, it takes time:
+    /// the request is without timeout
+    async fn hash(&mut self, path: &str) -> Result<Entry, ProtocolError>;
+}
+
+/// Send `request` and receive all answers
+async fn request_entries(
+    config: &ConfigRecipient<'_>,
+    request: ControlRequest,
+    with_timeout: bool,
+) -> Result<Vec<Entry>, ProtocolError> {
+    debug!("control request: {:?}; config: {:?}", request, config);
+
+    let socket = timeout!(
+        TcpConnection::connect((config.addr, get_control_port(config)), config),
+        |_| ProtocolError::TimeoutExpired,
+        config.timeout
+    )??;
+
+    let mut config = config.clone();
+    let (_, mut connection) = negotiate_for_recipient(socket, &mut config).await?;
+    send_message(&request, &mut connection).await?;
+
+    let mut entries = Vec::new();
+
+    loop {
+        let answer = match with_timeout {
+            true => timeout!(
+                recv_message(&mut connection),
+                |_| ProtocolError::TimeoutExpired,
+                config.timeout
+            )?,
+            false => recv_message(&mut connection).await,
+        }?;
+
+        match answer {
+            ControlAnswer::Entry(entry) => entries.push(entry),
+            ControlAnswer::Done => return Ok(entries),
+            ControlAnswer::Failed(reason) => return Err(ProtocolError::Rejected(reason)),
+        }
+    }
+}
+
+/// The first entry of the answer
+fn get_single_entry(entries: Vec<Entry>) -> Result<Entry, ProtocolError> {
+    entries
+        .into_iter()
+        .next()
+        .ok_or_else(|| ProtocolError::Handshake(HandshakeError::Assert("empty answer".to_string())))
+}
+
+#[async_trait(?Send)]
+impl<'a> ControlRecipient<'a> for Recipient<'a> {
+    async fn list_dir(&mut self, path: &str) -> Result<Vec<Entry>, ProtocolError> {
+        request_entries(
+            &self.config,
+            ControlRequest::ListDir(path.to_string()),
+            true,
+        )
+        .await
+    }
+
+    async fn stat(&mut self, path: &str) -> Result<Entry, ProtocolError> {
+        get_single_entry(
+            request_entries(&self.config, ControlRequest::Stat(path.to_string()), true).await?,
+        )
+    }
+
+    async fn hash(&mut self, path: &str) -> Result<Entry, ProtocolError> {
+        get_single_entry(
+            request_entries(&self.config, ControlRequest::Hash(path.to_string()), false).await?,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::Hasher;
+
+    #[tokio::test]
+    async fn control_requests() {
+        crate::init_logger_for_test();
+
+        let root = assert_fs::TempDir::new().unwrap();
+        std::fs::create_dir(root.join("dir")).unwrap();
+        std::fs::write(root.join("dir").join("file.txt"), b"test data").unwrap();
+        std::fs::create_dir(root.join("dir").join("sub")).unwrap();
+
+        let sender = Sender::new_single_port("::0".parse().unwrap(), 3304);
+        let server = ControlServer::new(sender.get_config(), root.path());
+        let (shutdown, shutdown_signal) = tokio::sync::oneshot::channel::<()>();
+
+        let mut recipient = Recipient::new_single_port("127.0.0.1".parse().unwrap(), 3304);
+
+        let requests = async {
+            let mut entries = recipient.list_dir("dir").await.unwrap();
+            entries.sort_by(|a, b| a.name.cmp(&b.name));
+            assert_eq!(entries.len(), 2);
+            assert_eq!(entries[0].name, "file.txt");
+            assert_eq!(entries[0].entry_type, EntryType::File);
+            assert_eq!(entries[0].size, 9);
+            assert_eq!(entries[0].hash, None);
+            assert_eq!(entries[1].name, "sub");
+            assert_eq!(entries[1].entry_type, EntryType::Dir);
+
+            let root_entries = recipient.list_dir("").await.unwrap();
+            assert_eq!(root_entries.len(), 1);
+            assert_eq!(root_entries[0].name, "dir");
+
+            let stat = recipient.stat("dir/file.txt").await.unwrap();
+            assert_eq!(stat.hash, None);
+            assert_eq!(stat.entry_type, EntryType::File);
+            assert_eq!(
+                stat.mtime,
+                std::fs::metadata(root.join("dir").join("file.txt"))
+                    .unwrap()
+                    .modified()
+                    .unwrap()
+            );
+
+            let mut hasher = Hasher::new(HashAlgorithm::Blake2b);
+            hasher.update(b"test data");
+            let hash = recipient.hash("dir/file.txt").await.unwrap();
+            assert_eq!(hash.name, "file.txt");
+            assert_eq!(hash.hash, Some(hasher.finalize()));
+            assert_eq!(hash.hash_algorithm, HashAlgorithm::Blake2b);
+
+            for result in [
+                recipient.stat("../dir").await,
+                recipient.stat("dir/missing.txt").await,
+                recipient.hash("dir/sub").await,
+            ] {
+                assert!(matches!(result, Err(ProtocolError::Rejected(_))));
+            }
+            assert!(matches!(
+                recipient.list_dir("dir/file.txt").await,
+                Err(ProtocolError::Rejected(_))
+            ));
+
+            shutdown.send(()).unwrap();
+        };
+
+        let (result, _) = tokio::join!(
+            server.run(async {
+                shutdown_signal.await.unwrap();
+            }),
+            requests
+        );
+        result.unwrap();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn control_symlink_out_of_root() {
+        crate::init_logger_for_test();
+
+        let root = assert_fs::TempDir::new().unwrap();
+        let outside = assert_fs::TempDir::new().unwrap();
+        std::fs::write(outside.join("secret.txt"), b"secret").unwrap();
+        std::os::unix::fs::symlink(outside.join("secret.txt"), root.join("link")).unwrap();
+
+        let sender = Sender::new_single_port("::0".parse().unwrap(), 3305);
+        let server = ControlServer::new(sender.get_config(), root.path());
+        let (shutdown, shutdown_signal) = tokio::sync::oneshot::channel::<()>();
+
+        let mut recipient = Recipient::new_single_port("127.0.0.1".parse().unwrap(), 3305);
+
+        let requests = async {
+            let entries = recipient.list_dir("").await.unwrap();
+            assert_eq!(entries.len(), 1);
+            assert_eq!(entries[0].entry_type, EntryType::Symlink);
+
+            assert!(matches!(
+                recipient.hash("link").await,
+                Err(ProtocolError::Rejected(_))
+            ));
+
+            shutdown.send(()).unwrap();
+        };
+
+        let (result, _) = tokio::join!(
+            server.run(async {
+                shutdown_signal.await.unwrap();
+            }),
+            requests
+        );
+        result.unwrap();
+    }
+
+    #[tokio::test]
+    async fn control_with_auth_key_and_idle_timeout() {
+        crate::init_logger_for_test();
+
+        let root = assert_fs::TempDir::new().unwrap();
+        std::fs::write(root.join("file.txt"), b"test data").unwrap();
+
+        let mut sender = Sender::new_single_port("::0".parse().unwrap(), 3366);
+        sender.set_auth_key(Some(b"shared secret".to_vec()));
+        let mut config = sender.get_config();
+        config.timeout = std::time::Duration::from_millis(200);
+        let server = ControlServer::new(config, root.path());
+        let (shutdown, shutdown_signal) = tokio::sync::oneshot::channel::<()>();
+
+        let requests = async {
+            let mut recipient = Recipient::new_single_port("127.0.0.1".parse().unwrap(), 3366);
+            assert!(matches!(
+                recipient.stat("file.txt").await,
+                Err(ProtocolError::Unauthorized)
+            ));
+
+            recipient.set_auth_key(Some(b"wrong secret".to_vec()));
+            assert!(matches!(
+                recipient.stat("file.txt").await,
+                Err(ProtocolError::Unauthorized)
+            ));
+
+            recipient.set_auth_key(Some(b"shared secret".to_vec()));
+            assert_eq!(recipient.stat("file.txt").await.unwrap().size, 9);
+
+            // Idle connection is closed by the server
+            let socket = TcpConnection::connect("127.0.0.1:3366", &recipient.config)
+                .await
+                .unwrap();
+            let mut config = recipient.config.clone();
+            let (_, mut connection) = negotiate_for_recipient(socket, &mut config).await.unwrap();
+            let answer: Result<ControlAnswer, _> = timeout!(
+                recv_message(&mut connection),
+                |_| ProtocolError::TimeoutExpired,
+                std::time::Duration::from_secs(5)
+            )
+            .unwrap();
+            assert!(
+                matches!(answer, Err(HandshakeError::IO(e)) if e.kind() == ErrorKind::UnexpectedEof)
+            );
+
+            shutdown.send(()).unwrap();
+        };
+
+        let (result, _) = tokio::join!(
+            server.run(async {
+                shutdown_signal.await.unwrap();
+            }),
+            requests
+        );
+        result.unwrap();
+    }
+}
@@ -0,0 +1,226 @@
+//! Encryption of the connection for sending files. See [`Encryption`]
+//!
+//! # Description
+//!
+//! After the negotiation, if both sides use encryption:
+//!
+//! 1. Each side sends its ephemeral X25519 public key (32 bytes)
+//! 2. Keys for both directions are derived from the shared secret, both public keys,
+//!    the negotiated capabilities and the pre-shared key
+//!    (see [`blake3::Hasher::new_derive_key`])
+//! 3. Each side sends the encrypted confirmation and checks the confirmation of the other side
+//!
+//! Then all data is sent by frames: size of frame (u32 big endian) + frame encrypted with
+//! ChaCha20-Poly1305. Nonce is the number of the frame (u64 big endian), separately for each direction.
+
+use super::{connection::DataConnection, error::ProtocolError, handshake::HandshakeError};
+use crate::{
+    common::timeout,
+    core::{Encryption, NegotiatedCapabilities},
+};
+use async_trait::async_trait;
+use chacha20poly1305::{
+    aead::{Aead, KeyInit},
+    ChaCha20Poly1305, Key, Nonce,
+};
+use log::debug;
+use std::{
+    io::{Error, ErrorKind},
+    time::Duration,
+};
+use x25519_dalek::{EphemeralSecret, PublicKey};
+
+/// Context of the derivation of keys
+const KEY_CONTEXT: &str = "snwf 2023-05-01 keys of the connection for sending files";
+
+/// Message for checking that both sides have the same keys
+const CONFIRMATION: &[u8] = b"snwf key confirmation";
+
+/// Max size of data in one frame
+const MAX_FRAME_SIZE: usize = 64 * 1024;
+
+/// Size of the tag of ChaCha20-Poly1305
+const TAG_SIZE: usize = 16;
+
+/// Size of the header of the frame
+const FRAME_HEADER_SIZE: usize = 4;
+
+/// Encrypted [`DataConnection`]
+pub(crate) struct EncryptedConnection<C> {
+    connection: C,
+    send_cipher: ChaCha20Poly1305,
+    recv_cipher: ChaCha20Poly1305,
+    send_counter: u64,
+    recv_counter: u64,
+
+    /// Decrypted data of the last frame, that is not read yet
+    received: Vec<u8>,
+    received_offset: usize,
+}
+
+fn get_nonce(counter: u64) -> Nonce {
+    let mut nonce = Nonce::default();
+    nonce[4..].copy_from_slice(&counter.to_be_bytes());
+    nonce
+}
+
+impl<C: DataConnection> EncryptedConnection<C> {
+    /// Exchange keys with the other side and check them
+    ///
+    /// * `is_sender` - is it [`Sender`](crate::sender::Sender)?
+    pub(crate) async fn new(
+        mut connection: C,
+        is_sender: bool,
+        encryption: &Encryption,
+        negotiated: &NegotiatedCapabilities,
+        timeout: Duration,
+    ) -> Result<Self, ProtocolError> {
+        let secret = EphemeralSecret::random();
+        let public_key = PublicKey::from(&secret);
+
+        timeout!(
+            connection.send_data(public_key.as_bytes()),
+            |_| ProtocolError::TimeoutExpired,
+            timeout
+        )?
+        .map_err(ProtocolError::FileIO)?;
+
+        let mut other_public_key = [0u8; 32];
+        timeout!(
+            connection.recv_exact(&mut other_public_key),
+            |_| ProtocolError::TimeoutExpired,
+            timeout
+        )?
+        .map_err(ProtocolError::ReceivingData)?;
+
+        let shared_secret = secret.diffie_hellman(&PublicKey::from(other_public_key));
+        let (sender_public_key, recipient_public_key) = match is_sender {
+            true => (public_key.to_bytes(), other_public_key),
+            false => (other_public_key, public_key.to_bytes()),
+        };
+
+        let mut hasher = blake3::Hasher::new_derive_key(KEY_CONTEXT);
+        hasher.update(shared_secret.as_bytes());
+        hasher.update(&sender_public_key);
+        hasher.update(&recipient_public_key);
+        // Changed negotiation gives other keys
+        hasher.update(&serde_json::to_vec(negotiated).map_err(HandshakeError::from)?);
+        if let Encryption::PreSharedKey(key) = encryption {
+            hasher.update(&(key.len() as u64).to_be_bytes());
+            hasher.update(key);
+        }
+
+        let mut keys = [0u8; 64];
+        hasher.finalize_xof().fill(&mut keys);
+        let (sender_key, recipient_key) = keys.split_at(32);
+        let (send_key, recv_key) = match is_sender {
+            true => (sender_key, recipient_key),
+            false => (recipient_key, sender_key),
+        };
+
+        let mut encrypted = Self {
+            connection,
+            send_cipher: ChaCha20Poly1305::new(Key::from_slice(send_key)),
+            recv_cipher: ChaCha20Poly1305::new(Key::from_slice(recv_key)),
+            send_counter: 0,
+            recv_counter: 0,
+            received: Vec::new(),
+            received_offset: 0,
+        };
+
+        timeout!(
+            encrypted.send_data(CONFIRMATION),
+            |_| ProtocolError::TimeoutExpired,
+            timeout
+        )?
+        .map_err(ProtocolError::FileIO)?;
+
+        let mut confirmation = [0u8; CONFIRMATION.len()];
+        match timeout!(
+            encrypted.recv_exact(&mut confirmation),
+            |_| ProtocolError::TimeoutExpired,
+            timeout
+        )? {
+            Ok(()) if confirmation == CONFIRMATION => {
+                debug!("done key exchange");
+                Ok(encrypted)
+            }
+            result => {
+                debug!("other key of encryption: {:?}", result);
+                Err(ProtocolError::AuthenticationFailed)
+            }
+        }
+    }
+
+    /// Receive and decrypt the next frame
+    ///
+    /// Returns `false` if the connection is closed
+    async fn recv_frame(&mut self) -> std::io::Result<bool> {
+        let mut header = [0u8; FRAME_HEADER_SIZE];
+        let len = self.connection.recv_data(&mut header).await?;
+        if len == 0 {
+            return Ok(false);
+        }
+        self.connection.recv_exact(&mut header[len..]).await?;
+
+        let size = u32::from_be_bytes(header) as usize;
+        if !(TAG_SIZE..=MAX_FRAME_SIZE + TAG_SIZE).contains(&size) {
+            return Err(ErrorKind::InvalidData.into());
+        }
+
+        let mut frame = vec![0u8; size];
+        self.connection.recv_exact(&mut frame).await?;
+
+        self.received = self
+            .recv_cipher
+            .decrypt(&get_nonce(self.recv_counter), frame.as_slice())
+            .map_err(|_| Error::new(ErrorKind::InvalidData, "authentication failed"))?;
+        self.received_offset = 0;
+        self.recv_counter += 1;
+
+        Ok(true)
+    }
+}
+
+#[async_trait(?Send)]
+impl<C: DataConnection> DataConnection for EncryptedConnection<C> {
+    async fn send_data(&mut self, buf: &[u8]) -> std::io::Result<()> {
+        for chunk in buf.chunks(MAX_FRAME_SIZE) {
+            let encrypted = self
+                .send_cipher
+                .encrypt(&get_nonce(self.send_counter), chunk)
+                .map_err(|_| Error::other("encryption failed"))?;
+            self.send_counter += 1;
+
+            // Header and frame separately: UDT receives only whole messages
+            self.connection
+                .send_data(&(encrypted.len() as u32).to_be_bytes())
+                .await?;
+            self.connection.send_data(&encrypted).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn recv_data(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        while self.received_offset == self.received.len() {
+            if !self.recv_frame().await? {
+                return Ok(0);
+            }
+        }
+
+        let len = buf.len().min(self.received.len() - self.received_offset);
+        buf[..len].copy_from_slice(&self.received[self.received_offset..][..len]);
+        self.received_offset += len;
+
+        Ok(len)
+    }
+
+    fn is_secure(&self) -> bool {
+        true
+    }
+}
@@ -1,6 +1,12 @@
 use super::handshake::HandshakeError;
+use std::path::PathBuf;
 use thiserror::Error;
 
+/// Result for every file of the batch transfer
+///
+/// An error of one file does not stop the transfer of other files
+pub type FilesResult = Vec<(PathBuf, Result<(), ProtocolError>)>;
+
 #[derive(Debug, Error)]
 pub enum ProtocolError {
     /// An error occurred while enabling the server socket (TcpListener)
@@ -51,6 +57,37 @@ pub enum ProtocolError {
     #[error("file invalid")]
     FileInvalid,
 
+    /// The other side refused the transfer: [`Recipient`](crate::recipient::Recipient)
+    /// doesn't want the file (see [`Decision::Reject`](crate::core::Decision::Reject))
+    /// or [`SenderServer`](crate::protocol::udt::SenderServer) doesn't give it
+    #[error("rejected: {0}")]
+    Rejected(String),
+
+    /// The other side has other key of encryption or the data is changed on the way
+    ///
+    /// See [`Encryption`](crate::core::Encryption)
+    #[error("authentication failed")]
+    AuthenticationFailed,
+
+    /// The other side has other shared secret for authentication or doesn't have it
+    ///
+    /// See [`auth`](crate::protocol::auth)
+    #[error("unauthorized")]
+    Unauthorized,
+
+    /// The file isn't signed by a trusted signer or the signature is wrong
+    ///
+    /// See [`Signer`](crate::core::Signer)
+    #[error("untrusted: {0}")]
+    Untrusted(String),
+
+    /// Wrong TLS settings. See [`tls`](crate::protocol::tls)
+    ///
+    /// Errors of the TLS handshake are errors of sending or receiving
+    #[cfg(feature = "tls")]
+    #[error("tls")]
+    Tls(#[source] rustls::Error),
+
     /// Each operation that is connected to the network has time limit
     ///
     /// This is the timeout
@@ -4,28 +4,404 @@
 //!
 //! Handshake - used information about a file for check valid.
 //!
-//! * Format: [json](https://github.com/serde-rs/json)
-//! * Max size: 512 (hash) + 300 (filename) + 60 (other information) = 872
+//! Format (all numbers are big endian):
+//!
+//! | Field          | Size                 | Description                                 |
+//! |----------------|----------------------|---------------------------------------------|
+//! | magic          | 4                    | `SNWF`                                      |
+//! | version        | u16                  | version of the format. Now `1`              |
+//! | size of body   | u32                  | size of all next fields. Max: 64 KiB        |
+//! | size of file   | u64                  |                                             |
+//! | hash algorithm | u8                   | id of [`HashAlgorithm`]                     |
+//! | file name      | u16 (size) + UTF-8   |                                             |
+//! | extensions     | u16 (count) + items  | item: u16 (id) + u16 (size) + data          |
+//!
+//! Extensions are optional fields. Unknown extensions are ignored, so new fields
+//! don't need a new version. If versions are different, the handshake is rejected
+//! with [`HandshakeError::VersionMismatch`].
+//!
+//! Other messages use [json](https://github.com/serde-rs/json).
+//!
+//! The file is hashed while it is sent. The hash is sent after the data of the file
+//! over the connection for sending files: `Trailer` (size of json (u32 big endian) + json).
+//!
+//! If there is no port for the handshake, it is sent over the connection for
+//! sending files in the same format, prefixed with its size (u32 big endian): UDT receives
+//! only whole messages. Other messages: size of json (u32 big endian) + json.
+//!
+//! # File names
+//!
+//! The file name and paths of the manifest come from [`Sender`](crate::sender::Sender).
+//! [`Recipient`](crate::recipient::Recipient) doesn't trust them: unsafe names are rejected,
+//! sanitized or renamed. See [`FileNamePolicy`].
+//!
+//! The name in the body is UTF-8. If the original name is not valid UTF-8 (it is possible
+//! on Unix), invalid bytes are replaced with `U+FFFD` and the handshake has the extension `3`
+//! with the original bytes of the name. See [`FileNameEncoding`].
+//!
+//! # Metadata
+//!
+//! The handshake has the extension `4` with the mode, times, owner and group of the file:
+//! only the fields [`Recipient`](crate::recipient::Recipient) asked for at the negotiation.
+//! It applies them after the hash check. See [`PreserveMetadata`](crate::core::PreserveMetadata)
+//! and [`metadata`](crate::protocol::metadata) for the format.
+//!
+//! # Answer
+//!
+//! After the handshake, [`Recipient`](crate::recipient::Recipient) answers over the connection
+//! for sending files: accepted or rejected with the reason (see [`Decision`](crate::core::Decision)).
+//! Not for a directory.
+//!
+//! # Resume
+//!
+//! After the answer, [`Recipient`](crate::recipient::Recipient) answers over the connection
+//! for sending files: how many bytes of the file it already has and the hash of these bytes.
+//! [`Sender`](crate::sender::Sender) checks the hash and answers with the offset
+//! (u64 big endian) from which the file will be sent. `0` - send the whole file.
+//!
+//! # Stream
+//!
+//! For data without a file, `StreamHandshake` is sent before the data: the name and maybe the size.
+//! The data is sent by chunks: size of chunk (u32 big endian) + chunk. Chunk with size `0` - end of data.
+//! Then `Trailer` with the hash is sent over the connection for sending files.
+//!
+//! # Pull
+//!
+//! [`Recipient`](crate::recipient::Recipient) may ask
+//! [`SenderServer`](crate::protocol::udt::SenderServer) for a file: after the negotiation,
+//! it sends `FetchRequest` with the relative path over the connection for sending files
+//! and gets `FetchAnswer`. Then the file is sent as usual.
+//!
+//! # Compression
+//!
+//! If [`Sender`](crate::sender::Sender) compresses the file, the handshake has the extension
+//! `1` with the id of [`Compression`]. Then data of the file is sent by blocks:
+//! size of block (u32 big endian) + block. Every block is one chunk of the file, compressed alone.
+//! If the highest bit of the size is set, the block is not compressed.
+//!
+//! # Encryption
+//!
+//! If both sides agreed on encryption, keys are exchanged right after the negotiation.
+//! All next messages, the handshake and the data are encrypted and sent over the connection
+//! for sending files, also if there is a port for the handshake.
+//! It includes `FetchRequest`. Control requests have their own connection,
+//! see [`control`](crate::protocol::control). See [`Encryption`](crate::core::Encryption).
+//!
+//! # Signing
+//!
+//! If [`Sender`](crate::sender::Sender) has the key for signing, the handshake has the extension
+//! `2` with the signature of the hash, size and name of the file. See [`Signer`](crate::core::Signer).
+//!
+//! # Authentication
+//!
+//! If any side has the shared secret, the sides check each other right after the negotiation
+//! (and the key exchange). Then the handshake and all next messages are sent over the connection
+//! for sending files, also if there is a port for the handshake.
+//! See [`auth`](crate::protocol::auth).
+//!
+//! # TLS
+//!
+//! With the feature `tls`, the connection for the handshake is protected by TLS, if it is set.
+//! See [`tls`](crate::protocol::tls).
+//!
+//! # Control
+//!
+//! Requests about files without sending them. See [`control`](crate::protocol::control).
+//!
+//! # Negotiation
+//!
+//! Before anything else, [`Sender`](crate::sender::Sender) sends `Capabilities` over the
+//! connection for sending files: what it supports. [`Recipient`](crate::recipient::Recipient)
+//! compares them with its own and answers with `Negotiation`: agreed
+//! [`NegotiatedCapabilities`] or the reason of refusal.
 //!
 //! **The algorithm of work may differ from the type of [`crate::protocol`]!**
 
-use crate::common::{get_hasher, timeout, DEFAULT_BUFFER_SIZE_FOR_NETWORK};
+#[cfg(feature = "udt")]
+use crate::protocol::connection::TcpConnection;
+#[cfg(any(feature = "tcp", feature = "udt", test))]
+use crate::protocol::connection::TcpConnectionListener;
+use crate::{
+    common::{timeout, Hasher, DEFAULT_BUFFER_SIZE_FOR_FILE, DEFAULT_BUFFER_SIZE_FOR_NETWORK},
+    core::{Compression, HashAlgorithm, NegotiatedCapabilities, PreserveMetadata},
+    protocol::{connection::DataConnection, metadata::FileMetadata},
+};
 use log::debug;
-use serde::{Deserialize, Serialize};
-use std::path::Path;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+#[cfg(feature = "udt")]
+use std::path::PathBuf;
+use std::{
+    ffi::{OsStr, OsString},
+    path::Path,
+};
 use thiserror::Error;
+#[cfg(any(feature = "tcp", feature = "udt", test))]
+use tokio::io::AsyncRead;
 use tokio::{
-    fs::metadata,
-    io::{AsyncReadExt, AsyncWriteExt},
-    net::{TcpListener, TcpStream},
+    fs::{metadata, File},
+    io::{AsyncReadExt, AsyncWrite, AsyncWriteExt},
 };
 
-/// Info about file
-#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+/// Max size of one file name in bytes. Limit of most file systems
+const MAX_FILE_NAME_SIZE: usize = 255;
+
+/// Characters that are not allowed in file names on Windows
+const WINDOWS_RESERVED_CHARACTERS: &str = "<>:\"|?*";
+
+/// Names of devices on Windows. Not allowed with any extension
+const WINDOWS_RESERVED_NAMES: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// What [`Recipient`](crate::recipient::Recipient) does with an unsafe file name from
+/// [`Sender`](crate::sender::Sender)
+///
+/// A name is unsafe, if it is empty, `.` or `..`, longer than 255 bytes or has separators
+/// (`/`, `\`) or control characters (NUL, new line and etc.). On Windows also:
+/// reserved characters (`<>:"|?*`), names of devices (`CON`, `NUL`, `COM1`, ...)
+/// and a dot or a space at the end.
+///
+/// Safe names are used as is. For directories, every part of the path is checked.
+///
+/// # Example
+///
+/// ```
+/// # use snwf::prelude::*;
+/// # use snwf::protocol::handshake::FileNamePolicy;
+/// #
+/// let mut recipient = Recipient::new("::0".parse().unwrap(), 4324, 6343);
+/// recipient.set_file_name_policy(FileNamePolicy::Sanitize);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FileNamePolicy {
+    /// Reject the file with [`HandshakeError::UnsafeFileName`]
+    #[default]
+    Reject,
+
+    /// Replace unsafe characters with `_`. For example: `../x` -> `.._x`
+    Sanitize,
+
+    /// Replace the name with `file_` + 16 hex digits of the hash of the name
+    Rename,
+}
+
+/// Which name [`Recipient`](crate::recipient::Recipient) uses, if the original name
+/// of the file is not valid UTF-8
+///
+/// On Unix, a file name is any bytes except `/` and NUL. [`Sender`](crate::sender::Sender)
+/// sends such names losslessly. The name is checked with [`FileNamePolicy`] in any case.
+///
+/// # Example
+///
+/// ```
+/// # use snwf::prelude::*;
+/// # use snwf::protocol::handshake::FileNameEncoding;
+/// #
+/// let mut recipient = Recipient::new("::0".parse().unwrap(), 4324, 6343);
+/// recipient.set_file_name_encoding(FileNameEncoding::Exact);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FileNameEncoding {
+    /// Invalid bytes are replaced with `U+FFFD`: `name\xFF` -> `name�`
+    #[default]
+    Utf8,
+
+    /// The same bytes as on the side of [`Sender`](crate::sender::Sender).
+    /// Only on Unix, other platforms use [`FileNameEncoding::Utf8`]
+    Exact,
+}
+
+/// First bytes of [`Handshake`]
+pub(crate) const HANDSHAKE_MAGIC: [u8; 4] = *b"SNWF";
+
+/// Version of the format of [`Handshake`]
+pub(crate) const PROTOCOL_VERSION: u16 = 1;
+
+/// Magic + version + size of body
+const HANDSHAKE_HEADER_SIZE: usize = 10;
+
+/// Max size of body of [`Handshake`]
+pub(crate) const MAX_HANDSHAKE_SIZE: usize = 64 * 1024;
+
+/// Info about file. Hash is sent in [`Trailer`] after the data
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
 pub(crate) struct Handshake {
-    pub(crate) hash: String,
     pub(crate) size: u64,
+
+    /// UTF-8 form of the name. Original bytes are in [`EXTENSION_RAW_FILE_NAME`]
     pub(crate) file_name: String,
+    pub(crate) hash_algorithm: HashAlgorithm,
+
+    /// Optional fields
+    #[serde(default)]
+    pub(crate) extensions: Vec<Extension>,
+}
+
+/// Optional field of [`Handshake`]
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+pub(crate) struct Extension {
+    pub(crate) id: u16,
+    pub(crate) data: Vec<u8>,
+}
+
+/// Id of [`Extension`] with [`Compression`] of the data. Data: id of the codec (u8)
+pub(crate) const EXTENSION_COMPRESSION: u16 = 1;
+
+/// Id of [`Extension`] with the signature of the handshake. See [`Signer`](crate::core::Signer)
+pub(crate) const EXTENSION_SIGNATURE: u16 = 2;
+
+/// Id of [`Extension`] with the original bytes of the file name. Only if they are not UTF-8
+pub(crate) const EXTENSION_RAW_FILE_NAME: u16 = 3;
+
+/// Id of [`Extension`] with the metadata of the file. See [`metadata`](crate::protocol::metadata)
+pub(crate) const EXTENSION_METADATA: u16 = 4;
+
+/// Info about stream. Size may be unknown, hash is sent in [`Trailer`] after the data
+#[cfg(feature = "udt")]
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub(crate) struct StreamHandshake {
+    pub(crate) file_name: String,
+    pub(crate) size_hint: Option<u64>,
+    pub(crate) hash_algorithm: HashAlgorithm,
+}
+
+/// Sent after the data of the file or the stream
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub(crate) struct Trailer {
+    pub(crate) hash: String,
+    pub(crate) size: u64,
+}
+
+/// What part of the file [`Recipient`](crate::recipient::Recipient) already has.
+///
+/// Answer for [`Handshake`]
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub(crate) struct Resume {
+    pub(crate) size: u64,
+    pub(crate) hash: String,
+}
+
+/// Message of [`Sender`](crate::sender::Sender) in the batch transfer
+#[cfg(feature = "udt")]
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub(crate) enum BatchMessage {
+    /// Handshake of the next file. Then data of the file
+    File(Handshake),
+
+    /// No more files
+    End,
+}
+
+/// Answer of [`Recipient`](crate::recipient::Recipient) for [`Handshake`]: does it want the file?
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub(crate) enum HandshakeAnswer {
+    Accepted,
+
+    /// Reason of rejection
+    Rejected(String),
+}
+
+/// Request of [`Recipient`](crate::recipient::Recipient) in pull mode
+#[cfg(feature = "udt")]
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub(crate) struct FetchRequest {
+    /// Relative path in the exported root. Separator: `/`
+    pub(crate) path: String,
+}
+
+/// Answer of [`SenderServer`](crate::protocol::udt::SenderServer) for [`FetchRequest`]
+#[cfg(feature = "udt")]
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub(crate) enum FetchAnswer {
+    Found,
+
+    /// Reason why the file is not sent
+    NotFound(String),
+}
+
+/// What one side supports. Lists are in order of preference
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+pub(crate) struct Capabilities {
+    pub(crate) compression: Vec<Compression>,
+    pub(crate) hash_algorithms: Vec<HashAlgorithm>,
+    pub(crate) resume: bool,
+    pub(crate) encryption: bool,
+    pub(crate) authentication: bool,
+    pub(crate) max_chunk_size: usize,
+
+    /// Which metadata of files the sender can send or the recipient applies
+    pub(crate) preserve_metadata: PreserveMetadata,
+}
+
+/// Answer of [`Recipient`](crate::recipient::Recipient) for [`Capabilities`]
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub(crate) enum Negotiation {
+    Agreed(NegotiatedCapabilities),
+
+    /// Reason of refusal
+    Refused(String),
+}
+
+impl Capabilities {
+    /// Intersection of capabilities of [`Sender`](crate::sender::Sender) (`self`)
+    /// and [`Recipient`](crate::recipient::Recipient). Preference of the sender wins
+    ///
+    /// Returns the reason of refusal, if there is nothing in common
+    pub(crate) fn negotiate(
+        &self,
+        recipient: &Capabilities,
+    ) -> Result<NegotiatedCapabilities, String> {
+        let compression = self
+            .compression
+            .iter()
+            .find(|compression| recipient.compression.contains(compression))
+            .ok_or_else(|| {
+                format!(
+                    "no common compression. sender: {:?}; recipient: {:?}",
+                    self.compression, recipient.compression
+                )
+            })?;
+
+        let hash_algorithm = self
+            .hash_algorithms
+            .iter()
+            .find(|hash_algorithm| recipient.hash_algorithms.contains(hash_algorithm))
+            .ok_or_else(|| {
+                format!(
+                    "no common hash algorithm. sender: {:?}; recipient: {:?}",
+                    self.hash_algorithms, recipient.hash_algorithms
+                )
+            })?;
+
+        let max_chunk_size = self.max_chunk_size.min(recipient.max_chunk_size);
+        if max_chunk_size == 0 {
+            return Err("max chunk size is 0".to_string());
+        }
+
+        Ok(NegotiatedCapabilities {
+            compression: *compression,
+            hash_algorithm: *hash_algorithm,
+            resume: self.resume && recipient.resume,
+            encryption: self.encryption && recipient.encryption,
+            // Required by any side
+            authentication: self.authentication || recipient.authentication,
+            max_chunk_size,
+            preserve_metadata: self
+                .preserve_metadata
+                .intersection(&recipient.preserve_metadata),
+            // Set by the recipient after the intersection
+            nonce: Vec::new(),
+        })
+    }
+}
+
+/// Answer of [`Recipient`](crate::recipient::Recipient) after the file of the batch transfer
+#[cfg(feature = "udt")]
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub(crate) enum FileStatus {
+    Valid,
+    Invalid,
 }
 
 #[derive(Debug, Error)]
@@ -41,6 +417,16 @@ pub enum HandshakeError {
 
     #[error("wrong use function: {0}")]
     Assert(String),
+
+    #[error("unsupported version of handshake: {received}; expected: {expected}")]
+    VersionMismatch { expected: u16, received: u16 },
+
+    #[error("sender and recipient can't agree: {0}")]
+    NegotiationFailed(String),
+
+    /// See [`FileNamePolicy`]
+    #[error("unsafe file name: {0:?}")]
+    UnsafeFileName(String),
 }
 
 /// [`std::assert`], but for [`HandshakeError`]
@@ -59,34 +445,437 @@ macro_rules! assert_handshake {
 
 pub(crate) use assert_handshake;
 
-fn get_file_name_from_as_ref_path(path: impl AsRef<Path>) -> String {
-    path.as_ref()
-        .file_name()
-        .unwrap()
-        .to_str()
-        .unwrap()
-        .to_string()
+/// Name of the file of `path`: UTF-8 form and the original bytes, if they are not UTF-8
+fn get_file_name_from_as_ref_path(
+    path: impl AsRef<Path>,
+) -> Result<(String, Option<Vec<u8>>), HandshakeError> {
+    let file_name = path.as_ref().file_name();
+    assert_handshake!(
+        file_name.is_some(),
+        "path has no file name: {:?}",
+        path.as_ref()
+    );
+    let file_name = file_name.unwrap();
+
+    match file_name.to_str() {
+        Some(file_name) => Ok((file_name.to_string(), None)),
+        None => {
+            debug!("file name isn't UTF-8: {:?}", file_name);
+            Ok((
+                file_name.to_string_lossy().into_owned(),
+                get_raw_file_name(file_name),
+            ))
+        }
+    }
+}
+
+/// Bytes of `file_name`. Only on Unix: other platforms don't have such names
+#[cfg(unix)]
+fn get_raw_file_name(file_name: &OsStr) -> Option<Vec<u8>> {
+    use std::os::unix::ffi::OsStrExt;
+
+    Some(file_name.as_bytes().to_vec())
+}
+
+#[cfg(not(unix))]
+fn get_raw_file_name(_file_name: &OsStr) -> Option<Vec<u8>> {
+    None
+}
+
+/// File name from the original bytes. `None`, if the platform can't have it
+#[cfg(unix)]
+fn get_os_file_name(raw: &[u8]) -> Option<OsString> {
+    use std::os::unix::ffi::OsStrExt;
+
+    Some(OsStr::from_bytes(raw).to_os_string())
+}
+
+#[cfg(not(unix))]
+fn get_os_file_name(raw: &[u8]) -> Option<OsString> {
+    std::str::from_utf8(raw).ok().map(OsString::from)
+}
+
+/// Character is not allowed in a file name on this platform
+fn is_unsafe_character(character: char) -> bool {
+    character == '/'
+        || character == '\\'
+        || character.is_control()
+        || (cfg!(windows) && WINDOWS_RESERVED_CHARACTERS.contains(character))
+}
+
+/// Name of a device on Windows, maybe with an extension: `nul.txt`
+fn is_windows_reserved_name(name: &str) -> bool {
+    let stem = name.split('.').next().unwrap_or_default();
+    WINDOWS_RESERVED_NAMES
+        .iter()
+        .any(|reserved| reserved.eq_ignore_ascii_case(stem))
+}
+
+/// Can `name` be used as one part of a path on this platform? See [`FileNamePolicy`]
+pub(crate) fn is_safe_file_name(name: &str) -> bool {
+    if name.is_empty() || name == "." || name == ".." || name.len() > MAX_FILE_NAME_SIZE {
+        return false;
+    }
+
+    if name.chars().any(is_unsafe_character) {
+        return false;
+    }
+
+    !(cfg!(windows)
+        && (name.ends_with('.') || name.ends_with(' ') || is_windows_reserved_name(name)))
+}
+
+/// Replace unsafe characters of `name` with `_`
+fn sanitize_file_name(name: &str) -> String {
+    let mut sanitized: String = name
+        .chars()
+        .map(|character| match is_unsafe_character(character) {
+            true => '_',
+            false => character,
+        })
+        .collect();
+
+    if sanitized.is_empty() || sanitized == "." || sanitized == ".." {
+        sanitized = "_".repeat(sanitized.len().max(1));
+    }
+
+    if cfg!(windows) {
+        while sanitized.ends_with('.') || sanitized.ends_with(' ') {
+            sanitized.pop();
+            sanitized.push('_');
+        }
+        if is_windows_reserved_name(&sanitized) {
+            sanitized.insert(0, '_');
+        }
+    }
+
+    while sanitized.len() > MAX_FILE_NAME_SIZE {
+        sanitized.pop();
+    }
+
+    sanitized
+}
+
+/// Check the file name from [`Sender`](crate::sender::Sender) and apply `policy`,
+/// if it is unsafe
+pub(crate) fn get_safe_file_name(
+    name: &str,
+    policy: FileNamePolicy,
+) -> Result<String, HandshakeError> {
+    if is_safe_file_name(name) {
+        return Ok(name.to_string());
+    }
+    debug!("unsafe file name: {:?}; policy: {:?}", name, policy);
+
+    match policy {
+        FileNamePolicy::Reject => Err(HandshakeError::UnsafeFileName(name.to_string())),
+        FileNamePolicy::Sanitize => Ok(sanitize_file_name(name)),
+        FileNamePolicy::Rename => Ok(format!(
+            "file_{}",
+            &blake3::hash(name.as_bytes()).to_hex()[..16]
+        )),
+    }
+}
+
+/// Check the file name of `handshake` and apply `policy`, if it is unsafe
+///
+/// With [`FileNameEncoding::Exact`], the original bytes are used, if they are safe.
+/// Otherwise, `policy` is applied to their UTF-8 form
+pub(crate) fn get_safe_file_name_of_handshake(
+    handshake: &Handshake,
+    policy: FileNamePolicy,
+    encoding: FileNameEncoding,
+) -> Result<OsString, HandshakeError> {
+    if encoding == FileNameEncoding::Utf8 {
+        return get_safe_file_name(&handshake.file_name, policy).map(OsString::from);
+    }
+
+    // Invalid bytes become U+FFFD, all other characters are checked as usual
+    let raw = handshake.get_raw_file_name();
+    let file_name = String::from_utf8_lossy(raw);
+    if is_safe_file_name(&file_name) {
+        if let Some(file_name) = get_os_file_name(raw) {
+            return Ok(file_name);
+        }
+    }
+
+    get_safe_file_name(&file_name, policy).map(OsString::from)
 }
 
-pub(crate) async fn send_handshake_from_file<P>(
+/// Path of the relative path from [`Sender`](crate::sender::Sender) inside `root`
+///
+/// Parts are separated by `/`. [`FileNamePolicy`] is applied to every part
+#[cfg(feature = "udt")]
+pub(crate) fn get_safe_path(
+    root: &Path,
+    relative: &str,
+    policy: FileNamePolicy,
+) -> Result<PathBuf, HandshakeError> {
+    let mut path = root.to_path_buf();
+
+    for part in relative.split('/') {
+        match get_safe_file_name(part, policy) {
+            Ok(part) => path.push(part),
+            // Report the whole path
+            Err(_) => return Err(HandshakeError::UnsafeFileName(relative.to_string())),
+        }
+    }
+
+    Ok(path)
+}
+
+/// Handshake for the file of `path` with the metadata chosen by `preserve`
+pub(crate) async fn get_handshake_from_file<P>(
     path: P,
-    socket: &mut TcpStream,
+    hash_algorithm: HashAlgorithm,
+    preserve: PreserveMetadata,
 ) -> Result<Handshake, HandshakeError>
 where
     P: AsRef<Path> + Sync + Copy,
 {
     assert_handshake!(path.as_ref().is_file(), "path must be a file");
 
-    let mut hasher = get_hasher();
-    let hash = file_hashing::get_hash_file(path, &mut hasher)?;
     let metadata = metadata(path).await?;
-    let handshake = Handshake {
-        hash,
+    let (file_name, raw_file_name) = get_file_name_from_as_ref_path(path)?;
+
+    let mut handshake = Handshake {
         size: metadata.len(),
-        file_name: get_file_name_from_as_ref_path(path),
+        file_name,
+        hash_algorithm,
+        extensions: Vec::new(),
     };
+    if let Some(raw_file_name) = raw_file_name {
+        handshake.set_extension(EXTENSION_RAW_FILE_NAME, raw_file_name);
+    }
+    if preserve.is_any() {
+        handshake.set_metadata(&FileMetadata::from_metadata(&metadata, preserve))?;
+    }
+
+    Ok(handshake)
+}
+
+/// Append `field` with its size (u16 big endian)
+pub(crate) fn put_field(bytes: &mut Vec<u8>, field: &[u8]) -> Result<(), HandshakeError> {
+    assert_handshake!(
+        field.len().le(&(u16::MAX as usize)),
+        "Field of handshake is too big. size: {}",
+        field.len()
+    );
+
+    bytes.extend_from_slice(&(field.len() as u16).to_be_bytes());
+    bytes.extend_from_slice(field);
+
+    Ok(())
+}
+
+/// Reader of the body of [`Handshake`] and of its extensions
+pub(crate) struct BodyReader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> BodyReader<'a> {
+    pub(crate) fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], HandshakeError> {
+        assert_handshake!(
+            len.le(&self.bytes.len()),
+            "Handshake is truncated. need: {}; have: {}",
+            len,
+            self.bytes.len()
+        );
+
+        let (field, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+
+        Ok(field)
+    }
+
+    pub(crate) fn u8(&mut self) -> Result<u8, HandshakeError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, HandshakeError> {
+        Ok(u16::from_be_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    pub(crate) fn u32(&mut self) -> Result<u32, HandshakeError> {
+        Ok(u32::from_be_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    pub(crate) fn u64(&mut self) -> Result<u64, HandshakeError> {
+        Ok(u64::from_be_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    /// Field written by [`put_field`]
+    pub(crate) fn field(&mut self) -> Result<&'a [u8], HandshakeError> {
+        let len = self.u16()? as usize;
+        self.take(len)
+    }
+}
+
+impl Handshake {
+    /// Binary format of the handshake. See docs of the module
+    pub(crate) fn to_bytes(&self) -> Result<Vec<u8>, HandshakeError> {
+        let mut body = Vec::new();
+        body.extend_from_slice(&self.size.to_be_bytes());
+        body.push(self.hash_algorithm.to_id());
+        put_field(&mut body, self.file_name.as_bytes())?;
+
+        assert_handshake!(
+            self.extensions.len().le(&(u16::MAX as usize)),
+            "Too many extensions: {}",
+            self.extensions.len()
+        );
+        body.extend_from_slice(&(self.extensions.len() as u16).to_be_bytes());
+        for extension in self.extensions.iter() {
+            body.extend_from_slice(&extension.id.to_be_bytes());
+            put_field(&mut body, &extension.data)?;
+        }
+
+        assert_handshake!(
+            body.len().le(&MAX_HANDSHAKE_SIZE),
+            "Handshake is too big. size: {}",
+            body.len()
+        );
+
+        let mut bytes = Vec::with_capacity(HANDSHAKE_HEADER_SIZE + body.len());
+        bytes.extend_from_slice(&HANDSHAKE_MAGIC);
+        bytes.extend_from_slice(&PROTOCOL_VERSION.to_be_bytes());
+        bytes.extend_from_slice(&(body.len() as u32).to_be_bytes());
+        bytes.extend_from_slice(&body);
+
+        Ok(bytes)
+    }
+
+    /// Data of [`Extension`] with `id`
+    pub(crate) fn get_extension(&self, id: u16) -> Option<&[u8]> {
+        self.extensions
+            .iter()
+            .find(|extension| extension.id == id)
+            .map(|extension| extension.data.as_slice())
+    }
+
+    /// Add [`Extension`] or replace it, if there is one with the same `id`
+    pub(crate) fn set_extension(&mut self, id: u16, data: Vec<u8>) {
+        self.extensions.retain(|extension| extension.id != id);
+        self.extensions.push(Extension { id, data });
+    }
+
+    /// Original bytes of the file name. Without the extension: bytes of [`Handshake::file_name`]
+    pub(crate) fn get_raw_file_name(&self) -> &[u8] {
+        self.get_extension(EXTENSION_RAW_FILE_NAME)
+            .unwrap_or(self.file_name.as_bytes())
+    }
+
+    /// [`Compression`] of the data. Without the extension: [`Compression::None`]
+    pub(crate) fn get_compression(&self) -> Result<Compression, HandshakeError> {
+        let data = match self.get_extension(EXTENSION_COMPRESSION) {
+            Some(data) => data,
+            None => return Ok(Compression::None),
+        };
+
+        let compression = match data {
+            [id] => Compression::from_id(*id),
+            _ => None,
+        };
+
+        match compression {
+            Some(compression) if compression.is_supported() => Ok(compression),
+            _ => Err(HandshakeError::Assert(format!(
+                "unsupported compression: {:?}",
+                data
+            ))),
+        }
+    }
+
+    /// Set [`Compression`] of the data. [`Compression::None`] is not sent
+    pub(crate) fn set_compression(&mut self, compression: Compression) {
+        match compression {
+            Compression::None => self
+                .extensions
+                .retain(|extension| extension.id != EXTENSION_COMPRESSION),
+            _ => self.set_extension(EXTENSION_COMPRESSION, vec![compression.to_id()]),
+        }
+    }
+
+    /// [`FileMetadata`] of the file. `None` without the extension
+    pub(crate) fn get_metadata(&self) -> Result<Option<FileMetadata>, HandshakeError> {
+        match self.get_extension(EXTENSION_METADATA) {
+            Some(data) => Ok(Some(FileMetadata::from_bytes(data)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Set [`FileMetadata`] of the file
+    pub(crate) fn set_metadata(&mut self, metadata: &FileMetadata) -> Result<(), HandshakeError> {
+        self.set_extension(EXTENSION_METADATA, metadata.to_bytes()?);
+        Ok(())
+    }
+
+    /// Check magic and version. Returns size of body
+    fn parse_header(header: &[u8; HANDSHAKE_HEADER_SIZE]) -> Result<usize, HandshakeError> {
+        assert_handshake!(
+            header[..4].eq(&HANDSHAKE_MAGIC),
+            "It isn't handshake. magic: {:?}",
+            &header[..4]
+        );
+
+        let version = u16::from_be_bytes([header[4], header[5]]);
+        if version != PROTOCOL_VERSION {
+            return Err(HandshakeError::VersionMismatch {
+                expected: PROTOCOL_VERSION,
+                received: version,
+            });
+        }
+
+        let size = u32::from_be_bytes(header[6..].try_into().unwrap()) as usize;
+        assert_handshake!(
+            size.le(&MAX_HANDSHAKE_SIZE),
+            "Handshake is too big. size: {}",
+            size
+        );
+
+        Ok(size)
+    }
+
+    /// Parse body of the handshake. Unknown fields after extensions are ignored
+    fn from_body(body: &[u8]) -> Result<Self, HandshakeError> {
+        let mut reader = BodyReader::new(body);
+
+        let size = reader.u64()?;
+
+        let id = reader.u8()?;
+        let hash_algorithm = HashAlgorithm::from_id(id);
+        assert_handshake!(
+            hash_algorithm.is_some(),
+            "Unknown hash algorithm. id: {}",
+            id
+        );
+
+        let file_name = String::from_utf8(reader.field()?.to_vec());
+        assert_handshake!(file_name.is_ok(), "file name must be UTF-8");
+
+        let mut extensions = Vec::new();
+        for _ in 0..reader.u16()? {
+            extensions.push(Extension {
+                id: reader.u16()?,
+                data: reader.field()?.to_vec(),
+            });
+        }
+
+        Ok(Handshake {
+            size,
+            file_name: file_name.unwrap(),
+            hash_algorithm: hash_algorithm.unwrap(),
+            extensions,
+        })
+    }
+}
 
-    let json = serde_json::to_string(&handshake)?;
+fn message_to_json(message: &impl Serialize) -> Result<String, HandshakeError> {
+    let json = serde_json::to_string(message)?;
 
     // json >= DEFAULT_BUFFER_SIZE_FOR_NETWORK - is error
     assert_handshake!(
@@ -95,32 +884,366 @@ where
         json.len()
     );
 
-    timeout!(socket.write_all(json.to_string().as_bytes()), |_| {
+    Ok(json)
+}
+
+/// Hash of the whole file
+pub(crate) async fn get_hash_of_file(
+    path: &Path,
+    hash_algorithm: HashAlgorithm,
+) -> Result<String, HandshakeError> {
+    Ok(get_hasher_of_prefix(path, u64::MAX, hash_algorithm)
+        .await?
+        .finalize())
+}
+
+/// Hasher after the first `size` bytes of the file. For continue hashing the rest of the file
+async fn get_hasher_of_prefix(
+    path: &Path,
+    size: u64,
+    hash_algorithm: HashAlgorithm,
+) -> Result<Hasher, HandshakeError> {
+    let mut reader = File::open(path).await?.take(size);
+    let mut hasher = Hasher::new(hash_algorithm);
+    let mut buf = vec![0u8; DEFAULT_BUFFER_SIZE_FOR_FILE];
+
+    loop {
+        let len = reader.read(&mut buf).await?;
+
+        if len == 0 {
+            break;
+        }
+
+        hasher.update(&buf[..len]);
+    }
+
+    Ok(hasher)
+}
+
+/// Send message over the connection for sending files
+///
+/// Format: size of json (u32 big endian) + json
+pub(crate) async fn send_message(
+    message: &impl Serialize,
+    connection: &mut impl DataConnection,
+) -> Result<(), HandshakeError> {
+    let json = message_to_json(message)?;
+
+    timeout!(
+        connection.send_data(&(json.len() as u32).to_be_bytes()),
+        |_| HandshakeError::TimeoutExpired
+    )??;
+    timeout!(connection.send_data(json.as_bytes()), |_| {
         HandshakeError::TimeoutExpired
     })??;
-    debug!("Done socket 'Handshake' send. Handshake: {:?}", json);
+    debug!("Done in-band message send. Message: {:?}", json);
 
-    Ok(handshake)
+    Ok(())
 }
 
+/// Receive message sent by [`send_message`]
+///
+/// **Without timeout!**
+pub(crate) async fn recv_message<T: DeserializeOwned>(
+    connection: &mut impl DataConnection,
+) -> Result<T, HandshakeError> {
+    let mut size = [0u8; 4];
+    connection.recv_exact(&mut size).await?;
+
+    let size = u32::from_be_bytes(size) as usize;
+    assert_handshake!(
+        DEFAULT_BUFFER_SIZE_FOR_NETWORK.cmp(&size).is_ge(),
+        "Buffer overflow. json size: {}",
+        size
+    );
+
+    let mut json = vec![0u8; size];
+    connection.recv_exact(&mut json).await?;
+
+    Ok(serde_json::from_slice(&json)?)
+}
+
+/// Send message to `socket`. If `socket` is `None` or `connection` is secure,
+/// send it over `connection`
+#[cfg(feature = "udt")]
+pub(crate) async fn send_message_to(
+    message: &impl Serialize,
+    connection: &mut impl DataConnection,
+    socket: Option<&mut impl DataConnection>,
+) -> Result<(), HandshakeError> {
+    match socket {
+        Some(socket) if !connection.is_secure() => send_message(message, socket).await,
+        _ => send_message(message, connection).await,
+    }
+}
+
+/// Receive message from `socket`. If `socket` is `None` or `connection` is secure,
+/// receive it from `connection`
+///
+/// **Without timeout!**
+#[cfg(feature = "udt")]
+pub(crate) async fn recv_message_from<T: DeserializeOwned>(
+    connection: &mut impl DataConnection,
+    socket: Option<&mut impl DataConnection>,
+) -> Result<T, HandshakeError> {
+    match socket {
+        Some(socket) if !connection.is_secure() => recv_message(socket).await,
+        _ => recv_message(connection).await,
+    }
+}
+
+pub(crate) async fn send_handshake<S>(
+    handshake: &Handshake,
+    socket: &mut S,
+) -> Result<(), HandshakeError>
+where
+    S: AsyncWrite + Unpin,
+{
+    let bytes = handshake.to_bytes()?;
+
+    timeout!(socket.write_all(&bytes), |_| {
+        HandshakeError::TimeoutExpired
+    })??;
+    timeout!(socket.flush(), |_| HandshakeError::TimeoutExpired)??;
+    debug!("Done socket 'Handshake' send. Handshake: {:?}", handshake);
+
+    Ok(())
+}
+
+/// Send handshake over the connection for sending files
+///
+/// Format: size of the handshake (u32 big endian) + handshake. UDT receives only whole
+/// messages: the size lets [`recv_handshake_in_band`] read the handshake by one message
+pub(crate) async fn send_handshake_in_band(
+    handshake: &Handshake,
+    connection: &mut impl DataConnection,
+) -> Result<(), HandshakeError> {
+    let bytes = handshake.to_bytes()?;
+
+    timeout!(
+        connection.send_data(&(bytes.len() as u32).to_be_bytes()),
+        |_| HandshakeError::TimeoutExpired
+    )??;
+    timeout!(connection.send_data(&bytes), |_| {
+        HandshakeError::TimeoutExpired
+    })??;
+    debug!("Done in-band 'Handshake' send. Handshake: {:?}", handshake);
+
+    Ok(())
+}
+
+/// Send handshake to `socket`. If `socket` is `None` or `connection` is secure,
+/// send it over `connection`
+pub(crate) async fn send_handshake_to<S>(
+    handshake: &Handshake,
+    connection: &mut impl DataConnection,
+    socket: Option<&mut S>,
+) -> Result<(), HandshakeError>
+where
+    S: AsyncWrite + Unpin,
+{
+    match socket {
+        Some(socket) if !connection.is_secure() => send_handshake(handshake, socket).await,
+        _ => send_handshake_in_band(handshake, connection).await,
+    }
+}
+
+#[cfg(any(feature = "tcp", feature = "udt", test))]
+pub(crate) async fn recv_handshake<S>(socket: &mut S) -> Result<Handshake, HandshakeError>
+where
+    S: AsyncRead + Unpin,
+{
+    let mut header = [0u8; HANDSHAKE_HEADER_SIZE];
+    timeout!(socket.read_exact(&mut header), |_| {
+        HandshakeError::TimeoutExpired
+    })??;
+
+    let mut body = vec![0u8; Handshake::parse_header(&header)?];
+    timeout!(socket.read_exact(&mut body), |_| {
+        HandshakeError::TimeoutExpired
+    })??;
+
+    Handshake::from_body(&body)
+}
+
+/// Accept the connection for handshakes from `listener` and receive the handshake
+#[cfg(any(feature = "tcp", test))]
 pub(crate) async fn recv_handshake_from_address(
-    listener: &mut TcpListener,
+    listener: &mut TcpConnectionListener,
 ) -> Result<Handshake, HandshakeError> {
     let (mut client, addr) = timeout!(listener.accept(), |_| HandshakeError::TimeoutExpired)??;
     debug!("Client for recv handshake: addr {}", addr);
 
-    let mut json = Vec::with_capacity(DEFAULT_BUFFER_SIZE_FOR_NETWORK);
-    timeout!(client.read_buf(&mut json), |_| {
+    recv_handshake(&mut client).await
+}
+
+/// Accept the connection for handshakes once, if there is `listener`
+///
+/// For sending many messages over one connection
+#[cfg(feature = "udt")]
+pub(crate) async fn accept_handshake_socket(
+    listener: Option<&mut TcpConnectionListener>,
+) -> Result<Option<TcpConnection>, HandshakeError> {
+    match listener {
+        Some(listener) => {
+            let (client, addr) = timeout!(listener.accept(), |_| HandshakeError::TimeoutExpired)??;
+            debug!("Client for recv handshake: addr {}", addr);
+
+            Ok(Some(client))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Receive handshake sent by [`send_handshake_in_band`]
+pub(crate) async fn recv_handshake_in_band(
+    connection: &mut impl DataConnection,
+) -> Result<Handshake, HandshakeError> {
+    let mut size = [0u8; 4];
+    timeout!(connection.recv_exact(&mut size), |_| {
+        HandshakeError::TimeoutExpired
+    })??;
+
+    let size = u32::from_be_bytes(size) as usize;
+    assert_handshake!(
+        (HANDSHAKE_HEADER_SIZE..=HANDSHAKE_HEADER_SIZE + MAX_HANDSHAKE_SIZE).contains(&size),
+        "Handshake has wrong size: {}",
+        size
+    );
+
+    let mut bytes = vec![0u8; size];
+    timeout!(connection.recv_exact(&mut bytes), |_| {
+        HandshakeError::TimeoutExpired
+    })??;
+
+    let (header, body) = bytes.split_at(HANDSHAKE_HEADER_SIZE);
+    let body_size = Handshake::parse_header(header.try_into().unwrap())?;
+    assert_handshake!(
+        body_size.eq(&body.len()),
+        "Handshake is truncated. need: {}; have: {}",
+        body_size,
+        body.len()
+    );
+
+    Handshake::from_body(body)
+}
+
+/// Receive handshake from `listener`. If `listener` is `None` or `connection` is secure,
+/// receive it from `connection`
+#[cfg(feature = "tcp")]
+pub(crate) async fn recv_handshake_from(
+    connection: &mut impl DataConnection,
+    listener: Option<&mut TcpConnectionListener>,
+) -> Result<Handshake, HandshakeError> {
+    match listener {
+        Some(listener) if !connection.is_secure() => recv_handshake_from_address(listener).await,
+        _ => recv_handshake_in_band(connection).await,
+    }
+}
+
+/// Receive handshake from the accepted `socket`. If `socket` is `None` or `connection`
+/// is secure, receive it from `connection`
+#[cfg(feature = "udt")]
+pub(crate) async fn recv_handshake_from_socket<S>(
+    connection: &mut impl DataConnection,
+    socket: Option<&mut S>,
+) -> Result<Handshake, HandshakeError>
+where
+    S: AsyncRead + Unpin,
+{
+    match socket {
+        Some(socket) if !connection.is_secure() => recv_handshake(socket).await,
+        _ => recv_handshake_in_band(connection).await,
+    }
+}
+
+/// Tell [`Sender`](crate::sender::Sender) what part of the file we already have.
+///
+/// * `path` - partial file. `None` - receive the whole file.
+///
+/// Returns offset from which the file will be sent and hasher of the bytes before it.
+/// The offset is `0` or the size of our part, otherwise [`HandshakeError::Assert`]
+pub(crate) async fn send_resume(
+    path: Option<&Path>,
+    handshake: &Handshake,
+    connection: &mut impl DataConnection,
+) -> Result<(u64, Hasher), HandshakeError> {
+    let new_hasher = || Hasher::new(handshake.hash_algorithm);
+
+    let (resume, hasher) = match path {
+        Some(path) if path.is_file() && metadata(path).await?.len() <= handshake.size => {
+            let size = metadata(path).await?.len();
+            let hasher = get_hasher_of_prefix(path, size, handshake.hash_algorithm).await?;
+
+            let resume = Resume {
+                size,
+                hash: hasher.clone().finalize(),
+            };
+            (resume, hasher)
+        }
+        _ => {
+            let resume = Resume {
+                size: 0,
+                hash: String::new(),
+            };
+            (resume, new_hasher())
+        }
+    };
+    send_message(&resume, connection).await?;
+
+    // Sender is hashing the same part of the file. Can't use timeout
+    let mut offset = [0u8; 8];
+    connection.recv_exact(&mut offset).await?;
+    let offset = u64::from_be_bytes(offset);
+    debug!("resume. Have: {:?}; offset: {}", resume, offset);
+
+    // Sender can only accept our part or send the whole file
+    match offset {
+        0 => Ok((0, new_hasher())),
+        _ if offset == resume.size => Ok((offset, hasher)),
+        _ => Err(HandshakeError::Assert(format!(
+            "wrong offset for resume: {}; have: {} bytes",
+            offset, resume.size
+        ))),
+    }
+}
+
+/// Get [`Resume`] from [`Recipient`](crate::recipient::Recipient) and check it.
+///
+/// Returns offset from which the file will be sent and hasher of the bytes before it
+pub(crate) async fn recv_resume(
+    path: &Path,
+    handshake: &Handshake,
+    connection: &mut impl DataConnection,
+) -> Result<(u64, Hasher), HandshakeError> {
+    // Recipient is hashing its part of the file. Can't use timeout
+    let resume: Resume = recv_message(connection).await?;
+
+    let mut offset = 0;
+    let mut hasher = Hasher::new(handshake.hash_algorithm);
+
+    if resume.size > 0 && resume.size <= handshake.size {
+        let hasher_of_prefix =
+            get_hasher_of_prefix(path, resume.size, handshake.hash_algorithm).await?;
+
+        if hasher_of_prefix.clone().finalize() == resume.hash {
+            offset = resume.size;
+            hasher = hasher_of_prefix;
+        }
+    }
+
+    timeout!(connection.send_data(&offset.to_be_bytes()), |_| {
         HandshakeError::TimeoutExpired
     })??;
+    debug!("resume. Recipient has: {:?}; offset: {}", resume, offset);
 
-    Ok(serde_json::from_str(&String::from_utf8_lossy(&json[..]))?)
+    Ok((offset, hasher))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use blake2::Digest;
+    use tokio::net::{TcpListener, TcpStream};
 
     pub(crate) mod detail {
         use super::*;
@@ -129,12 +1252,20 @@ mod tests {
             path_for_send: P,
             socket: &mut TcpStream,
         ) -> Result<(), HandshakeError> {
-            send_handshake_from_file(path_for_send, socket).await?;
+            let handshake = get_handshake_from_file(
+                path_for_send,
+                HashAlgorithm::Blake3,
+                PreserveMetadata::all(),
+            )
+            .await?;
+            send_handshake(&handshake, socket).await?;
             Ok(())
         }
 
-        pub(crate) async fn recv(listener: &mut TcpListener) -> Result<Handshake, HandshakeError> {
-            Ok(recv_handshake_from_address(listener).await?)
+        pub(crate) async fn recv(
+            listener: &mut TcpConnectionListener,
+        ) -> Result<Handshake, HandshakeError> {
+            recv_handshake_from_address(listener).await
         }
     }
 
@@ -143,12 +1274,9 @@ mod tests {
         crate::init_logger_for_test();
 
         let (_temp_dir, path_to_file) = file_hashing::fs::extra::generate_random_file(1000);
-        let mut hasher = blake2::Blake2b512::new();
-        let hash_from_test_file =
-            file_hashing::get_hash_file(path_to_file.path(), &mut hasher).unwrap();
 
-        const ADDRESS: &'static str = "127.0.0.1:45254";
-        let mut recv_socket = TcpListener::bind(ADDRESS).await.unwrap();
+        const ADDRESS: &str = "127.0.0.1:45254";
+        let mut recv_socket = TcpListener::bind(ADDRESS).await.unwrap().into();
         let mut send_socket = TcpStream::connect(ADDRESS).await.unwrap();
 
         let recv_future = detail::recv(&mut recv_socket);
@@ -158,14 +1286,214 @@ mod tests {
         recv.unwrap();
 
         let handshake = send.unwrap();
+        let mut expected = Handshake {
+            size: 1000,
+            file_name: get_file_name_from_as_ref_path(path_to_file.path())
+                .unwrap()
+                .0,
+            hash_algorithm: HashAlgorithm::Blake3,
+            extensions: Vec::new(),
+        };
+        expected
+            .set_metadata(&FileMetadata::from_metadata(
+                &std::fs::metadata(path_to_file.path()).unwrap(),
+                PreserveMetadata::all(),
+            ))
+            .unwrap();
+        assert_eq!(handshake, expected);
+    }
+
+    #[tokio::test]
+    async fn send_and_recv_handshake_in_band() {
+        crate::init_logger_for_test();
+
+        let (_temp_dir, path_to_file) = file_hashing::fs::extra::generate_random_file(1000);
+
+        const ADDRESS: &str = "127.0.0.1:45255";
+        let listener = TcpListener::bind(ADDRESS).await.unwrap();
+        let mut send_socket = TcpStream::connect(ADDRESS).await.unwrap();
+        let (mut recv_socket, _addr) = listener.accept().await.unwrap();
+
+        let handshake = get_handshake_from_file(
+            path_to_file.path(),
+            HashAlgorithm::default(),
+            PreserveMetadata::default(),
+        )
+        .await
+        .unwrap();
+        send_handshake_in_band(&handshake, &mut send_socket)
+            .await
+            .unwrap();
+        send_socket
+            .write_all(b"data after handshake")
+            .await
+            .unwrap();
+
         assert_eq!(
-            handshake,
-            Handshake {
-                hash: hash_from_test_file,
-                size: 1000,
-                file_name: get_file_name_from_as_ref_path(path_to_file)
+            recv_handshake_in_band(&mut recv_socket).await.unwrap(),
+            handshake
+        );
+
+        let mut data = [0u8; 20];
+        recv_socket.read_exact(&mut data).await.unwrap();
+        assert_eq!(&data, b"data after handshake");
+    }
+
+    #[tokio::test]
+    async fn send_resume_with_wrong_offset() {
+        crate::init_logger_for_test();
+
+        let (_temp_dir, path_to_file) = file_hashing::fs::extra::generate_random_file(1000);
+        let handshake = get_handshake_from_file(
+            path_to_file.path(),
+            HashAlgorithm::default(),
+            PreserveMetadata::default(),
+        )
+        .await
+        .unwrap();
+        let (_temp_dir_partial, path_partial) = file_hashing::fs::extra::generate_random_file(100);
+
+        const ADDRESS: &str = "127.0.0.1:45258";
+        let listener = TcpListener::bind(ADDRESS).await.unwrap();
+        let mut send_socket = TcpStream::connect(ADDRESS).await.unwrap();
+        let (mut recv_socket, _addr) = listener.accept().await.unwrap();
+
+        for (offset, is_ok) in [(0u64, true), (100, true), (50, false), (2000, false)] {
+            let sender = async {
+                let resume: Resume = recv_message(&mut send_socket).await.unwrap();
+                assert_eq!(resume.size, 100);
+                send_socket.write_all(&offset.to_be_bytes()).await.unwrap();
+            };
+            let (result, _) = tokio::join!(
+                send_resume(Some(path_partial.path()), &handshake, &mut recv_socket),
+                sender
+            );
+
+            match is_ok {
+                true => assert_eq!(result.unwrap().0, offset),
+                false => assert!(matches!(result, Err(HandshakeError::Assert(_)))),
             }
+        }
+    }
+
+    #[tokio::test]
+    async fn recv_handshake_by_parts() {
+        crate::init_logger_for_test();
+
+        let handshake = Handshake {
+            size: 1000,
+            file_name: "long_name_".repeat(1000),
+            hash_algorithm: HashAlgorithm::Xxh3,
+            extensions: vec![Extension {
+                id: 7,
+                data: b"extension".to_vec(),
+            }],
+        };
+        let bytes = handshake.to_bytes().unwrap();
+        assert!(bytes.len() > DEFAULT_BUFFER_SIZE_FOR_NETWORK);
+
+        const ADDRESS: &str = "127.0.0.1:45257";
+        let mut listener = TcpListener::bind(ADDRESS).await.unwrap().into();
+        let mut send_socket = TcpStream::connect(ADDRESS).await.unwrap();
+
+        let send_future = async {
+            for part in bytes.chunks(100) {
+                send_socket.write_all(part).await.unwrap();
+                send_socket.flush().await.unwrap();
+                tokio::time::sleep(std::time::Duration::from_millis(1)).await;
+            }
+        };
+
+        let (recv, _) = tokio::join!(recv_handshake_from_address(&mut listener), send_future);
+        assert_eq!(recv.unwrap(), handshake);
+    }
+
+    #[test]
+    fn handshake_with_unknown_version() {
+        let handshake = Handshake {
+            size: 10,
+            file_name: "file.txt".to_string(),
+            hash_algorithm: HashAlgorithm::default(),
+            extensions: Vec::new(),
+        };
+        let mut bytes = handshake.to_bytes().unwrap();
+
+        let header: [u8; HANDSHAKE_HEADER_SIZE] =
+            bytes[..HANDSHAKE_HEADER_SIZE].try_into().unwrap();
+        let size = Handshake::parse_header(&header).unwrap();
+        assert_eq!(
+            Handshake::from_body(&bytes[HANDSHAKE_HEADER_SIZE..][..size]).unwrap(),
+            handshake
         );
+
+        bytes[4..6].copy_from_slice(&(PROTOCOL_VERSION + 1).to_be_bytes());
+        let header: [u8; HANDSHAKE_HEADER_SIZE] =
+            bytes[..HANDSHAKE_HEADER_SIZE].try_into().unwrap();
+        match Handshake::parse_header(&header).err().unwrap() {
+            HandshakeError::VersionMismatch { expected, received } => {
+                assert_eq!(expected, PROTOCOL_VERSION);
+                assert_eq!(received, PROTOCOL_VERSION + 1);
+            }
+            e => panic!("{:?} != HandshakeError::VersionMismatch", e),
+        }
+
+        let mut header = header;
+        header[..4].copy_from_slice(b"{\"si");
+        assert!(matches!(
+            Handshake::parse_header(&header),
+            Err(HandshakeError::Assert(_))
+        ));
+
+        let body = &bytes[HANDSHAKE_HEADER_SIZE..];
+        assert!(matches!(
+            Handshake::from_body(&body[..body.len() - 1]),
+            Err(HandshakeError::Assert(_))
+        ));
+    }
+
+    #[test]
+    fn negotiate_capabilities() {
+        let sender = Capabilities {
+            compression: vec![Compression::None],
+            hash_algorithms: vec![HashAlgorithm::Blake3, HashAlgorithm::Sha256],
+            resume: true,
+            encryption: false,
+            authentication: false,
+            max_chunk_size: 4096,
+            preserve_metadata: PreserveMetadata::all(),
+        };
+        let mut recipient = Capabilities {
+            compression: vec![Compression::None],
+            hash_algorithms: vec![HashAlgorithm::Sha256, HashAlgorithm::Blake3],
+            resume: false,
+            encryption: true,
+            authentication: true,
+            max_chunk_size: 1024,
+            preserve_metadata: PreserveMetadata {
+                times: true,
+                ..Default::default()
+            },
+        };
+
+        assert_eq!(
+            sender.negotiate(&recipient).unwrap(),
+            NegotiatedCapabilities {
+                compression: Compression::None,
+                hash_algorithm: HashAlgorithm::Blake3,
+                resume: false,
+                encryption: false,
+                authentication: true,
+                max_chunk_size: 1024,
+                preserve_metadata: PreserveMetadata {
+                    times: true,
+                    ..Default::default()
+                },
+                nonce: Vec::new(),
+            }
+        );
+
+        recipient.hash_algorithms = vec![HashAlgorithm::Xxh3];
+        assert!(sender.negotiate(&recipient).is_err());
     }
 
     #[test]
@@ -180,4 +1508,184 @@ mod tests {
             _ => panic!("fn_test() != UdtError::Assert"),
         }
     }
+
+    /// Names that must not be used as is
+    fn get_hostile_names() -> Vec<String> {
+        [
+            "../../etc/cron.d/x",
+            "/etc/passwd",
+            "..",
+            ".",
+            "",
+            "name\0with_nul",
+            "new\nline",
+            "..\\..\\windows",
+            "dir/",
+            "\u{1b}[31mred",
+        ]
+        .into_iter()
+        .map(str::to_string)
+        .chain(["x".repeat(256)])
+        .collect()
+    }
+
+    #[test]
+    fn safe_file_names() {
+        for name in ["file.txt", ".hidden", "файл.txt", "with space", "..dots"] {
+            assert!(is_safe_file_name(name), "{:?}", name);
+
+            for policy in [
+                FileNamePolicy::Reject,
+                FileNamePolicy::Sanitize,
+                FileNamePolicy::Rename,
+            ] {
+                assert_eq!(get_safe_file_name(name, policy).unwrap(), name);
+            }
+        }
+    }
+
+    #[test]
+    fn hostile_file_names() {
+        for name in get_hostile_names() {
+            assert!(!is_safe_file_name(&name), "{:?}", name);
+
+            assert!(matches!(
+                get_safe_file_name(&name, FileNamePolicy::Reject),
+                Err(HandshakeError::UnsafeFileName(_))
+            ));
+
+            for policy in [FileNamePolicy::Sanitize, FileNamePolicy::Rename] {
+                let safe = get_safe_file_name(&name, policy).unwrap();
+                assert!(is_safe_file_name(&safe), "{:?} -> {:?}", name, safe);
+            }
+        }
+
+        assert_eq!(
+            get_safe_file_name("../../etc/cron.d/x", FileNamePolicy::Sanitize).unwrap(),
+            ".._.._etc_cron.d_x"
+        );
+        assert_eq!(
+            get_safe_file_name("..", FileNamePolicy::Sanitize).unwrap(),
+            "__"
+        );
+        assert!(get_safe_file_name("/etc/passwd", FileNamePolicy::Rename)
+            .unwrap()
+            .starts_with("file_"));
+    }
+
+    #[test]
+    fn windows_file_names() {
+        for name in ["a:b.txt", "what?", "nul.txt", "COM1", "dot."] {
+            assert_eq!(is_safe_file_name(name), !cfg!(windows), "{:?}", name);
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn non_utf8_file_name() {
+        use std::os::unix::ffi::OsStrExt;
+
+        crate::init_logger_for_test();
+
+        let (temp_dir, _path_to_file) = file_hashing::fs::extra::generate_random_file(1);
+        let raw = b"bad_\xff\xfe.txt";
+        let path = temp_dir.join(OsStr::from_bytes(raw));
+        std::fs::write(&path, b"data").unwrap();
+
+        let mut handshake = get_handshake_from_file(
+            path.as_path(),
+            HashAlgorithm::default(),
+            PreserveMetadata::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(handshake.file_name, "bad_\u{FFFD}\u{FFFD}.txt");
+        assert_eq!(handshake.get_raw_file_name(), raw);
+
+        let bytes = handshake.to_bytes().unwrap();
+        assert_eq!(
+            Handshake::from_body(&bytes[HANDSHAKE_HEADER_SIZE..]).unwrap(),
+            handshake
+        );
+
+        assert_eq!(
+            get_safe_file_name_of_handshake(
+                &handshake,
+                FileNamePolicy::Reject,
+                FileNameEncoding::Utf8
+            )
+            .unwrap(),
+            OsString::from("bad_\u{FFFD}\u{FFFD}.txt")
+        );
+        assert_eq!(
+            get_safe_file_name_of_handshake(
+                &handshake,
+                FileNamePolicy::Reject,
+                FileNameEncoding::Exact
+            )
+            .unwrap(),
+            OsStr::from_bytes(raw)
+        );
+
+        // Original bytes are checked too
+        handshake.set_extension(EXTENSION_RAW_FILE_NAME, b"../\xff".to_vec());
+        assert!(matches!(
+            get_safe_file_name_of_handshake(
+                &handshake,
+                FileNamePolicy::Reject,
+                FileNameEncoding::Exact
+            ),
+            Err(HandshakeError::UnsafeFileName(_))
+        ));
+        let safe = get_safe_file_name_of_handshake(
+            &handshake,
+            FileNamePolicy::Sanitize,
+            FileNameEncoding::Exact,
+        )
+        .unwrap();
+        assert!(is_safe_file_name(safe.to_str().unwrap()));
+    }
+
+    #[cfg(feature = "udt")]
+    #[test]
+    fn hostile_paths() {
+        let root = Path::new("/tmp/root");
+
+        assert_eq!(
+            get_safe_path(root, "dir/file.txt", FileNamePolicy::Reject).unwrap(),
+            root.join("dir").join("file.txt")
+        );
+
+        for path in [
+            "../x",
+            "dir/../../x",
+            "/etc/passwd",
+            "dir//x",
+            "dir/./x",
+            "a\0/b",
+        ] {
+            assert!(matches!(
+                get_safe_path(root, path, FileNamePolicy::Reject),
+                Err(HandshakeError::UnsafeFileName(_))
+            ));
+
+            for policy in [FileNamePolicy::Sanitize, FileNamePolicy::Rename] {
+                let safe = get_safe_path(root, path, policy).unwrap();
+                let relative = safe.strip_prefix(root).unwrap();
+                assert!(
+                    relative
+                        .components()
+                        .all(|component| matches!(component, std::path::Component::Normal(_))),
+                    "{:?} -> {:?}",
+                    path,
+                    safe
+                );
+            }
+        }
+
+        assert_eq!(
+            get_safe_path(root, "dir/../x", FileNamePolicy::Sanitize).unwrap(),
+            root.join("dir").join("__").join("x")
+        );
+    }
 }
@@ -0,0 +1,347 @@
+//! Manifest - information about all files of a directory
+//!
+//! # Description
+//!
+//! Before sending a directory, [`Sender`](crate::sender::Sender) sends the manifest:
+//! relative paths, sizes and hashes of all files and the list of directories.
+//! [`Recipient`](crate::recipient::Recipient) recreates the tree from it.
+//! Then the files are sent one after another in the order of the manifest.
+//!
+//! * Format: [json](https://github.com/serde-rs/json)
+//! * Relative paths are separated by `/`
+//! * Sent as: size of json (u64 big endian) + json
+//!
+//! Without a port for the handshake, it is sent over the connection for sending files.
+//! Directories are sent only by [`udt`](crate::protocol::udt): the manifest needs its feature.
+
+use crate::protocol::handshake::{assert_handshake, HandshakeError};
+#[cfg(feature = "udt")]
+use crate::{
+    common::{timeout, DEFAULT_BUFFER_SIZE_FOR_NETWORK},
+    core::HashAlgorithm,
+    protocol::{
+        connection::DataConnection,
+        handshake::{get_hash_of_file, Handshake},
+    },
+};
+#[cfg(feature = "udt")]
+use log::debug;
+#[cfg(feature = "udt")]
+use serde::{Deserialize, Serialize};
+use std::path::{Component, Path, PathBuf};
+use tokio::fs::canonicalize;
+#[cfg(feature = "udt")]
+use tokio::fs::read_dir;
+
+/// Max size of the manifest (json)
+#[cfg(feature = "udt")]
+pub(crate) const MAX_MANIFEST_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Info about file in the directory
+#[cfg(feature = "udt")]
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+pub(crate) struct ManifestEntry {
+    /// Relative path. Separator: `/`
+    pub(crate) path: String,
+    pub(crate) size: u64,
+    pub(crate) hash: String,
+}
+
+/// Info about directory
+#[cfg(feature = "udt")]
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub(crate) struct Manifest {
+    /// Algorithm of all hashes
+    pub(crate) hash_algorithm: HashAlgorithm,
+
+    /// Relative paths of all directories (also empty). Parents go before children
+    pub(crate) directories: Vec<String>,
+    pub(crate) files: Vec<ManifestEntry>,
+}
+
+#[cfg(feature = "udt")]
+impl ManifestEntry {
+    pub(crate) fn to_handshake(&self, hash_algorithm: HashAlgorithm) -> Handshake {
+        Handshake {
+            size: self.size,
+            file_name: self.path.clone(),
+            hash_algorithm,
+            extensions: Vec::new(),
+        }
+    }
+}
+
+/// Convert relative path to `/`-separated string
+#[cfg(feature = "udt")]
+fn relative_path_to_string(path: &Path) -> Result<String, HandshakeError> {
+    let mut parts = Vec::new();
+
+    for component in path.components() {
+        let part = component.as_os_str().to_str();
+        assert_handshake!(
+            part.is_some(),
+            "path must be UTF-8. path: {}",
+            path.display()
+        );
+        parts.push(part.unwrap());
+    }
+
+    Ok(parts.join("/"))
+}
+
+/// Convert relative path from the manifest to path inside `root`
+///
+/// Only normal components are allowed: no `..`, root or prefix
+pub(crate) fn get_path_in_root(root: &Path, relative: &str) -> Result<PathBuf, HandshakeError> {
+    let mut path = root.to_path_buf();
+
+    for part in relative.split('/') {
+        let mut components = Path::new(part).components();
+        let is_normal = matches!(
+            (components.next(), components.next()),
+            (Some(Component::Normal(_)), None)
+        );
+
+        assert_handshake!(is_normal, "invalid path in manifest: {:?}", relative);
+        path.push(part);
+    }
+
+    Ok(path)
+}
+
+/// Path of `remote_path` in `root` for giving it to the other side. Empty path is `root`
+///
+/// `root` must be canonical. Symbolic links must not lead out of `root`.
+/// Returns the reason, if the path can't be given
+pub(crate) async fn get_exported_path(root: &Path, remote_path: &str) -> Result<PathBuf, String> {
+    let path = match remote_path.is_empty() {
+        true => root.to_path_buf(),
+        false => get_path_in_root(root, remote_path)
+            .map_err(|_| format!("invalid path: {}", remote_path))?,
+    };
+
+    let path = canonicalize(path)
+        .await
+        .map_err(|_| format!("not found: {}", remote_path))?;
+
+    if !path.starts_with(root) {
+        return Err(format!("not found: {}", remote_path));
+    }
+
+    Ok(path)
+}
+
+/// Walk over the directory and hash all files
+///
+/// Symbolic links are skipped
+#[cfg(feature = "udt")]
+pub(crate) async fn get_manifest_from_dir<P>(
+    path: P,
+    hash_algorithm: HashAlgorithm,
+) -> Result<Manifest, HandshakeError>
+where
+    P: AsRef<Path>,
+{
+    assert_handshake!(path.as_ref().is_dir(), "path must be a directory");
+
+    let mut manifest = Manifest {
+        hash_algorithm,
+        ..Default::default()
+    };
+    let mut dirs_for_walk = vec![PathBuf::new()];
+
+    while let Some(relative_dir) = dirs_for_walk.pop() {
+        let mut entries = Vec::new();
+        let mut reader = read_dir(path.as_ref().join(&relative_dir)).await?;
+
+        while let Some(entry) = reader.next_entry().await? {
+            entries.push(entry);
+        }
+
+        // The same order on all platforms
+        entries.sort_by_key(|entry| entry.file_name());
+
+        for entry in entries.iter().rev() {
+            let file_type = entry.file_type().await?;
+            let relative = relative_dir.join(entry.file_name());
+
+            if file_type.is_dir() {
+                manifest
+                    .directories
+                    .push(relative_path_to_string(&relative)?);
+                dirs_for_walk.push(relative);
+            } else if file_type.is_file() {
+                let hash = get_hash_of_file(&entry.path(), hash_algorithm).await?;
+
+                manifest.files.push(ManifestEntry {
+                    path: relative_path_to_string(&relative)?,
+                    size: entry.metadata().await?.len(),
+                    hash,
+                });
+            } else {
+                debug!("skip not regular file: {}", entry.path().display());
+            }
+        }
+    }
+
+    debug!(
+        "manifest. directories: {}; files: {}",
+        manifest.directories.len(),
+        manifest.files.len()
+    );
+
+    Ok(manifest)
+}
+
+/// Send manifest over `connection`
+#[cfg(feature = "udt")]
+pub(crate) async fn send_manifest(
+    manifest: &Manifest,
+    connection: &mut impl DataConnection,
+) -> Result<(), HandshakeError> {
+    let json = serde_json::to_vec(manifest)?;
+    assert_handshake!(
+        (json.len() as u64).le(&MAX_MANIFEST_SIZE),
+        "Manifest is too big. json size: {}",
+        json.len()
+    );
+
+    timeout!(
+        connection.send_data(&(json.len() as u64).to_be_bytes()),
+        |_| HandshakeError::TimeoutExpired
+    )??;
+
+    for chunk in json.chunks(DEFAULT_BUFFER_SIZE_FOR_NETWORK) {
+        timeout!(connection.send_data(chunk), |_| {
+            HandshakeError::TimeoutExpired
+        })??;
+    }
+    debug!("Done manifest send. Size: {}", json.len());
+
+    Ok(())
+}
+
+/// Send manifest to `socket`. If `socket` is `None` or `connection` is secure,
+/// send it over `connection`
+#[cfg(feature = "udt")]
+pub(crate) async fn send_manifest_to(
+    manifest: &Manifest,
+    connection: &mut impl DataConnection,
+    socket: Option<&mut impl DataConnection>,
+) -> Result<(), HandshakeError> {
+    match socket {
+        Some(socket) if !connection.is_secure() => send_manifest(manifest, socket).await,
+        _ => send_manifest(manifest, connection).await,
+    }
+}
+
+/// Receive manifest sent by [`send_manifest`]
+#[cfg(feature = "udt")]
+///
+/// [`Sender`](crate::sender::Sender) hashes all files before connecting, so the manifest
+/// comes right after the negotiation
+pub(crate) async fn recv_manifest(
+    connection: &mut impl DataConnection,
+) -> Result<Manifest, HandshakeError> {
+    let mut size = [0u8; 8];
+    timeout!(connection.recv_exact(&mut size), |_| {
+        HandshakeError::TimeoutExpired
+    })??;
+
+    let size = u64::from_be_bytes(size);
+    assert_handshake!(
+        size.le(&MAX_MANIFEST_SIZE),
+        "Manifest is too big. json size: {}",
+        size
+    );
+
+    let mut json = vec![0u8; size as usize];
+    timeout!(connection.recv_exact(&mut json), |_| {
+        HandshakeError::TimeoutExpired
+    })??;
+
+    let manifest: Manifest = serde_json::from_slice(&json)?;
+    for path in manifest
+        .directories
+        .iter()
+        .chain(manifest.files.iter().map(|entry| &entry.path))
+    {
+        get_path_in_root(Path::new(""), path)?;
+    }
+
+    Ok(manifest)
+}
+
+/// Receive manifest from `socket`. If `socket` is `None` or `connection` is secure,
+/// receive it from `connection`
+#[cfg(feature = "udt")]
+pub(crate) async fn recv_manifest_from(
+    connection: &mut impl DataConnection,
+    socket: Option<&mut impl DataConnection>,
+) -> Result<Manifest, HandshakeError> {
+    match socket {
+        Some(socket) if !connection.is_secure() => recv_manifest(socket).await,
+        _ => recv_manifest(connection).await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "udt")]
+    #[tokio::test]
+    async fn send_and_recv_manifest() {
+        use tokio::net::{TcpListener, TcpStream};
+
+        crate::init_logger_for_test();
+
+        let (temp_dir, _path_to_file) = file_hashing::fs::extra::generate_random_file(1000);
+        std::fs::create_dir_all(temp_dir.join("dir/empty")).unwrap();
+        std::fs::write(temp_dir.join("dir/file.txt"), b"test data").unwrap();
+
+        let manifest = get_manifest_from_dir(temp_dir.path(), HashAlgorithm::Sha256)
+            .await
+            .unwrap();
+        assert_eq!(manifest.directories, vec!["dir", "dir/empty"]);
+        assert_eq!(manifest.files.len(), 2);
+        assert!(manifest
+            .files
+            .iter()
+            .any(|entry| entry.path == "dir/file.txt" && entry.size == 9));
+
+        const ADDRESS: &str = "127.0.0.1:45256";
+        let listener = TcpListener::bind(ADDRESS).await.unwrap();
+        let mut send_socket = TcpStream::connect(ADDRESS).await.unwrap();
+        let (mut recv_socket, _addr) = listener.accept().await.unwrap();
+
+        let (send, recv) = tokio::join!(
+            send_manifest(&manifest, &mut send_socket),
+            recv_manifest(&mut recv_socket)
+        );
+
+        send.unwrap();
+        assert_eq!(recv.unwrap(), manifest);
+    }
+
+    #[test]
+    fn path_in_root() {
+        let root = Path::new("/tmp/root");
+
+        assert_eq!(
+            get_path_in_root(root, "dir/file.txt").unwrap(),
+            root.join("dir").join("file.txt")
+        );
+
+        for path in [
+            "../file.txt",
+            "dir/../../file.txt",
+            "/etc/passwd",
+            "",
+            "dir//file.txt",
+            ".",
+        ] {
+            assert!(get_path_in_root(root, path).is_err(), "path: {}", path);
+        }
+    }
+}
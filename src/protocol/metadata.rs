@@ -0,0 +1,376 @@
+//! Metadata of the file in [`Handshake`]. See [`PreserveMetadata`]
+//!
+//! # Description
+//!
+//! [`Recipient`](crate::recipient::Recipient) asks for the fields at the negotiation.
+//! [`Sender`](crate::sender::Sender) adds the extension `4` to the handshake only with them.
+//! All fields are optional: other platforms don't have some of them.
+//!
+//! Format of the extension: flags of the present fields (u8), then only the present fields
+//! in this order. Numbers are big endian.
+//!
+//! | Field    | Flag   | Type                                                        |
+//! |----------|--------|-------------------------------------------------------------|
+//! | mode     | `0x01` | u32                                                         |
+//! | modified | `0x02` | u64 (seconds since the Unix epoch) + u32 (nanoseconds)      |
+//! | accessed | `0x04` | u64 (seconds since the Unix epoch) + u32 (nanoseconds)      |
+//! | uid      | `0x08` | u32                                                         |
+//! | gid      | `0x10` | u32                                                         |
+//! | user     | `0x20` | u16 (size) + UTF-8                                          |
+//! | group    | `0x40` | u16 (size) + UTF-8                                          |
+//!
+//! [`Recipient`](crate::recipient::Recipient) applies the chosen fields after the hash check:
+//! times, then the owner and the group, then the mode.
+
+use super::{
+    error::ProtocolError,
+    handshake::{assert_handshake, put_field, BodyReader, Handshake, HandshakeError},
+};
+use crate::core::{CoreConfig, PreserveMetadata};
+use log::debug;
+use std::{
+    fs::{FileTimes, Metadata, OpenOptions},
+    path::Path,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// Only permissions. setuid, setgid and sticky bits are not applied
+#[cfg(unix)]
+const MODE_MASK: u32 = 0o777;
+
+/// Flags of the fields in the extension. See [module](self)
+const FLAG_MODE: u8 = 0x01;
+const FLAG_MODIFIED: u8 = 0x02;
+const FLAG_ACCESSED: u8 = 0x04;
+const FLAG_UID: u8 = 0x08;
+const FLAG_GID: u8 = 0x10;
+const FLAG_USER: u8 = 0x20;
+const FLAG_GROUP: u8 = 0x40;
+
+const NANOS_PER_SEC: u32 = 1_000_000_000;
+
+/// Metadata of the file from [`Sender`](crate::sender::Sender)
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub(crate) struct FileMetadata {
+    /// Unix mode with the type of the file
+    pub(crate) mode: Option<u32>,
+    pub(crate) modified: Option<SystemTime>,
+    pub(crate) accessed: Option<SystemTime>,
+    pub(crate) uid: Option<u32>,
+    pub(crate) gid: Option<u32>,
+
+    /// Name of the owner
+    pub(crate) user: Option<String>,
+
+    /// Name of the group
+    pub(crate) group: Option<String>,
+}
+
+impl FileMetadata {
+    /// Fields of the file on this platform chosen by `preserve`
+    #[cfg_attr(not(unix), allow(unused_mut))]
+    pub(crate) fn from_metadata(metadata: &Metadata, preserve: PreserveMetadata) -> Self {
+        let mut file_metadata = FileMetadata::default();
+        if preserve.times {
+            file_metadata.modified = metadata.modified().ok();
+            file_metadata.accessed = metadata.accessed().ok();
+        }
+
+        #[cfg(unix)]
+        {
+            use nix::unistd::{Gid, Group, Uid, User};
+            use std::os::unix::fs::MetadataExt;
+
+            if preserve.mode {
+                file_metadata.mode = Some(metadata.mode());
+            }
+
+            // Names of accounts are sent only if they are needed
+            if preserve.ownership {
+                file_metadata.uid = Some(metadata.uid());
+                file_metadata.gid = Some(metadata.gid());
+                file_metadata.user = User::from_uid(Uid::from_raw(metadata.uid()))
+                    .ok()
+                    .flatten()
+                    .map(|user| user.name);
+                file_metadata.group = Group::from_gid(Gid::from_raw(metadata.gid()))
+                    .ok()
+                    .flatten()
+                    .map(|group| group.name);
+            }
+        }
+
+        file_metadata
+    }
+
+    /// Binary form for the extension. See [module](self)
+    pub(crate) fn to_bytes(&self) -> Result<Vec<u8>, HandshakeError> {
+        let mut flags = 0;
+        let mut fields = Vec::new();
+
+        if let Some(mode) = self.mode {
+            flags |= FLAG_MODE;
+            fields.extend_from_slice(&mode.to_be_bytes());
+        }
+        for (flag, time) in [
+            (FLAG_MODIFIED, self.modified),
+            (FLAG_ACCESSED, self.accessed),
+        ] {
+            // Times before the Unix epoch are not sent
+            if let Some(time) = time.and_then(|time| time.duration_since(UNIX_EPOCH).ok()) {
+                flags |= flag;
+                fields.extend_from_slice(&time.as_secs().to_be_bytes());
+                fields.extend_from_slice(&time.subsec_nanos().to_be_bytes());
+            }
+        }
+        for (flag, id) in [(FLAG_UID, self.uid), (FLAG_GID, self.gid)] {
+            if let Some(id) = id {
+                flags |= flag;
+                fields.extend_from_slice(&id.to_be_bytes());
+            }
+        }
+        for (flag, name) in [(FLAG_USER, &self.user), (FLAG_GROUP, &self.group)] {
+            if let Some(name) = name {
+                flags |= flag;
+                put_field(&mut fields, name.as_bytes())?;
+            }
+        }
+
+        let mut bytes = Vec::with_capacity(1 + fields.len());
+        bytes.push(flags);
+        bytes.extend_from_slice(&fields);
+
+        Ok(bytes)
+    }
+
+    /// Parse the extension made by [`FileMetadata::to_bytes`]
+    pub(crate) fn from_bytes(bytes: &[u8]) -> Result<Self, HandshakeError> {
+        let mut reader = BodyReader::new(bytes);
+        let flags = reader.u8()?;
+        let has = |flag: u8| flags & flag != 0;
+
+        let mut metadata = FileMetadata::default();
+        if has(FLAG_MODE) {
+            metadata.mode = Some(reader.u32()?);
+        }
+        if has(FLAG_MODIFIED) {
+            metadata.modified = Some(read_time(&mut reader)?);
+        }
+        if has(FLAG_ACCESSED) {
+            metadata.accessed = Some(read_time(&mut reader)?);
+        }
+        if has(FLAG_UID) {
+            metadata.uid = Some(reader.u32()?);
+        }
+        if has(FLAG_GID) {
+            metadata.gid = Some(reader.u32()?);
+        }
+        if has(FLAG_USER) {
+            metadata.user = Some(read_name(&mut reader)?);
+        }
+        if has(FLAG_GROUP) {
+            metadata.group = Some(read_name(&mut reader)?);
+        }
+
+        Ok(metadata)
+    }
+
+    /// Owner and group on this side: by names, if they are known, otherwise by numbers
+    #[cfg(unix)]
+    fn get_owner(&self) -> (Option<u32>, Option<u32>) {
+        use nix::unistd::{Group, User};
+
+        let uid = self
+            .user
+            .as_deref()
+            .and_then(|name| User::from_name(name).ok().flatten())
+            .map(|user| user.uid.as_raw())
+            .or(self.uid);
+        let gid = self
+            .group
+            .as_deref()
+            .and_then(|name| Group::from_name(name).ok().flatten())
+            .map(|group| group.gid.as_raw())
+            .or(self.gid);
+
+        (uid, gid)
+    }
+
+    /// Apply the fields chosen by `preserve` to the file of `path`
+    pub(crate) fn apply(&self, path: &Path, preserve: PreserveMetadata) -> std::io::Result<()> {
+        if preserve.times && (self.modified.is_some() || self.accessed.is_some()) {
+            let mut times = FileTimes::new();
+            if let Some(modified) = self.modified {
+                times = times.set_modified(modified);
+            }
+            if let Some(accessed) = self.accessed {
+                times = times.set_accessed(accessed);
+            }
+            OpenOptions::new()
+                .write(true)
+                .open(path)?
+                .set_times(times)?;
+        }
+
+        #[cfg(unix)]
+        {
+            use std::{
+                fs::{set_permissions, Permissions},
+                os::unix::fs::{chown, PermissionsExt},
+            };
+
+            if preserve.ownership {
+                let (uid, gid) = self.get_owner();
+                chown(path, uid, gid)?;
+            }
+
+            if let (true, Some(mode)) = (preserve.mode, self.mode) {
+                set_permissions(path, Permissions::from_mode(mode & MODE_MASK))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Time of [`FileMetadata::to_bytes`]
+fn read_time(reader: &mut BodyReader<'_>) -> Result<SystemTime, HandshakeError> {
+    let secs = reader.u64()?;
+    let nanos = reader.u32()?;
+    assert_handshake!(
+        nanos.lt(&NANOS_PER_SEC),
+        "invalid time. nanoseconds: {}",
+        nanos
+    );
+
+    let time = UNIX_EPOCH.checked_add(Duration::new(secs, nanos));
+    assert_handshake!(time.is_some(), "invalid time. seconds: {}", secs);
+
+    Ok(time.unwrap())
+}
+
+/// Name of the owner or the group of [`FileMetadata::to_bytes`]
+fn read_name(reader: &mut BodyReader<'_>) -> Result<String, HandshakeError> {
+    let name = String::from_utf8(reader.field()?.to_vec());
+    assert_handshake!(name.is_ok(), "name of the owner must be UTF-8");
+
+    Ok(name.unwrap())
+}
+
+/// [`PreserveMetadata`] of `config` or default
+pub(crate) fn get_preserve_metadata(config: &Option<impl CoreConfig>) -> PreserveMetadata {
+    config
+        .as_ref()
+        .map(|config| config.get_preserve_metadata())
+        .unwrap_or_default()
+}
+
+/// Apply the metadata of `handshake` to the received file, if `config` wants it.
+/// The file must be already checked
+pub(crate) fn apply_metadata_of_handshake(
+    path: &Path,
+    handshake: &Handshake,
+    config: &Option<impl CoreConfig>,
+) -> Result<(), ProtocolError> {
+    let preserve = get_preserve_metadata(config);
+    if !preserve.is_any() {
+        return Ok(());
+    }
+
+    match handshake.get_metadata()? {
+        Some(metadata) => {
+            debug!("apply metadata: {:?}; preserve: {:?}", metadata, preserve);
+            metadata
+                .apply(path, preserve)
+                .map_err(ProtocolError::FileIO)
+        }
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn apply_metadata() {
+        crate::init_logger_for_test();
+
+        let (_temp_dir, path) = file_hashing::fs::extra::generate_random_file(100);
+        let modified = SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_000_000_000);
+        let metadata = FileMetadata {
+            mode: Some(0o104755),
+            modified: Some(modified),
+            ..Default::default()
+        };
+
+        metadata
+            .apply(path.path(), PreserveMetadata::default())
+            .unwrap();
+        assert_ne!(
+            std::fs::metadata(path.path()).unwrap().modified().unwrap(),
+            modified
+        );
+
+        metadata
+            .apply(
+                path.path(),
+                PreserveMetadata {
+                    mode: true,
+                    times: true,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+        let applied = std::fs::metadata(path.path()).unwrap();
+        assert_eq!(applied.modified().unwrap(), modified);
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            // Without setuid
+            assert_eq!(applied.permissions().mode() & 0o7777, 0o755);
+        }
+    }
+
+    #[test]
+    fn metadata_to_bytes_and_back() {
+        let metadata = FileMetadata {
+            mode: Some(0o100644),
+            modified: Some(UNIX_EPOCH + Duration::new(1_000_000_000, 123)),
+            accessed: None,
+            uid: Some(1000),
+            gid: Some(1000),
+            user: Some("user".to_string()),
+            group: None,
+        };
+        let bytes = metadata.to_bytes().unwrap();
+        assert_eq!(FileMetadata::from_bytes(&bytes).unwrap(), metadata);
+        assert!(FileMetadata::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+
+        assert_eq!(FileMetadata::default().to_bytes().unwrap(), vec![0]);
+
+        // Only the chosen fields
+        let (_temp_dir, path) = file_hashing::fs::extra::generate_random_file(100);
+        let chosen = FileMetadata::from_metadata(
+            &std::fs::metadata(path.path()).unwrap(),
+            PreserveMetadata {
+                times: true,
+                ..Default::default()
+            },
+        );
+        assert!(chosen.modified.is_some());
+        assert_eq!(
+            (
+                chosen.mode,
+                chosen.uid,
+                chosen.gid,
+                chosen.user,
+                chosen.group
+            ),
+            (None, None, None, None, None)
+        );
+    }
+}
@@ -0,0 +1,132 @@
+//! More detailed functions for [`quic`](crate::protocol::quic)
+
+use super::QuicError;
+use crate::{
+    common::timeout,
+    prelude::{ConfigRecipient, ConfigSender},
+    protocol::{connection::DataConnection, error::ProtocolError},
+};
+use async_trait::async_trait;
+use log::debug;
+use quinn::{ClientConfig, Connection, Endpoint, RecvStream, SendStream, ServerConfig};
+use rustls::{Certificate, PrivateKey, RootCertStore};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+/// TLS settings for [`quic`](crate::protocol::quic)
+#[derive(Clone)]
+pub(crate) struct QuicTlsConfig {
+    /// Certificate chain and private key of [`Recipient`](crate::recipient::Recipient)
+    pub(crate) certificate: Option<(Vec<Certificate>, PrivateKey)>,
+
+    /// Certificates that [`Sender`](crate::sender::Sender) trusts
+    pub(crate) trusted_certificates: Vec<Certificate>,
+
+    /// Name for checking the certificate of [`Recipient`](crate::recipient::Recipient)
+    pub(crate) server_name: String,
+}
+
+impl Default for QuicTlsConfig {
+    fn default() -> Self {
+        Self {
+            certificate: None,
+            trusted_certificates: Vec::new(),
+            server_name: "localhost".to_string(),
+        }
+    }
+}
+
+/// Bidirectional QUIC stream
+pub(crate) struct QuicStream {
+    pub(crate) send: SendStream,
+    pub(crate) recv: RecvStream,
+}
+
+#[async_trait(?Send)]
+impl DataConnection for QuicStream {
+    async fn send_data(&mut self, buf: &[u8]) -> std::io::Result<()> {
+        Ok(self.send.write_all(buf).await?)
+    }
+
+    async fn recv_data(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        Ok(self.recv.read(buf).await?.unwrap_or(0))
+    }
+}
+
+/// Make connection for [`Sender`](crate::sender::Sender)
+pub(crate) async fn connect_for_sender(
+    config: &ConfigSender<'_>,
+) -> Result<(Endpoint, Connection), QuicError> {
+    debug!("run connect_for_sender for quic. Config: {:?}", config);
+
+    let mut roots = RootCertStore::empty();
+    for certificate in config.quic_tls.trusted_certificates.iter() {
+        roots.add(certificate).map_err(QuicError::Tls)?;
+    }
+
+    let bind_addr = match config.addr {
+        IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+        IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+    };
+
+    let mut endpoint = Endpoint::client(SocketAddr::new(bind_addr, 0))
+        .map_err(|e| QuicError::Protocol(ProtocolError::Bind(e)))?;
+    endpoint.set_default_client_config(ClientConfig::with_root_certificates(roots));
+
+    let connecting = endpoint
+        .connect(
+            SocketAddr::new(config.addr, config.port_for_send_files),
+            &config.quic_tls.server_name,
+        )
+        .map_err(QuicError::Connect)?;
+
+    let connection = timeout!(
+        connecting,
+        |_| QuicError::Protocol(ProtocolError::TimeoutExpired),
+        config.timeout
+    )?
+    .map_err(QuicError::Connection)?;
+    debug!("done quic connect");
+
+    Ok((endpoint, connection))
+}
+
+/// Make bind and accept connection for [`Recipient`](crate::recipient::Recipient)
+pub(crate) async fn accept_for_recipient(
+    config: &ConfigRecipient<'_>,
+) -> Result<(Endpoint, Connection), QuicError> {
+    debug!("run accept_for_recipient for quic. Config: {:?}", config);
+
+    let (cert_chain, private_key) = config.quic_tls.certificate.clone().ok_or_else(|| {
+        QuicError::Assert("certificate for quic is not set. Use set_quic_certificate".to_string())
+    })?;
+    let server_config =
+        ServerConfig::with_single_cert(cert_chain, private_key).map_err(QuicError::Tls)?;
+
+    let endpoint = Endpoint::server(
+        server_config,
+        SocketAddr::new(config.addr, config.port_for_send_files),
+    )
+    .map_err(|e| QuicError::Protocol(ProtocolError::Bind(e)))?;
+    debug!("done quic bind");
+
+    let connecting = timeout!(
+        endpoint.accept(),
+        |_| QuicError::Protocol(ProtocolError::TimeoutExpired),
+        config.timeout
+    )?
+    .ok_or_else(|| {
+        QuicError::Protocol(ProtocolError::Accept(
+            std::io::ErrorKind::NotConnected.into(),
+        ))
+    })?;
+
+    let connection = timeout!(
+        connecting,
+        |_| QuicError::Protocol(ProtocolError::TimeoutExpired),
+        config.timeout
+    )?
+    .map_err(QuicError::Connection)?;
+    debug!("accepted connection from {}", connection.remote_address());
+
+    Ok((endpoint, connection))
+}
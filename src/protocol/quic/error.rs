@@ -0,0 +1,62 @@
+//! All error in [`quic`](crate::protocol::quic)
+
+use crate::protocol::error::ProtocolError;
+use thiserror::Error;
+
+/// Enum error
+#[derive(Debug, Error)]
+pub enum QuicError {
+    #[error("problem in protocol: {0}")]
+    Protocol(ProtocolError),
+
+    /// Certificate or private key is invalid
+    #[error("TLS configuration")]
+    Tls(#[source] rustls::Error),
+
+    /// [`Sender`](crate::sender::Sender) can't start the connection.
+    /// For example, invalid server name
+    #[error("start connection")]
+    Connect(#[source] quinn::ConnectError),
+
+    /// QUIC connection is lost or closed
+    #[error("connection")]
+    Connection(#[source] quinn::ConnectionError),
+
+    /// Wrong use function in [quic](crate::protocol::quic)
+    #[error("wrong use function: {0}")]
+    Assert(String),
+}
+
+/// [`std::assert`], but for [`QuicError`]
+///
+/// # Example
+///
+/// See unit tests
+macro_rules! assert_quic {
+    ($for_check:expr, $($message_error:tt)*) => {
+        if $for_check == false {
+            log::error!("assert quic! message_error: {}", format!($($message_error)*));
+            return Err(crate::protocol::quic::QuicError::Assert(format!($($message_error)*)));
+        }
+    };
+}
+
+pub(crate) use assert_quic;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn macro_assert_quic() {
+        let fn_test = || -> Result<(), QuicError> {
+            assert_quic!(false, "test message: {}", "test value");
+            Ok(())
+        };
+
+        match fn_test().err().unwrap() {
+            QuicError::Assert(message) => assert_eq!(message, "test message: test value"),
+            _ => panic!("fn_test() != QuicError::Assert"),
+        }
+    }
+}
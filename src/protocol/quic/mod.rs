@@ -0,0 +1,123 @@
+//! [QUIC](https://en.wikipedia.org/wiki/QUIC) implementation
+//!
+//! Handshake and file are sent over streams of one QUIC connection.
+//! Only one UDP port is needed: `port_for_send_files`.
+//!
+//! QUIC has built-in TLS, congestion control and connection migration.
+//!
+//! # Example
+//!
+//! ```no_run
+//! # use snwf::prelude::*;
+//! # use snwf::protocol::quic::{Certificate, PrivateKey};
+//! # use std::path::Path;
+//! #
+//! #[tokio::main]
+//! async fn main() {
+//!    let certificate = Certificate(std::fs::read("cert.der").unwrap());
+//!    let private_key = PrivateKey(std::fs::read("key.der").unwrap());
+//!
+//!    let mut sender = Sender::new("127.0.0.1".parse().unwrap(), 4324, 6343);
+//!    let mut recipient = Recipient::new("::0".parse().unwrap(), 4324, 6343);
+//!
+//!    sender.add_quic_trusted_certificate(certificate.clone());
+//!    recipient.set_quic_certificate(vec![certificate], private_key);
+//!    
+//!    let (recv, send) = tokio::join!(
+//!        recipient.quic_recv_file(Path::new("other_file.txt")),
+//!        sender.quic_send_file(Path::new("file_for_send.txt"))
+//!    );
+//!    
+//!    send.unwrap();
+//!    recv.unwrap();
+//! }
+//! ```
+//!
+//! # How it works?
+//!
+//! 1. We open a bidirectional stream
+//! 2. Agree with the recipient on capabilities over it
+//!    (see [`NegotiatedCapabilities`](crate::core::NegotiatedCapabilities))
+//! 3. Send a handshake over it (see [in-band handshake](crate::protocol::handshake))
+//! 4. Send the file and its checksum over the same stream
+//!
+//! # What libraries to use
+//!
+//! * [`quinn`](https://github.com/quinn-rs/quinn) - pure-Rust implementation of QUIC
+//! * [`rustls`](https://github.com/rustls/rustls) - TLS for QUIC
+
+mod detail;
+pub mod error;
+
+pub mod quic_recipient;
+pub mod quic_sender;
+
+pub(crate) use detail::QuicTlsConfig;
+pub use error::QuicError;
+pub use quic_recipient::QuicRecipient;
+pub use quic_sender::QuicSender;
+pub use rustls::{Certificate, PrivateKey};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{common::get_hasher, prelude::*};
+
+    fn generate_certificate() -> (Certificate, PrivateKey) {
+        let certificate = rcgen::generate_simple_self_signed(vec!["localhost".into()]).unwrap();
+
+        (
+            Certificate(certificate.serialize_der().unwrap()),
+            PrivateKey(certificate.serialize_private_key_der()),
+        )
+    }
+
+    #[tokio::test]
+    async fn send_and_recv_quic() {
+        crate::init_logger_for_test();
+
+        let (temp_dir, path_input) = file_hashing::fs::extra::generate_random_file(43526);
+        let path_output = temp_dir.join("tess_file.txt");
+        let (certificate, private_key) = generate_certificate();
+
+        let mut sender = Sender::new("127.0.0.1".parse().unwrap(), 4294, 0);
+        let mut recipient = Recipient::new("127.0.0.1".parse().unwrap(), 4294, 0);
+
+        sender.add_quic_trusted_certificate(certificate.clone());
+        recipient.set_quic_certificate(vec![certificate], private_key);
+
+        let (recv, send) = tokio::join!(
+            recipient.quic_recv_file(path_output.as_path()),
+            sender.quic_send_file(path_input.path())
+        );
+
+        send.unwrap();
+        recv.unwrap();
+
+        let hash_input = file_hashing::get_hash_file(path_input, &mut get_hasher()).unwrap();
+        let hash_output = file_hashing::get_hash_file(path_output, &mut get_hasher()).unwrap();
+
+        assert_eq!(hash_input, hash_output);
+    }
+
+    #[tokio::test]
+    async fn send_quic_with_untrusted_certificate() {
+        crate::init_logger_for_test();
+
+        let (_temp_dir, path_input) = file_hashing::fs::extra::generate_random_file(100);
+        let (output_dir, _path_input) = file_hashing::fs::extra::generate_random_file(1);
+        let (certificate, private_key) = generate_certificate();
+
+        let mut sender = Sender::new("127.0.0.1".parse().unwrap(), 4295, 0);
+        let mut recipient = Recipient::new("127.0.0.1".parse().unwrap(), 4295, 0);
+        recipient.set_quic_certificate(vec![certificate], private_key);
+
+        let (recv, send) = tokio::join!(
+            recipient.quic_recv_file_with_original_file_name(output_dir.path()),
+            sender.quic_send_file(path_input.path())
+        );
+
+        assert!(send.is_err());
+        assert!(recv.is_err());
+    }
+}
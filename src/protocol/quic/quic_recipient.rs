@@ -0,0 +1,205 @@
+//! Implementation [quic](https://en.wikipedia.org/wiki/QUIC) for trait [`CoreRecipient`]
+
+use super::QuicError;
+use crate::{
+    common::timeout,
+    core::NegotiatedCapabilities,
+    prelude::*,
+    protocol::{
+        connection::SecureConnection,
+        error::ProtocolError,
+        handshake::{recv_handshake_in_band, Handshake},
+        quic::{
+            detail::{self, QuicStream},
+            error::assert_quic,
+        },
+        raw,
+    },
+};
+use async_trait::async_trait;
+use log::debug;
+use quinn::Connection;
+use rustls::{Certificate, PrivateKey};
+use std::path::Path;
+
+/// [QUIC](https://en.wikipedia.org/wiki/QUIC) trait for [`CoreRecipient`]
+#[async_trait(?Send)]
+pub trait QuicRecipient<'a>: CoreRecipient<'a> {
+    /// Set certificate for TLS. **Required!**
+    ///
+    /// # Arguments
+    ///
+    /// * `cert_chain` - DER-encoded certificate chain.
+    /// * `private_key` - DER-encoded private key for the first certificate.
+    fn set_quic_certificate(&mut self, cert_chain: Vec<Certificate>, private_key: PrivateKey);
+
+    /// Receive a file via [quic](https://en.wikipedia.org/wiki/QUIC) protocol
+    ///
+    /// # Arguments
+    ///
+    /// * `output` - path to save file.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use snwf::prelude::*;
+    /// # use snwf::protocol::quic::{Certificate, PrivateKey};
+    /// # use std::path::Path;
+    /// #
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let certificate = std::fs::read("cert.der").unwrap();
+    ///     let private_key = std::fs::read("key.der").unwrap();
+    ///
+    ///     let mut recipient = Recipient::new("::0".parse().unwrap(), 4324, 6343);
+    ///     recipient.set_quic_certificate(
+    ///         vec![Certificate(certificate)],
+    ///         PrivateKey(private_key),
+    ///     );
+    ///
+    ///     recipient.quic_recv_file(Path::new("file.txt"));
+    /// }
+    /// ```
+    ///
+    /// **Warning:** not save original file name! If we want save it,
+    /// use [`QuicRecipient::quic_recv_file_with_original_file_name`]
+    async fn quic_recv_file<P>(&mut self, output: P) -> Result<(), QuicError>
+    where
+        P: AsRef<Path> + Send + Copy + Sync;
+
+    /// Receive a file via [quic](https://en.wikipedia.org/wiki/QUIC) protocol
+    ///
+    /// **But save original name** (not save [`QuicRecipient::quic_recv_file`])
+    ///
+    /// Unsafe names (`../x`, `/etc/x` and etc.) are handled by
+    /// [`FileNamePolicy`](crate::protocol::handshake::FileNamePolicy), names that are
+    /// not UTF-8 - by [`FileNameEncoding`](crate::protocol::handshake::FileNameEncoding)
+    ///
+    /// # Arguments
+    ///
+    /// * `output` - path to save file.
+    async fn quic_recv_file_with_original_file_name<P>(
+        &mut self,
+        output: P,
+    ) -> Result<(), QuicError>
+    where
+        P: AsRef<Path> + Send + Copy + Sync;
+}
+
+/// Negotiate capabilities, get handshake and data stream from [`Sender`](crate::sender::Sender)
+async fn accept_streams(
+    connection: &Connection,
+    config: &mut ConfigRecipient<'_>,
+) -> Result<
+    (
+        NegotiatedCapabilities,
+        Handshake,
+        SecureConnection<QuicStream>,
+    ),
+    QuicError,
+> {
+    let (send, recv) = timeout!(
+        connection.accept_bi(),
+        |_| QuicError::Protocol(ProtocolError::TimeoutExpired),
+        config.timeout
+    )?
+    .map_err(QuicError::Connection)?;
+    let stream = QuicStream { send, recv };
+
+    let (negotiated, mut stream) = raw::negotiate_for_recipient(stream, config)
+        .await
+        .map_err(QuicError::Protocol)?;
+
+    let handshake = recv_handshake_in_band(&mut stream)
+        .await
+        .map_err(|e| QuicError::Protocol(ProtocolError::Handshake(e)))?;
+
+    Ok((negotiated, handshake, stream))
+}
+
+#[async_trait(?Send)]
+impl<'a> QuicRecipient<'a> for Recipient<'a> {
+    fn set_quic_certificate(&mut self, cert_chain: Vec<Certificate>, private_key: PrivateKey) {
+        self.config.quic_tls.certificate = Some((cert_chain, private_key));
+    }
+
+    async fn quic_recv_file<P>(&mut self, output: P) -> Result<(), QuicError>
+    where
+        P: AsRef<Path> + Send + Copy + Sync,
+    {
+        assert_quic!(
+            !output.as_ref().exists(),
+            "output must be no exists. output path: {}",
+            output.as_ref().display()
+        );
+
+        let mut config = self.get_config();
+        debug!("running quic_recv_file; config: {:?}", config);
+
+        let (endpoint, connection) = detail::accept_for_recipient(&config).await?;
+        let (negotiated, handshake, mut stream) = accept_streams(&connection, &mut config).await?;
+        self.negotiated_capabilities = Some(negotiated);
+
+        let (_, signer) = raw::recv_file(
+            &mut stream,
+            output,
+            &Some(config),
+            0,
+            handshake,
+            false,
+            connection.remote_address(),
+        )
+        .await
+        .map_err(QuicError::Protocol)?;
+        self.signer = signer;
+
+        connection.close(0u32.into(), b"done");
+        endpoint.wait_idle().await;
+
+        Ok(())
+    }
+
+    async fn quic_recv_file_with_original_file_name<P>(
+        &mut self,
+        output: P,
+    ) -> Result<(), QuicError>
+    where
+        P: AsRef<Path> + Send + Copy + Sync,
+    {
+        assert_quic!(output.as_ref().is_dir(), "output must be a folder path");
+
+        let mut config = self.get_config();
+        debug!("running quic_recv_file; config: {:?}", config);
+
+        let (endpoint, connection) = detail::accept_for_recipient(&config).await?;
+        let (negotiated, handshake, mut stream) = accept_streams(&connection, &mut config).await?;
+        self.negotiated_capabilities = Some(negotiated);
+
+        let path = raw::get_path_for_file(
+            &mut stream,
+            output.as_ref(),
+            &handshake,
+            config.file_name_policy,
+            config.file_name_encoding,
+        )
+        .await
+        .map_err(QuicError::Protocol)?;
+
+        let (_, signer) = raw::recv_file(
+            &mut stream,
+            path.as_path(),
+            &Some(config),
+            0,
+            handshake,
+            false,
+            connection.remote_address(),
+        )
+        .await
+        .map_err(QuicError::Protocol)?;
+        self.signer = signer;
+
+        connection.close(0u32.into(), b"done");
+        endpoint.wait_idle().await;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,114 @@
+//! Implementation [quic](https://en.wikipedia.org/wiki/QUIC) for trait [`CoreSender`]
+
+use super::QuicError;
+use crate::{
+    common::timeout,
+    prelude::*,
+    protocol::{
+        error::ProtocolError,
+        quic::{
+            detail::{self, QuicStream},
+            error::assert_quic,
+        },
+        raw,
+    },
+};
+use async_trait::async_trait;
+use log::debug;
+use rustls::Certificate;
+use std::fmt::Debug;
+use std::path::Path;
+
+/// [QUIC](https://en.wikipedia.org/wiki/QUIC) trait for [`CoreSender`]
+#[async_trait(?Send)]
+pub trait QuicSender<'a>: CoreSender<'a> {
+    /// Trust the certificate of [`Recipient`](crate::recipient::Recipient).
+    ///
+    /// For example, self-signed certificate
+    fn add_quic_trusted_certificate(&mut self, certificate: Certificate);
+
+    /// Set name for checking the certificate of [`Recipient`](crate::recipient::Recipient).
+    ///
+    /// Default: `localhost`
+    fn set_quic_server_name(&mut self, server_name: &str);
+
+    /// Send file via [quic](https://en.wikipedia.org/wiki/QUIC) protocol
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use snwf::prelude::*;
+    /// # use snwf::protocol::quic::Certificate;
+    /// # use std::path::Path;
+    /// #
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let certificate = std::fs::read("cert.der").unwrap();
+    ///     let mut sender = Sender::new("127.0.0.1".parse().unwrap(), 4324, 6343);
+    ///     sender.add_quic_trusted_certificate(Certificate(certificate));
+    ///
+    ///     sender.quic_send_file(Path::new("file.txt"));
+    /// }
+    async fn quic_send_file<P>(&mut self, path: P) -> Result<(), QuicError>
+    where
+        P: AsRef<Path> + Send + Copy + Sync + Debug;
+}
+
+#[async_trait(?Send)]
+impl<'a> QuicSender<'a> for Sender<'a> {
+    fn add_quic_trusted_certificate(&mut self, certificate: Certificate) {
+        self.config.quic_tls.trusted_certificates.push(certificate);
+    }
+
+    fn set_quic_server_name(&mut self, server_name: &str) {
+        self.config.quic_tls.server_name = server_name.to_string();
+    }
+
+    async fn quic_send_file<P>(&mut self, path: P) -> Result<(), QuicError>
+    where
+        P: AsRef<Path> + Send + Copy + Sync + Debug,
+    {
+        assert_quic!(path.as_ref().is_file(), "path isn't file or not exists");
+        let mut config = self.get_config();
+
+        debug!(
+            "running quic_send_file; config: {:?}; path: {:?}",
+            config, path
+        );
+
+        let (endpoint, connection) = detail::connect_for_sender(&config).await?;
+
+        let (send, recv) = timeout!(
+            connection.open_bi(),
+            |_| QuicError::Protocol(ProtocolError::TimeoutExpired),
+            config.timeout
+        )?
+        .map_err(QuicError::Connection)?;
+        let mut stream = QuicStream { send, recv };
+
+        let (negotiated, mut secure_stream) = raw::negotiate_for_sender(&mut stream, &mut config)
+            .await
+            .map_err(QuicError::Protocol)?;
+        self.negotiated_capabilities = Some(negotiated);
+
+        raw::send_file(
+            &mut secure_stream,
+            path,
+            None::<&mut quinn::SendStream>,
+            &Some(config),
+            0,
+        )
+        .await
+        .map_err(QuicError::Protocol)?;
+
+        // Wait until recipient gets all data
+        stream
+            .send
+            .finish()
+            .await
+            .map_err(|e| QuicError::Protocol(ProtocolError::FileIO(e.into())))?;
+        connection.close(0u32.into(), b"done");
+        endpoint.wait_idle().await;
+
+        Ok(())
+    }
+}
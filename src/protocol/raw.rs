@@ -0,0 +1,1493 @@
+//! Raw implementation of sending files
+//!
+//! It does not depend on the transport. See [`DataConnection`]
+
+#[cfg(feature = "encryption")]
+use crate::protocol::encryption::EncryptedConnection;
+#[cfg(feature = "udt")]
+use crate::protocol::{
+    error::FilesResult,
+    handshake::{
+        get_safe_path, recv_message_from, send_message_to, BatchMessage, FileStatus,
+        StreamHandshake,
+    },
+    manifest::{get_path_in_root, recv_manifest_from, send_manifest_to, Manifest},
+    signing::check_unsigned_allowed,
+};
+use crate::{
+    common::{
+        compress_block, decompress_block, get_compression_for_file, timeout, Hasher,
+        DEFAULT_BUFFER_SIZE_FOR_NETWORK as NBUFFER_SIZE,
+    },
+    core::*,
+    prelude::{ConfigRecipient, ConfigSender},
+    protocol::{
+        auth::{authenticate_for_recipient, authenticate_for_sender},
+        connection::{DataConnection, SecureConnection},
+        error::ProtocolError,
+        handshake::{
+            assert_handshake, get_handshake_from_file, get_safe_file_name_of_handshake,
+            recv_message, recv_resume, send_handshake_to, send_message, send_resume, Capabilities,
+            FileNameEncoding, FileNamePolicy, Handshake, HandshakeAnswer, HandshakeError,
+            Negotiation, Trailer,
+        },
+        metadata::{apply_metadata_of_handshake, get_preserve_metadata},
+        signing::{
+            check_signature, check_signed_hash, get_session_nonce, get_signing_key,
+            get_trusted_signers, sign_handshake, VerifiedSignature,
+        },
+    },
+};
+use log::debug;
+use std::{
+    io::SeekFrom,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+};
+#[cfg(feature = "udt")]
+use tokio::{fs::create_dir_all, io::AsyncRead};
+use tokio::{
+    fs::{File, OpenOptions},
+    io::{AsyncReadExt, AsyncSeekExt, AsyncWrite, AsyncWriteExt, BufReader, BufWriter},
+};
+
+/// Size of the header of the chunk in [`send_stream`] and of the compressed block
+const CHUNK_HEADER_SIZE: usize = 4;
+
+/// Flag in the header of the compressed block: the block is sent as is
+const RAW_BLOCK_FLAG: u32 = 1 << 31;
+
+/// Size of the random nonce of [`NegotiatedCapabilities`]
+const SESSION_NONCE_SIZE: usize = 32;
+
+pub(crate) fn run_progress_fn(config: &Option<impl CoreConfig>, progressing: Progressing) {
+    if let Some(config) = config {
+        config.run_progress_fn(progressing);
+    }
+}
+
+/// [`HashAlgorithm`] of `config` or default
+pub(crate) fn get_hash_algorithm(config: &Option<impl CoreConfig>) -> HashAlgorithm {
+    config
+        .as_ref()
+        .map(|config| config.get_hash_algorithm())
+        .unwrap_or_default()
+}
+
+/// Max size of one chunk of data of `config` or default
+pub(crate) fn get_max_chunk_size(config: &Option<impl CoreConfig>) -> usize {
+    config
+        .as_ref()
+        .map(|config| config.get_max_chunk_size())
+        .unwrap_or(NBUFFER_SIZE)
+}
+
+/// [`Compression`] of `config` or default
+pub(crate) fn get_compression(config: &Option<impl CoreConfig>) -> Compression {
+    config
+        .as_ref()
+        .map(|config| config.get_compression())
+        .unwrap_or_default()
+}
+
+/// Agree on [`NegotiatedCapabilities`] with [`Recipient`](crate::recipient::Recipient)
+/// and apply them to `config`. Must be called first after connecting
+///
+/// Returns the connection for all next messages: encrypted and authenticated, if it is agreed
+pub(crate) async fn negotiate_for_sender<C: DataConnection>(
+    mut connection: C,
+    config: &mut ConfigSender<'_>,
+) -> Result<(NegotiatedCapabilities, SecureConnection<C>), ProtocolError> {
+    let mut compression = vec![Compression::None];
+    if config.get_compression() != Compression::None && config.get_compression().is_supported() {
+        compression.insert(0, config.get_compression());
+    }
+
+    let capabilities = Capabilities {
+        compression,
+        hash_algorithms: vec![config.get_hash_algorithm()],
+        resume: true,
+        encryption: is_encryption_enabled(config),
+        authentication: config.get_auth_key().is_some(),
+        max_chunk_size: config.get_max_chunk_size(),
+        // Sent only if the recipient asks for it
+        preserve_metadata: PreserveMetadata::all(),
+    };
+    send_message(&capabilities, &mut connection).await?;
+
+    let negotiation = timeout!(recv_message(&mut connection), |_| {
+        ProtocolError::TimeoutExpired
+    })??;
+    debug!(
+        "negotiation. Have: {:?}; answer: {:?}",
+        capabilities, negotiation
+    );
+
+    let negotiated = match negotiation {
+        Negotiation::Agreed(negotiated) => negotiated,
+        Negotiation::Refused(reason) => {
+            return Err(HandshakeError::NegotiationFailed(reason).into())
+        }
+    };
+
+    if capabilities.encryption && !negotiated.encryption {
+        return Err(HandshakeError::NegotiationFailed(
+            "recipient doesn't support encryption".to_string(),
+        )
+        .into());
+    }
+    if capabilities.authentication && !negotiated.authentication {
+        return Err(ProtocolError::Unauthorized);
+    }
+
+    config.apply_capabilities(&negotiated);
+    let mut connection = secure_connection(connection, true, config, &negotiated).await?;
+    if negotiated.authentication {
+        authenticate_for_sender(
+            &mut connection,
+            config.get_auth_key(),
+            &negotiated,
+            config.get_timeout(),
+        )
+        .await?;
+        connection = connection.into_authenticated();
+    }
+
+    Ok((negotiated, connection))
+}
+
+/// Answer [`negotiate_for_sender`] and apply [`NegotiatedCapabilities`] to `config`
+///
+/// [`HashAlgorithm::None`] is accepted only if `config` also uses it.
+/// If `config` uses [`Encryption`], the sender must use it too.
+/// If `config` has the key of authentication, the sender must have the same
+pub(crate) async fn negotiate_for_recipient<C: DataConnection>(
+    mut connection: C,
+    config: &mut ConfigRecipient<'_>,
+) -> Result<(NegotiatedCapabilities, SecureConnection<C>), ProtocolError> {
+    let mut hash_algorithms = vec![
+        HashAlgorithm::Blake2b,
+        HashAlgorithm::Blake3,
+        HashAlgorithm::Sha256,
+        HashAlgorithm::Xxh3,
+    ];
+    if config.get_hash_algorithm() == HashAlgorithm::None {
+        hash_algorithms.push(HashAlgorithm::None);
+    }
+
+    let capabilities = Capabilities {
+        compression: Compression::supported(),
+        hash_algorithms,
+        resume: true,
+        encryption: is_encryption_enabled(config),
+        authentication: config.get_auth_key().is_some(),
+        max_chunk_size: config.get_max_chunk_size(),
+        preserve_metadata: config.preserve_metadata,
+    };
+
+    let sender: Capabilities = timeout!(recv_message(&mut connection), |_| {
+        ProtocolError::TimeoutExpired
+    })??;
+    debug!(
+        "negotiation. Have: {:?}; sender: {:?}",
+        capabilities, sender
+    );
+
+    let negotiated = sender.negotiate(&capabilities).and_then(|mut negotiated| {
+        if capabilities.encryption && !negotiated.encryption {
+            return Err("encryption is required".to_string());
+        }
+
+        negotiated.nonce = vec![0; SESSION_NONCE_SIZE];
+        getrandom::getrandom(&mut negotiated.nonce)
+            .map_err(|e| format!("no random nonce: {}", e))?;
+        Ok(negotiated)
+    });
+
+    match negotiated {
+        Ok(negotiated) => {
+            send_message(&Negotiation::Agreed(negotiated.clone()), &mut connection).await?;
+            config.apply_capabilities(&negotiated);
+            let mut connection = secure_connection(connection, false, config, &negotiated).await?;
+            if negotiated.authentication {
+                authenticate_for_recipient(
+                    &mut connection,
+                    config.get_auth_key(),
+                    &negotiated,
+                    config.get_timeout(),
+                )
+                .await?;
+                connection = connection.into_authenticated();
+            }
+
+            Ok((negotiated, connection))
+        }
+        Err(reason) => {
+            send_message(&Negotiation::Refused(reason.clone()), &mut connection).await?;
+            Err(HandshakeError::NegotiationFailed(reason).into())
+        }
+    }
+}
+
+/// Does `config` use [`Encryption`]? Always `false` without the feature `encryption`
+#[cfg(feature = "encryption")]
+fn is_encryption_enabled(config: &impl CoreConfig) -> bool {
+    config.get_encryption() != &Encryption::None
+}
+
+#[cfg(not(feature = "encryption"))]
+fn is_encryption_enabled(_config: &impl CoreConfig) -> bool {
+    false
+}
+
+/// Wrap `connection` in [`SecureConnection`]. Keys are exchanged, if encryption is agreed
+#[cfg_attr(not(feature = "encryption"), allow(unused_variables))]
+async fn secure_connection<C: DataConnection>(
+    connection: C,
+    is_sender: bool,
+    config: &impl CoreConfig,
+    negotiated: &NegotiatedCapabilities,
+) -> Result<SecureConnection<C>, ProtocolError> {
+    #[cfg(feature = "encryption")]
+    if negotiated.encryption {
+        let connection = EncryptedConnection::new(
+            connection,
+            is_sender,
+            config.get_encryption(),
+            negotiated,
+            config.get_timeout(),
+        )
+        .await?;
+
+        return Ok(SecureConnection::Encrypted(Box::new(connection)));
+    }
+
+    Ok(SecureConnection::Plain(connection))
+}
+
+/// Refuse the negotiation of [`Sender`](crate::sender::Sender) with `reason`,
+/// if the connection can't be used. For example, TLS of the connection for handshakes failed
+#[cfg(feature = "udt")]
+pub(crate) async fn refuse_negotiation<C: DataConnection>(
+    mut connection: C,
+    reason: String,
+) -> Result<(), ProtocolError> {
+    let sender: Capabilities = timeout!(recv_message(&mut connection), |_| {
+        ProtocolError::TimeoutExpired
+    })??;
+    debug!("negotiation refused: {}; sender: {:?}", reason, sender);
+
+    send_message(&Negotiation::Refused(reason), &mut connection).await?;
+    Ok(())
+}
+
+/// [`FileNamePolicy`] of `config` or default
+#[cfg(feature = "udt")]
+pub(crate) fn get_file_name_policy(config: &Option<ConfigRecipient<'_>>) -> FileNamePolicy {
+    config
+        .as_ref()
+        .map(|config| config.file_name_policy)
+        .unwrap_or_default()
+}
+
+/// [`FileNameEncoding`] of `config` or default
+#[cfg(feature = "udt")]
+pub(crate) fn get_file_name_encoding(config: &Option<ConfigRecipient<'_>>) -> FileNameEncoding {
+    config
+        .as_ref()
+        .map(|config| config.file_name_encoding)
+        .unwrap_or_default()
+}
+
+/// Path for the file with the original name from `handshake` in `output`
+///
+/// If the name is unsafe and `policy` rejects it, [`Sender`](crate::sender::Sender)
+/// gets [`HandshakeAnswer::Rejected`]
+pub(crate) async fn get_path_for_file(
+    connection: &mut impl DataConnection,
+    output: &Path,
+    handshake: &Handshake,
+    policy: FileNamePolicy,
+    encoding: FileNameEncoding,
+) -> Result<PathBuf, ProtocolError> {
+    match get_safe_file_name_of_handshake(handshake, policy, encoding) {
+        Ok(file_name) => Ok(output.join(file_name)),
+        Err(e) => {
+            send_message(&HandshakeAnswer::Rejected(e.to_string()), connection).await?;
+            Err(e.into())
+        }
+    }
+}
+
+/// [`Recipient`](crate::recipient::Recipient) accepts [`HashAlgorithm::None`] only if it also uses it
+pub(crate) fn check_hash_algorithm(
+    config: &Option<ConfigRecipient<'_>>,
+    hash_algorithm: HashAlgorithm,
+) -> Result<(), HandshakeError> {
+    assert_handshake!(
+        hash_algorithm != HashAlgorithm::None || get_hash_algorithm(config) == HashAlgorithm::None,
+        "sender doesn't hash files, but recipient uses {:?}",
+        get_hash_algorithm(config)
+    );
+
+    Ok(())
+}
+
+/// Send [`Trailer`] after the data
+pub(crate) async fn send_trailer(
+    connection: &mut impl DataConnection,
+    hasher: Hasher,
+    size: u64,
+) -> Result<(), ProtocolError> {
+    let trailer = Trailer {
+        hash: hasher.finalize(),
+        size,
+    };
+    send_message(&trailer, connection).await?;
+    debug!("Done trailer send. Trailer: {:?}", trailer);
+
+    Ok(())
+}
+
+/// Receive [`Trailer`] and compare it with the received data
+///
+/// Returns the checked hash
+pub(crate) async fn recv_and_check_trailer(
+    connection: &mut impl DataConnection,
+    hasher: Hasher,
+    size: u64,
+) -> Result<String, ProtocolError> {
+    let trailer: Trailer = timeout!(recv_message(connection), |_| {
+        ProtocolError::TimeoutExpired
+    })??;
+    let hash = hasher.finalize();
+
+    if hash != trailer.hash || size != trailer.size {
+        debug!(
+            "hash not valid! hash: {}; size: {}; trailer: {:?}",
+            hash, size, trailer
+        );
+        return Err(ProtocolError::FileInvalid);
+    }
+
+    Ok(hash)
+}
+
+/// Wait for [`HandshakeAnswer`] of [`Recipient`](crate::recipient::Recipient)
+async fn recv_handshake_answer(connection: &mut impl DataConnection) -> Result<(), ProtocolError> {
+    // Recipient may ask the user. Can't use timeout
+    match recv_message(connection).await? {
+        HandshakeAnswer::Accepted => Ok(()),
+        HandshakeAnswer::Rejected(reason) => {
+            debug!("file rejected by recipient: {}", reason);
+            Err(ProtocolError::Rejected(reason))
+        }
+    }
+}
+
+/// Ask [`Decision`] about `incoming`. Without the callback, the file is accepted
+fn get_decision(config: &Option<ConfigRecipient<'_>>, incoming: &IncomingFile) -> Decision {
+    let decision = match config
+        .as_ref()
+        .and_then(|config| config.decision_fn.clone())
+    {
+        Some(decision_fn) => decision_fn.borrow_mut()(incoming),
+        None => Decision::Accept,
+    };
+    debug!("decision: {:?}; file: {:?}", decision, incoming);
+
+    decision
+}
+
+/// Reason for [`HandshakeAnswer::Rejected`]
+#[cfg(feature = "udt")]
+fn get_reason(error: &ProtocolError) -> String {
+    match error {
+        ProtocolError::Rejected(reason) | ProtocolError::Untrusted(reason) => reason.clone(),
+        e => e.to_string(),
+    }
+}
+
+/// Answer to the manifest or [`StreamHandshake`]: accepted, if `result` is ok
+#[cfg(feature = "udt")]
+async fn send_answer<T>(
+    connection: &mut impl DataConnection,
+    result: Result<T, ProtocolError>,
+) -> Result<T, ProtocolError> {
+    let answer = match &result {
+        Ok(_) => HandshakeAnswer::Accepted,
+        Err(e) => HandshakeAnswer::Rejected(get_reason(e)),
+    };
+    send_message(&answer, connection).await?;
+
+    result
+}
+
+/// Check the signature, ask [`Decision`] about the file and send [`HandshakeAnswer`]
+/// to [`Sender`](crate::sender::Sender)
+///
+/// Returns path for the file and its checked signature
+async fn answer_handshake(
+    connection: &mut impl DataConnection,
+    path: &Path,
+    handshake: &Handshake,
+    config: &Option<ConfigRecipient<'_>>,
+    peer_addr: SocketAddr,
+) -> Result<(PathBuf, Option<VerifiedSignature>), ProtocolError> {
+    let signature = match check_signature(
+        handshake,
+        get_trusted_signers(config),
+        get_session_nonce(config),
+    ) {
+        Ok(signature) => signature,
+        Err(ProtocolError::Untrusted(reason)) => {
+            send_message(&HandshakeAnswer::Rejected(reason.clone()), connection).await?;
+            return Err(ProtocolError::Untrusted(reason));
+        }
+        Err(e) => return Err(e),
+    };
+
+    let incoming = IncomingFile {
+        file_name: handshake.file_name.clone(),
+        size: handshake.size,
+        hash_algorithm: handshake.hash_algorithm,
+        peer_addr,
+        signer: signature.as_ref().map(|signature| signature.signer.clone()),
+    };
+
+    match get_decision(config, &incoming) {
+        Decision::Accept => {
+            send_message(&HandshakeAnswer::Accepted, connection).await?;
+            Ok((path.to_path_buf(), signature))
+        }
+        Decision::Redirect(path) => {
+            send_message(&HandshakeAnswer::Accepted, connection).await?;
+            Ok((path, signature))
+        }
+        Decision::Reject(reason) => {
+            send_message(&HandshakeAnswer::Rejected(reason.clone()), connection).await?;
+            Err(ProtocolError::Rejected(reason))
+        }
+    }
+}
+
+pub(crate) async fn send_file<P, S>(
+    connection: &mut impl DataConnection,
+    path: P,
+    handshake_socket: Option<&mut S>,
+    config: &Option<ConfigSender<'_>>,
+    number_file: u64,
+) -> Result<(), ProtocolError>
+where
+    P: AsRef<Path> + Sync + Copy,
+    S: AsyncWrite + Unpin,
+{
+    let mut handshake = get_handshake_from_file(
+        path,
+        get_hash_algorithm(config),
+        get_preserve_metadata(config),
+    )
+    .await?;
+    handshake.set_compression(get_compression_for_file(
+        get_compression(config),
+        path.as_ref(),
+        handshake.size,
+    ));
+    if let Some(signing_key) = get_signing_key(config) {
+        sign_handshake(
+            &mut handshake,
+            path.as_ref(),
+            signing_key,
+            get_session_nonce(config),
+        )
+        .await?;
+    }
+    send_handshake_to(&handshake, connection, handshake_socket).await?;
+    recv_handshake_answer(connection).await?;
+    send_file_data(connection, path, &handshake, config, number_file).await?;
+
+    run_progress_fn(config, Progressing::Done);
+    Ok(())
+}
+
+/// Send one chunk of the file. If there is [`Compression`], it is sent as a block
+///
+/// See [`handshake`](crate::protocol::handshake)
+async fn send_chunk(
+    connection: &mut impl DataConnection,
+    compression: Compression,
+    chunk: &[u8],
+) -> Result<(), ProtocolError> {
+    if compression == Compression::None {
+        return timeout!(connection.send_data(chunk), |_| {
+            ProtocolError::TimeoutExpired
+        })?
+        .map_err(ProtocolError::FileIO);
+    }
+
+    // Header and block separately: UDT receives only whole messages
+    let compressed = compress_block(compression, chunk).map_err(ProtocolError::FileIO)?;
+    let (header, block) = match compressed.as_deref() {
+        Some(compressed) => (compressed.len() as u32, compressed),
+        None => (chunk.len() as u32 | RAW_BLOCK_FLAG, chunk),
+    };
+
+    timeout!(connection.send_data(&header.to_be_bytes()), |_| {
+        ProtocolError::TimeoutExpired
+    })?
+    .map_err(ProtocolError::FileIO)?;
+    timeout!(connection.send_data(block), |_| {
+        ProtocolError::TimeoutExpired
+    })?
+    .map_err(ProtocolError::FileIO)
+}
+
+/// Receive one block of [`send_chunk`] and decompress it to `output`. Returns size of the data
+///
+/// `block` - buffer for the compressed block
+async fn recv_block(
+    connection: &mut impl DataConnection,
+    compression: Compression,
+    block: &mut [u8],
+    output: &mut [u8],
+) -> Result<usize, ProtocolError> {
+    let mut header = [0u8; CHUNK_HEADER_SIZE];
+    connection
+        .recv_exact(&mut header)
+        .await
+        .map_err(ProtocolError::ReceivingData)?;
+
+    let header = u32::from_be_bytes(header);
+    let size = (header & !RAW_BLOCK_FLAG) as usize;
+
+    // Block is never bigger than its data
+    if size == 0 || size > output.len() || size > block.len() {
+        return Err(ProtocolError::ReceivingData(
+            std::io::ErrorKind::InvalidData.into(),
+        ));
+    }
+
+    if header & RAW_BLOCK_FLAG != 0 {
+        connection
+            .recv_exact(&mut output[..size])
+            .await
+            .map_err(ProtocolError::ReceivingData)?;
+
+        return Ok(size);
+    }
+
+    connection
+        .recv_exact(&mut block[..size])
+        .await
+        .map_err(ProtocolError::ReceivingData)?;
+
+    match decompress_block(compression, &block[..size], output) {
+        Ok(len) if len > 0 => Ok(len),
+        _ => Err(ProtocolError::ReceivingData(
+            std::io::ErrorKind::InvalidData.into(),
+        )),
+    }
+}
+
+/// Send data of the file. [`Handshake`] must be already sent
+pub(crate) async fn send_file_data<P>(
+    connection: &mut impl DataConnection,
+    path: P,
+    handshake: &Handshake,
+    config: &Option<ConfigSender<'_>>,
+    number_file: u64,
+) -> Result<(), ProtocolError>
+where
+    P: AsRef<Path> + Sync + Copy,
+{
+    let compression = handshake.get_compression()?;
+    let (offset, mut hasher) = recv_resume(path.as_ref(), handshake, connection).await?;
+
+    let mut file = File::open(path).await.map_err(ProtocolError::FileIO)?;
+    file.seek(SeekFrom::Start(offset))
+        .await
+        .map_err(ProtocolError::FileIO)?;
+    // Don't send more than in the handshake, if the file grows
+    let mut reader = BufReader::new(file).take(handshake.size - offset);
+    let mut done_bytes = offset as usize;
+
+    let mut buf = vec![0u8; get_max_chunk_size(config)];
+    loop {
+        let len = reader.read(&mut buf).await.map_err(ProtocolError::FileIO)?;
+
+        if len == 0 {
+            break;
+        }
+
+        send_chunk(connection, compression, &buf[0..len]).await?;
+
+        hasher.update(&buf[0..len]);
+        done_bytes += len;
+        run_progress_fn(
+            config,
+            Progressing::Yield {
+                done_files: number_file,
+                total_bytes: handshake.size,
+                done_bytes: done_bytes as u64,
+                path_to_file: path.as_ref().to_path_buf(),
+            },
+        );
+    }
+
+    // The file was truncated while sending
+    if done_bytes as u64 != handshake.size {
+        return Err(ProtocolError::FileIO(
+            std::io::ErrorKind::UnexpectedEof.into(),
+        ));
+    }
+
+    send_trailer(connection, hasher, done_bytes as u64).await
+}
+
+/// Receive file
+///
+/// * `resume` - if `path` already has the beginning of the file, receive only the rest of it
+/// * `peer_addr` - address of [`Sender`](crate::sender::Sender) for [`IncomingFile`]
+///
+/// Returns path of the received file and its verified [`Signer`].
+/// Path differs from `path` after [`Decision::Redirect`]
+pub(crate) async fn recv_file<P>(
+    connection: &mut impl DataConnection,
+    path: P,
+    config: &Option<ConfigRecipient<'_>>,
+    number_file: u64,
+    handshake: Handshake,
+    resume: bool,
+    peer_addr: SocketAddr,
+) -> Result<(PathBuf, Option<Signer>), ProtocolError>
+where
+    P: AsRef<Path> + Sync + Copy,
+{
+    let (path, signature) =
+        answer_handshake(connection, path.as_ref(), &handshake, config, peer_addr).await?;
+    let hash = recv_file_data(
+        connection,
+        path.as_path(),
+        config,
+        number_file,
+        &handshake,
+        resume,
+    )
+    .await?;
+    check_signed_hash(&signature, &hash)?;
+    apply_metadata_of_handshake(path.as_path(), &handshake, config)?;
+
+    run_progress_fn(config, Progressing::Done);
+    Ok((path, signature.map(|signature| signature.signer)))
+}
+
+/// Receive data of the file and check it. [`Handshake`] must be already received
+///
+/// Returns the checked hash of the file
+pub(crate) async fn recv_file_data<P>(
+    connection: &mut impl DataConnection,
+    path: P,
+    config: &Option<ConfigRecipient<'_>>,
+    number_file: u64,
+    handshake: &Handshake,
+    resume: bool,
+) -> Result<String, ProtocolError>
+where
+    P: AsRef<Path> + Sync + Copy,
+{
+    debug!("raw_recv_file. Getting file");
+    check_hash_algorithm(config, handshake.hash_algorithm)?;
+    let compression = handshake.get_compression()?;
+
+    let (offset, mut hasher) =
+        send_resume(resume.then_some(path.as_ref()), handshake, connection).await?;
+
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(offset == 0)
+        .open(path)
+        .await
+        .map_err(ProtocolError::FileIO)?;
+
+    if offset > 0 {
+        debug!("raw_recv_file. Resume from {} bytes", offset);
+        file.set_len(offset).await.map_err(ProtocolError::FileIO)?;
+        file.seek(SeekFrom::Start(offset))
+            .await
+            .map_err(ProtocolError::FileIO)?;
+    }
+
+    let mut file = BufWriter::new(file);
+    let mut buf = vec![0u8; get_max_chunk_size(config)];
+    let mut block = match compression {
+        Compression::None => Vec::new(),
+        _ => vec![0u8; buf.len()],
+    };
+    let mut total_bytes_for_send = handshake.size - offset;
+    let mut done_bytes = offset as usize;
+
+    while total_bytes_for_send > 0 {
+        // Don't read the data after the file
+        let need = buf.len().min(total_bytes_for_send as usize);
+        let len = match compression {
+            Compression::None => connection
+                .recv_data(&mut buf[..need])
+                .await
+                .map_err(ProtocolError::ReceivingData)?,
+            _ => recv_block(connection, compression, &mut block, &mut buf[..need]).await?,
+        };
+
+        if len == 0 {
+            return Err(ProtocolError::ReceivingData(
+                std::io::ErrorKind::UnexpectedEof.into(),
+            ));
+        }
+
+        file.write_all(&buf[0..len])
+            .await
+            .map_err(ProtocolError::FileIO)?;
+
+        hasher.update(&buf[0..len]);
+        total_bytes_for_send -= len as u64;
+        done_bytes += len;
+        run_progress_fn(
+            config,
+            Progressing::Yield {
+                done_files: number_file,
+                total_bytes: handshake.size,
+                done_bytes: done_bytes as u64,
+                path_to_file: path.as_ref().to_path_buf(),
+            },
+        );
+    }
+    file.flush().await.map_err(ProtocolError::FileIO)?;
+
+    // Check file
+    debug!("raw_recv_file. Checking file");
+    recv_and_check_trailer(connection, hasher, done_bytes as u64).await
+}
+
+/// [`Progressing`] after one of many files is done
+#[cfg(feature = "udt")]
+fn file_done(number_file: usize, size: u64, path_to_file: PathBuf) -> Progressing {
+    Progressing::Yield {
+        done_files: number_file as u64 + 1,
+        total_bytes: size,
+        done_bytes: size,
+        path_to_file,
+    }
+}
+
+/// Send directory recursively: `manifest` of `path`, then all files, if
+/// [`Recipient`](crate::recipient::Recipient) accepts them
+///
+/// The manifest is made before connecting by [`get_manifest_from_dir`]: hashing all files
+/// takes time, and [`Recipient`](crate::recipient::Recipient) waits for it with timeout
+#[cfg(feature = "udt")]
+pub(crate) async fn send_dir<P>(
+    connection: &mut impl DataConnection,
+    path: P,
+    manifest: &Manifest,
+    handshake_socket: Option<&mut impl DataConnection>,
+    config: &Option<ConfigSender<'_>>,
+) -> Result<(), ProtocolError>
+where
+    P: AsRef<Path> + Sync + Copy,
+{
+    send_manifest_to(manifest, connection, handshake_socket).await?;
+    recv_handshake_answer(connection).await?;
+
+    for (number_file, entry) in manifest.files.iter().enumerate() {
+        let path_to_file = get_path_in_root(path.as_ref(), &entry.path)?;
+
+        send_file_data(
+            connection,
+            path_to_file.as_path(),
+            &entry.to_handshake(manifest.hash_algorithm),
+            config,
+            number_file as u64,
+        )
+        .await?;
+
+        run_progress_fn(config, file_done(number_file, entry.size, path_to_file));
+    }
+
+    run_progress_fn(config, Progressing::Done);
+    Ok(())
+}
+
+/// Paths for files of `manifest` in `output`. [`Decision`] is asked about every file
+#[cfg(feature = "udt")]
+fn get_paths_for_dir(
+    output: &Path,
+    manifest: &Manifest,
+    config: &Option<ConfigRecipient<'_>>,
+    peer_addr: SocketAddr,
+) -> Result<Vec<PathBuf>, ProtocolError> {
+    check_unsigned_allowed(config, "directory")?;
+    check_hash_algorithm(config, manifest.hash_algorithm)?;
+
+    let policy = get_file_name_policy(config);
+    let mut paths = Vec::with_capacity(manifest.files.len());
+    for entry in manifest.files.iter() {
+        let incoming = IncomingFile {
+            file_name: entry.path.clone(),
+            size: entry.size,
+            hash_algorithm: manifest.hash_algorithm,
+            peer_addr,
+            signer: None,
+        };
+
+        match get_decision(config, &incoming) {
+            Decision::Accept => paths.push(get_safe_path(output, &entry.path, policy)?),
+            Decision::Redirect(path) => paths.push(path),
+            Decision::Reject(reason) => return Err(ProtocolError::Rejected(reason)),
+        }
+    }
+
+    Ok(paths)
+}
+
+/// Receive directory sent by [`send_dir`] and recreate the tree in `output`
+///
+/// [`Decision`] is asked about every file before the data. If one of them is rejected,
+/// the whole directory is rejected
+///
+/// * `peer_addr` - address of [`Sender`](crate::sender::Sender) for [`IncomingFile`]
+#[cfg(feature = "udt")]
+pub(crate) async fn recv_dir<P>(
+    connection: &mut impl DataConnection,
+    output: P,
+    handshake_socket: Option<&mut impl DataConnection>,
+    config: &Option<ConfigRecipient<'_>>,
+    peer_addr: SocketAddr,
+) -> Result<(), ProtocolError>
+where
+    P: AsRef<Path> + Sync + Copy,
+{
+    let manifest = recv_manifest_from(connection, handshake_socket).await?;
+    let paths = get_paths_for_dir(output.as_ref(), &manifest, config, peer_addr);
+    let paths = send_answer(connection, paths).await?;
+
+    create_dir_all(output)
+        .await
+        .map_err(ProtocolError::FileIO)?;
+
+    let policy = get_file_name_policy(config);
+    for directory in manifest.directories.iter() {
+        let path = get_safe_path(output.as_ref(), directory, policy)?;
+        create_dir_all(path).await.map_err(ProtocolError::FileIO)?;
+    }
+
+    for (number_file, (entry, path_to_file)) in manifest.files.iter().zip(paths).enumerate() {
+        let hash = recv_file_data(
+            connection,
+            path_to_file.as_path(),
+            config,
+            number_file as u64,
+            &entry.to_handshake(manifest.hash_algorithm),
+            false,
+        )
+        .await?;
+
+        if hash != entry.hash {
+            debug!("hash not valid! hash: {}; entry: {:?}", hash, entry);
+            return Err(ProtocolError::FileInvalid);
+        }
+
+        run_progress_fn(config, file_done(number_file, entry.size, path_to_file));
+    }
+
+    run_progress_fn(config, Progressing::Done);
+    Ok(())
+}
+
+/// Send many files over one connection
+///
+/// Each file: [`BatchMessage::File`], data of the file, then [`FileStatus`] from
+/// [`Recipient`](crate::recipient::Recipient). At the end: [`BatchMessage::End`]
+///
+/// A file that can't be read or is rejected by [`Recipient`](crate::recipient::Recipient) is skipped
+#[cfg(feature = "udt")]
+pub(crate) async fn send_files<I, P, S>(
+    connection: &mut impl DataConnection,
+    paths: I,
+    mut handshake_socket: Option<&mut S>,
+    config: &Option<ConfigSender<'_>>,
+) -> Result<FilesResult, ProtocolError>
+where
+    I: IntoIterator<Item = P>,
+    P: AsRef<Path>,
+    S: DataConnection,
+{
+    let mut results = FilesResult::new();
+
+    for path in paths {
+        let path = path.as_ref();
+        let mut handshake = match get_handshake_from_file(
+            path,
+            get_hash_algorithm(config),
+            get_preserve_metadata(config),
+        )
+        .await
+        {
+            Ok(handshake) => handshake,
+            Err(e) => {
+                debug!("skip file {}: {:?}", path.display(), e);
+                results.push((path.to_path_buf(), Err(ProtocolError::Handshake(e))));
+                continue;
+            }
+        };
+
+        handshake.set_compression(get_compression_for_file(
+            get_compression(config),
+            path,
+            handshake.size,
+        ));
+        if let Some(signing_key) = get_signing_key(config) {
+            if let Err(e) =
+                sign_handshake(&mut handshake, path, signing_key, get_session_nonce(config)).await
+            {
+                debug!("skip file {}: {:?}", path.display(), e);
+                results.push((path.to_path_buf(), Err(ProtocolError::Handshake(e))));
+                continue;
+            }
+        }
+
+        let message = BatchMessage::File(handshake.clone());
+        send_message_to(&message, connection, handshake_socket.as_deref_mut()).await?;
+
+        match recv_handshake_answer(connection).await {
+            Ok(()) => {}
+            Err(ProtocolError::Rejected(reason)) => {
+                results.push((path.to_path_buf(), Err(ProtocolError::Rejected(reason))));
+                continue;
+            }
+            Err(e) => return Err(e),
+        }
+
+        send_file_data(connection, path, &handshake, config, results.len() as u64).await?;
+
+        // Recipient is checking the file. Can't use timeout
+        let result = match recv_message(connection).await? {
+            FileStatus::Valid => Ok(()),
+            FileStatus::Invalid => Err(ProtocolError::FileInvalid),
+        };
+
+        results.push((path.to_path_buf(), result));
+        run_progress_fn(
+            config,
+            file_done(results.len() - 1, handshake.size, path.to_path_buf()),
+        );
+    }
+
+    send_message_to(&BatchMessage::End, connection, handshake_socket).await?;
+
+    run_progress_fn(config, Progressing::Done);
+    Ok(results)
+}
+
+/// Receive files sent by [`send_files`] to `output` with original names
+///
+/// * `peer_addr` - address of [`Sender`](crate::sender::Sender) for [`IncomingFile`]
+#[cfg(feature = "udt")]
+pub(crate) async fn recv_files<P, S>(
+    connection: &mut impl DataConnection,
+    output: P,
+    mut handshake_socket: Option<&mut S>,
+    config: &Option<ConfigRecipient<'_>>,
+    peer_addr: SocketAddr,
+) -> Result<FilesResult, ProtocolError>
+where
+    P: AsRef<Path> + Sync + Copy,
+    S: DataConnection,
+{
+    let mut results = FilesResult::new();
+
+    // Sender is hashing the next file. Can't use timeout
+    while let BatchMessage::File(handshake) =
+        recv_message_from(connection, handshake_socket.as_deref_mut()).await?
+    {
+        let path_to_file = match get_path_for_file(
+            connection,
+            output.as_ref(),
+            &handshake,
+            get_file_name_policy(config),
+            get_file_name_encoding(config),
+        )
+        .await
+        {
+            Ok(path_to_file) => path_to_file,
+            Err(e @ ProtocolError::Handshake(HandshakeError::UnsafeFileName(_))) => {
+                results.push((PathBuf::from(&handshake.file_name), Err(e)));
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+        let (path_to_file, signature) = match answer_handshake(
+            connection,
+            path_to_file.as_path(),
+            &handshake,
+            config,
+            peer_addr,
+        )
+        .await
+        {
+            Ok(answer) => answer,
+            Err(e @ (ProtocolError::Rejected(_) | ProtocolError::Untrusted(_))) => {
+                results.push((path_to_file, Err(e)));
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+
+        let result = match recv_file_data(
+            connection,
+            path_to_file.as_path(),
+            config,
+            results.len() as u64,
+            &handshake,
+            false,
+        )
+        .await
+        {
+            Ok(hash) => check_signed_hash(&signature, &hash),
+            Err(ProtocolError::FileInvalid) => Err(ProtocolError::FileInvalid),
+            Err(e) => return Err(e),
+        };
+
+        let status = match result {
+            Ok(()) => FileStatus::Valid,
+            Err(_) => FileStatus::Invalid,
+        };
+        send_message(&status, connection).await?;
+
+        let result = result
+            .and_then(|()| apply_metadata_of_handshake(path_to_file.as_path(), &handshake, config));
+        results.push((path_to_file.clone(), result));
+        run_progress_fn(
+            config,
+            file_done(results.len() - 1, handshake.size, path_to_file),
+        );
+    }
+
+    run_progress_fn(config, Progressing::Done);
+    Ok(results)
+}
+
+/// Send data from `reader` by chunks and [`Trailer`] with the hash after it
+///
+/// [`StreamHandshake`] must be already sent. The data is sent after the answer
+/// of [`answer_stream_handshake`]
+#[cfg(feature = "udt")]
+pub(crate) async fn send_stream<R>(
+    connection: &mut impl DataConnection,
+    reader: &mut R,
+    handshake: &StreamHandshake,
+    config: &Option<ConfigSender<'_>>,
+) -> Result<(), ProtocolError>
+where
+    R: AsyncRead + Unpin,
+{
+    recv_handshake_answer(connection).await?;
+
+    let path_to_file = PathBuf::from(&handshake.file_name);
+    let mut hasher = Hasher::new(handshake.hash_algorithm);
+    let mut done_bytes = 0;
+
+    // size of chunk (u32 big endian) + chunk. Separately: UDT receives only whole messages
+    let mut buf = vec![0u8; get_max_chunk_size(config)];
+    loop {
+        let len = reader.read(&mut buf).await.map_err(ProtocolError::FileIO)?;
+
+        timeout!(connection.send_data(&(len as u32).to_be_bytes()), |_| {
+            ProtocolError::TimeoutExpired
+        })?
+        .map_err(ProtocolError::FileIO)?;
+
+        if len == 0 {
+            break;
+        }
+
+        timeout!(connection.send_data(&buf[..len]), |_| {
+            ProtocolError::TimeoutExpired
+        })?
+        .map_err(ProtocolError::FileIO)?;
+
+        hasher.update(&buf[..len]);
+        done_bytes += len as u64;
+        run_progress_fn(
+            config,
+            Progressing::Yield {
+                done_files: 0,
+                total_bytes: handshake.size_hint.unwrap_or(0),
+                done_bytes,
+                path_to_file: path_to_file.clone(),
+            },
+        );
+    }
+
+    send_trailer(connection, hasher, done_bytes).await?;
+
+    run_progress_fn(config, Progressing::Done);
+    Ok(())
+}
+
+/// Check [`StreamHandshake`], ask [`Decision`] about it and send [`HandshakeAnswer`]
+///
+/// The data goes to the writer of the caller, so [`Decision::Redirect`] is refused.
+/// Call it before the writer is made
+///
+/// * `peer_addr` - address of [`Sender`](crate::sender::Sender) for [`IncomingFile`]
+#[cfg(feature = "udt")]
+pub(crate) async fn answer_stream_handshake(
+    connection: &mut impl DataConnection,
+    handshake: &StreamHandshake,
+    config: &Option<ConfigRecipient<'_>>,
+    peer_addr: SocketAddr,
+) -> Result<(), ProtocolError> {
+    let result = check_unsigned_allowed(config, "stream").and_then(|()| {
+        check_hash_algorithm(config, handshake.hash_algorithm)?;
+
+        let incoming = IncomingFile {
+            file_name: handshake.file_name.clone(),
+            size: handshake.size_hint.unwrap_or(0),
+            hash_algorithm: handshake.hash_algorithm,
+            peer_addr,
+            signer: None,
+        };
+
+        match get_decision(config, &incoming) {
+            Decision::Accept => Ok(()),
+            Decision::Reject(reason) => Err(ProtocolError::Rejected(reason)),
+            Decision::Redirect(_) => Err(ProtocolError::Rejected(
+                "stream can't be redirected".to_string(),
+            )),
+        }
+    });
+
+    send_answer(connection, result).await
+}
+
+/// Receive data sent by [`send_stream`] to `writer` and check it with [`Trailer`]
+///
+/// [`answer_stream_handshake`] must be already sent
+#[cfg(feature = "udt")]
+pub(crate) async fn recv_stream<W>(
+    connection: &mut impl DataConnection,
+    writer: &mut W,
+    handshake: &StreamHandshake,
+    config: &Option<ConfigRecipient<'_>>,
+) -> Result<(), ProtocolError>
+where
+    W: AsyncWrite + Unpin,
+{
+    let path_to_file = PathBuf::from(&handshake.file_name);
+    let mut hasher = Hasher::new(handshake.hash_algorithm);
+    let mut done_bytes = 0;
+
+    let mut buf = vec![0u8; get_max_chunk_size(config)];
+    loop {
+        let mut size = [0u8; CHUNK_HEADER_SIZE];
+        timeout!(connection.recv_exact(&mut size), |_| {
+            ProtocolError::TimeoutExpired
+        })?
+        .map_err(ProtocolError::ReceivingData)?;
+
+        let len = u32::from_be_bytes(size) as usize;
+        if len == 0 {
+            break;
+        }
+
+        if len > buf.len() {
+            return Err(ProtocolError::ReceivingData(
+                std::io::ErrorKind::InvalidData.into(),
+            ));
+        }
+
+        timeout!(connection.recv_exact(&mut buf[..len]), |_| {
+            ProtocolError::TimeoutExpired
+        })?
+        .map_err(ProtocolError::ReceivingData)?;
+
+        hasher.update(&buf[..len]);
+        writer
+            .write_all(&buf[..len])
+            .await
+            .map_err(ProtocolError::FileIO)?;
+
+        done_bytes += len as u64;
+        run_progress_fn(
+            config,
+            Progressing::Yield {
+                done_files: 0,
+                total_bytes: handshake.size_hint.unwrap_or(0),
+                done_bytes,
+                path_to_file: path_to_file.clone(),
+            },
+        );
+    }
+    writer.flush().await.map_err(ProtocolError::FileIO)?;
+
+    // Check data
+    recv_and_check_trailer(connection, hasher, done_bytes).await?;
+
+    run_progress_fn(config, Progressing::Done);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::get_hasher;
+    use crate::protocol::handshake::{
+        recv_handshake_from_address, recv_handshake_in_band, send_handshake_in_band,
+    };
+    use log::debug;
+    use tokio::net::{TcpListener, TcpStream, ToSocketAddrs};
+    #[cfg(feature = "udt")]
+    use tokio_udt::{UdtConnection, UdtListener};
+
+    pub(crate) mod detail {
+        use super::*;
+
+        pub(crate) async fn async_send(
+            address_for_data: impl ToSocketAddrs,
+            address_for_tcp: impl ToSocketAddrs,
+            path_to_file: &Path,
+        ) -> Result<(), ProtocolError> {
+            let mut connection = TcpStream::connect(address_for_data)
+                .await
+                .map_err(ProtocolError::Connect)?;
+            let mut tcp = TcpStream::connect(address_for_tcp)
+                .await
+                .map_err(ProtocolError::Connect)?;
+            debug!("Done all connect");
+
+            debug!("Running raw_send_file...");
+            send_file(&mut connection, path_to_file, Some(&mut tcp), &None, 0).await?;
+            debug!("Done raw_send_file!");
+
+            Ok(())
+        }
+
+        pub(crate) async fn async_recv(
+            address_for_data: impl ToSocketAddrs,
+            address_for_tcp: impl ToSocketAddrs,
+            output: &Path,
+        ) -> Result<(), ProtocolError> {
+            let data_listener = TcpListener::bind(address_for_data)
+                .await
+                .map_err(ProtocolError::Bind)?;
+            let mut tcp_listener = TcpListener::bind(address_for_tcp)
+                .await
+                .map_err(ProtocolError::Bind)?
+                .into();
+            debug!("Done all bind!");
+
+            let (mut connection, addr) = data_listener
+                .accept()
+                .await
+                .map_err(ProtocolError::Accept)?;
+            debug!("Accept client: {}", addr);
+
+            debug!("Running raw_recv_file...");
+            let handshake = recv_handshake_from_address(&mut tcp_listener).await?;
+            recv_file(&mut connection, output, &None, 0, handshake, false, addr).await?;
+            debug!("Done raw_recv_file!");
+
+            Ok(())
+        }
+
+        #[cfg(feature = "udt")]
+        pub(crate) async fn async_send_udt(
+            address_for_udt: impl ToSocketAddrs,
+            address_for_tcp: impl ToSocketAddrs,
+            path_to_file: &Path,
+        ) -> Result<(), ProtocolError> {
+            let mut udt = UdtConnection::connect(address_for_udt, None)
+                .await
+                .map_err(ProtocolError::Connect)?;
+            let mut tcp = TcpStream::connect(address_for_tcp)
+                .await
+                .map_err(ProtocolError::Connect)?;
+            debug!("Done all connect");
+
+            debug!("Running raw_send_file...");
+            send_file(&mut udt, path_to_file, Some(&mut tcp), &None, 0).await?;
+            debug!("Done raw_send_file!");
+
+            Ok(())
+        }
+
+        #[cfg(feature = "udt")]
+        pub(crate) async fn async_recv_udt(
+            address_for_udt: SocketAddr,
+            address_for_tcp: impl ToSocketAddrs,
+            output: &Path,
+        ) -> Result<(), ProtocolError> {
+            let udt_listener = UdtListener::bind(address_for_udt, None)
+                .await
+                .map_err(ProtocolError::Bind)?;
+            let mut tcp_listener = TcpListener::bind(address_for_tcp)
+                .await
+                .map_err(ProtocolError::Bind)?
+                .into();
+            debug!("Done all bind!");
+
+            let (addr, mut udt_connection) =
+                udt_listener.accept().await.map_err(ProtocolError::Accept)?;
+            debug!("Accept client: {}", addr);
+
+            debug!("Running raw_recv_file...");
+            let handshake = recv_handshake_from_address(&mut tcp_listener).await?;
+            recv_file(
+                &mut udt_connection,
+                output,
+                &None,
+                0,
+                handshake,
+                false,
+                addr,
+            )
+            .await?;
+            debug!("Done raw_recv_file!");
+
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn raw_send_and_recv() {
+        crate::init_logger_for_test();
+
+        const ADDRESS_DATA: &str = "127.0.0.1:6437";
+        const ADDRESS_TCP: &str = "127.0.0.1:6438";
+
+        let (temp_dir, input_path) = file_hashing::fs::extra::generate_random_file(3626);
+        let output_path = temp_dir.join("tess.txt");
+        let hash_input = file_hashing::get_hash_file(input_path.path(), &mut get_hasher()).unwrap();
+
+        let (recv, send) = tokio::join!(
+            detail::async_recv(ADDRESS_DATA, ADDRESS_TCP, output_path.as_path()),
+            detail::async_send(ADDRESS_DATA, ADDRESS_TCP, input_path.path())
+        );
+
+        send.unwrap();
+        recv.unwrap();
+        let hash_output = file_hashing::get_hash_file(output_path, &mut get_hasher()).unwrap();
+
+        assert_eq!(hash_input, hash_output)
+    }
+
+    #[cfg(feature = "udt")]
+    #[tokio::test]
+    async fn udt_raw() {
+        crate::init_logger_for_test();
+
+        const ADDRESS_UDT: &str = "127.0.0.1:6432";
+        const ADDRESS_TCP: &str = "127.0.0.1:6424";
+
+        let (temp_dir, input_path) = file_hashing::fs::extra::generate_random_file(3626);
+        let output_path = temp_dir.join("tess.txt");
+        let hash_input = file_hashing::get_hash_file(input_path.path(), &mut get_hasher()).unwrap();
+
+        let (recv, send) = tokio::join!(
+            detail::async_recv_udt(
+                ADDRESS_UDT.parse().unwrap(),
+                ADDRESS_TCP,
+                output_path.as_path()
+            ),
+            detail::async_send_udt(ADDRESS_UDT, ADDRESS_TCP, input_path.path())
+        );
+
+        send.unwrap();
+        recv.unwrap();
+        let hash_output = file_hashing::get_hash_file(output_path, &mut get_hasher()).unwrap();
+
+        assert_eq!(hash_input, hash_output)
+    }
+
+    #[tokio::test]
+    async fn raw_recv_file_with_hostile_name() {
+        crate::init_logger_for_test();
+
+        let (temp_dir, input_path) = file_hashing::fs::extra::generate_random_file(3626);
+        let output_dir = temp_dir.join("output");
+        std::fs::create_dir(&output_dir).unwrap();
+
+        for (i, policy) in [
+            FileNamePolicy::Reject,
+            FileNamePolicy::Sanitize,
+            FileNamePolicy::Rename,
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let address = format!("127.0.0.1:{}", 6433 + i);
+            let listener = TcpListener::bind(&address).await.unwrap();
+
+            let send = async {
+                let mut connection = TcpStream::connect(&address).await.unwrap();
+                let mut handshake = get_handshake_from_file(
+                    input_path.path(),
+                    HashAlgorithm::Blake2b,
+                    PreserveMetadata::default(),
+                )
+                .await
+                .unwrap();
+                handshake.file_name = "../escaped.txt".to_string();
+
+                send_handshake_in_band(&handshake, &mut connection).await?;
+                recv_handshake_answer(&mut connection).await?;
+                send_file_data(&mut connection, input_path.path(), &handshake, &None, 0).await
+            };
+
+            let recv = async {
+                let (mut connection, addr) = listener.accept().await.unwrap();
+                let handshake = recv_handshake_in_band(&mut connection).await?;
+                let path = get_path_for_file(
+                    &mut connection,
+                    &output_dir,
+                    &handshake,
+                    policy,
+                    FileNameEncoding::Utf8,
+                )
+                .await?;
+                recv_file(
+                    &mut connection,
+                    path.as_path(),
+                    &None,
+                    0,
+                    handshake,
+                    false,
+                    addr,
+                )
+                .await
+            };
+
+            let (recv, send) = tokio::join!(recv, send);
+            assert!(!temp_dir.join("escaped.txt").exists());
+
+            match policy {
+                FileNamePolicy::Reject => {
+                    assert!(matches!(send, Err(ProtocolError::Rejected(_))));
+                    assert!(matches!(
+                        recv,
+                        Err(ProtocolError::Handshake(HandshakeError::UnsafeFileName(_)))
+                    ));
+                }
+                _ => {
+                    send.unwrap();
+                    let (path, _) = recv.unwrap();
+                    assert!(path.starts_with(&output_dir));
+                    assert_eq!(
+                        std::fs::read(input_path.path()).unwrap(),
+                        std::fs::read(path).unwrap()
+                    );
+                }
+            }
+        }
+    }
+}
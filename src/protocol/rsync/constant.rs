@@ -1,5 +1,21 @@
+//! Constants for [`rsync`](crate::protocol::rsync)
+
 use crate::common::DEFAULT_BUFFER_SIZE_FOR_NETWORK;
 
+/// Block size for [`Signature`](fast_rsync::Signature)
 pub const DEFAULT_BLOCK_SIZE: u32 = DEFAULT_BUFFER_SIZE_FOR_NETWORK as u32;
 
-pub const DEFAULT_CRYPTO_HASH_SIZE: u32 = 256;
+/// How many bytes of the MD4 hash are stored for each block.
+///
+/// **Must be no more than 16!**
+pub const DEFAULT_CRYPTO_HASH_SIZE: u32 = 8;
+
+/// How many bytes of the files are read, diffed and applied at once.
+///
+/// **Must be multiple of [`DEFAULT_BLOCK_SIZE`]!**
+pub const DEFAULT_WINDOW_SIZE: usize = 256 * DEFAULT_BLOCK_SIZE as usize;
+
+/// Max size of the old file
+///
+/// Its signature is held in memory: 12 bytes for every block of [`DEFAULT_BLOCK_SIZE`]
+pub const MAX_FILE_SIZE: u64 = 16 * 1024 * 1024 * 1024;
@@ -0,0 +1,244 @@
+//! Streamed [`Signature`] and delta of [`fast_rsync`]
+//!
+//! `fast_rsync` works on whole buffers. Here files are read by windows of [`DEFAULT_WINDOW_SIZE`]:
+//! signatures of the windows are joined into one and each window of the new file
+//! gets its own delta. Delta is applied with reading the old file by blocks,
+//! the same way as [`fast_rsync::apply`].
+
+use super::{DEFAULT_BLOCK_SIZE, DEFAULT_CRYPTO_HASH_SIZE, DEFAULT_WINDOW_SIZE, MAX_FILE_SIZE};
+use fast_rsync::{ApplyError, Signature, SignatureOptions};
+use std::io::SeekFrom;
+use tokio::{
+    fs::File,
+    io::{AsyncRead, AsyncReadExt, AsyncSeekExt},
+};
+
+/// Magic, block size and size of the crypto hash: u32 each
+const SIGNATURE_HEADER_SIZE: usize = 12;
+
+/// Commands of the delta. See [librsync](https://github.com/librsync/librsync/blob/master/doc/format.md)
+const DELTA_MAGIC: u32 = 0x72730236;
+const RS_OP_END: u8 = 0x00;
+const RS_OP_LITERAL_1: u8 = 0x01;
+const RS_OP_LITERAL_64: u8 = 0x40;
+const RS_OP_LITERAL_N1: u8 = 0x41;
+const RS_OP_LITERAL_N8: u8 = 0x44;
+const RS_OP_COPY_N1_N1: u8 = 0x45;
+const RS_OP_COPY_N8_N8: u8 = 0x54;
+
+/// Max size of the signature of a file no bigger than [`MAX_FILE_SIZE`]
+pub(crate) const MAX_SIGNATURE_SIZE: u64 = SIGNATURE_HEADER_SIZE as u64
+    + MAX_FILE_SIZE.div_ceil(DEFAULT_BLOCK_SIZE as u64) * (4 + DEFAULT_CRYPTO_HASH_SIZE as u64);
+
+/// Max size of the delta of one window. Literals have a header
+pub(crate) const MAX_DELTA_SIZE: u64 = 2 * DEFAULT_WINDOW_SIZE as u64;
+
+/// Read until `buf` is full or the end of the file
+pub(crate) async fn read_window(
+    reader: &mut (impl AsyncRead + Unpin),
+    buf: &mut [u8],
+) -> std::io::Result<usize> {
+    let mut len = 0;
+    while len < buf.len() {
+        match reader.read(&mut buf[len..]).await? {
+            0 => break,
+            n => len += n,
+        }
+    }
+
+    Ok(len)
+}
+
+/// Calculate the signature of the whole file, reading it by windows
+///
+/// Windows are multiple of [`DEFAULT_BLOCK_SIZE`], so their blocks are the blocks of the file
+pub(crate) async fn calculate_signature(
+    reader: &mut (impl AsyncRead + Unpin),
+) -> std::io::Result<Vec<u8>> {
+    let mut buf = vec![0u8; DEFAULT_WINDOW_SIZE];
+    let mut storage = Vec::new();
+    let mut serialized = Vec::new();
+    let mut window = Vec::new();
+
+    loop {
+        let len = read_window(reader, &mut buf).await?;
+        let signature = Signature::calculate(
+            &buf[..len],
+            &mut storage,
+            SignatureOptions {
+                block_size: DEFAULT_BLOCK_SIZE,
+                crypto_hash_size: DEFAULT_CRYPTO_HASH_SIZE,
+            },
+        );
+
+        window.clear();
+        signature.serialize(&mut window);
+        match serialized.is_empty() {
+            true => serialized.append(&mut window),
+            false => serialized.extend_from_slice(&window[SIGNATURE_HEADER_SIZE..]),
+        }
+
+        if len < buf.len() {
+            return Ok(serialized);
+        }
+    }
+}
+
+/// Read `n` bytes from the start of `delta`
+fn take<'a>(delta: &mut &'a [u8], n: usize, reading: &'static str) -> Result<&'a [u8], ApplyError> {
+    if delta.len() < n {
+        return Err(ApplyError::UnexpectedEof {
+            reading,
+            expected: n,
+            available: delta.len(),
+        });
+    }
+
+    let (prefix, rest) = delta.split_at(n);
+    *delta = rest;
+    Ok(prefix)
+}
+
+/// Read number (big endian) of `n` bytes from the start of `delta`
+fn take_number(delta: &mut &[u8], n: usize, reading: &'static str) -> Result<u64, ApplyError> {
+    let mut bytes = [0u8; 8];
+    bytes[8 - n..].copy_from_slice(take(delta, n, reading)?);
+    Ok(u64::from_be_bytes(bytes))
+}
+
+/// Apply `delta` of one window to the old file `base`, appending the result to `out`
+///
+/// Errors if more than `limit` bytes would be appended
+pub(crate) async fn apply(
+    base: &mut File,
+    base_size: u64,
+    mut delta: &[u8],
+    out: &mut Vec<u8>,
+    limit: usize,
+) -> Result<(), ApplyError> {
+    let magic = take_number(&mut delta, 4, "magic")? as u32;
+    if magic != DELTA_MAGIC {
+        return Err(ApplyError::WrongMagic { magic });
+    }
+
+    let limit = out.len() + limit;
+    loop {
+        let command = take(&mut delta, 1, "cmd")?[0];
+        let (offset, len, what) = match command {
+            RS_OP_END => break,
+            RS_OP_LITERAL_1..=RS_OP_LITERAL_64 => {
+                (None, (1 + command - RS_OP_LITERAL_1) as u64, "literal")
+            }
+            RS_OP_LITERAL_N1..=RS_OP_LITERAL_N8 => {
+                let size = 1 << (command - RS_OP_LITERAL_N1);
+                let len = take_number(&mut delta, size, "literal length")?;
+                (None, len, "literal")
+            }
+            RS_OP_COPY_N1_N1..=RS_OP_COPY_N8_N8 => {
+                let mode = command - RS_OP_COPY_N1_N1;
+                let offset = take_number(&mut delta, 1 << (mode / 4), "copy offset")?;
+                let len = take_number(&mut delta, 1 << (mode % 4), "copy length")?;
+                (Some(offset), len, "copy")
+            }
+            _ => return Err(ApplyError::UnknownCommand { command }),
+        };
+
+        let available = limit - out.len();
+        if len > available as u64 {
+            return Err(ApplyError::OutputLimit {
+                what,
+                wanted: len.try_into().unwrap_or(usize::MAX),
+                available,
+            });
+        }
+        let len = len as usize;
+
+        match offset {
+            None => out.extend_from_slice(take(&mut delta, len, "literal")?),
+            Some(offset) => {
+                if len == 0 {
+                    return Err(ApplyError::CopyZero);
+                }
+                if offset
+                    .checked_add(len as u64)
+                    .is_none_or(|end| end > base_size)
+                {
+                    return Err(ApplyError::CopyOutOfBounds {
+                        offset,
+                        len: len as u64,
+                        data_len: base_size as usize,
+                    });
+                }
+
+                base.seek(SeekFrom::Start(offset)).await?;
+                let start = out.len();
+                out.resize(start + len, 0);
+                base.read_exact(&mut out[start..]).await?;
+            }
+        }
+    }
+
+    match delta.is_empty() {
+        true => Ok(()),
+        false => Err(ApplyError::TrailingData {
+            length: delta.len(),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn streamed_signature_and_apply_match_fast_rsync() {
+        let base: Vec<u8> = (0..DEFAULT_WINDOW_SIZE * 2 + 1000)
+            .map(|i| (i * 7 % 251) as u8)
+            .collect();
+        let mut new_data = base.clone();
+        new_data.drain(10..5000);
+        new_data.extend_from_slice(b"new end of the file");
+
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        let path = temp_dir.join("base");
+        std::fs::write(&path, &base).unwrap();
+
+        let mut storage = Vec::new();
+        let mut expected = Vec::new();
+        Signature::calculate(
+            &base,
+            &mut storage,
+            SignatureOptions {
+                block_size: DEFAULT_BLOCK_SIZE,
+                crypto_hash_size: DEFAULT_CRYPTO_HASH_SIZE,
+            },
+        )
+        .serialize(&mut expected);
+
+        let mut base_file = File::open(&path).await.unwrap();
+        let serialized = calculate_signature(&mut base_file).await.unwrap();
+        assert_eq!(serialized, expected);
+
+        let signature = Signature::deserialize(&serialized).unwrap().index();
+        let mut out = Vec::new();
+        for window in new_data.chunks(DEFAULT_WINDOW_SIZE) {
+            let mut delta = Vec::new();
+            fast_rsync::diff(&signature, window, &mut delta).unwrap();
+            apply(
+                &mut base_file,
+                base.len() as u64,
+                &delta,
+                &mut out,
+                DEFAULT_WINDOW_SIZE,
+            )
+            .await
+            .unwrap();
+        }
+        assert_eq!(out, new_data);
+
+        let mut delta = Vec::new();
+        fast_rsync::diff(&signature, &new_data[..100], &mut delta).unwrap();
+        let result = apply(&mut base_file, base.len() as u64, &delta, &mut out, 99).await;
+        assert!(matches!(result, Err(ApplyError::OutputLimit { .. })));
+    }
+}
@@ -1,11 +1,30 @@
-use crate::protocol::error::ProtocolError;
+//! All error in [`rsync`](crate::protocol::rsync)
+
+use crate::protocol::{error::ProtocolError, udt::UdtError};
 use thiserror::Error;
 
+/// Enum error
 #[derive(Debug, Error)]
 pub enum RSyncError {
     #[error("problem in protocol: {0}")]
     Protocol(ProtocolError),
 
+    /// Received [`Signature`](fast_rsync::Signature) is broken
+    #[error("parse signature")]
+    Signature(#[source] fast_rsync::SignatureParseError),
+
+    /// Failed to calculate delta from [`Signature`](fast_rsync::Signature)
+    #[error("calculate delta")]
+    Diff(#[source] fast_rsync::DiffError),
+
+    /// Failed to apply delta to the old file
+    #[error("apply delta")]
+    Apply(#[source] fast_rsync::ApplyError),
+
+    /// The old file or its signature is bigger than [`MAX_FILE_SIZE`](crate::protocol::rsync::MAX_FILE_SIZE) allows
+    #[error("file is too big for rsync: {0} bytes")]
+    FileTooBig(u64),
+
     /// Wrong use function in [`rsync`](crate::protocol::rsync)
     #[error("wrong use function: {0}")]
     Assert(String),
@@ -27,6 +46,15 @@ macro_rules! assert_rsync {
 
 pub(crate) use assert_rsync;
 
+impl From<UdtError> for RSyncError {
+    fn from(error: UdtError) -> Self {
+        match error {
+            UdtError::Protocol(e) => RSyncError::Protocol(e),
+            UdtError::Assert(message) => RSyncError::Assert(message),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
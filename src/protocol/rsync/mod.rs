@@ -1,5 +1,26 @@
 //! Sync files by rsync
 //!
+//! # Example
+//!
+//! ```no_run
+//! # use snwf::prelude::*;
+//! # use std::path::Path;
+//! #
+//! #[tokio::main]
+//! async fn main() {
+//!    let mut sender = Sender::new("127.0.0.1".parse().unwrap(), 4324, 6343);
+//!    let mut recipient = Recipient::new("::0".parse().unwrap(), 4324, 6343);
+//!
+//!    let (recv, send) = tokio::join!(
+//!        recipient.rsync_sync_file(Path::new("old_file.txt")),
+//!        sender.rsync_sync_file(Path::new("new_file.txt"))
+//!    );
+//!
+//!    send.unwrap();
+//!    recv.unwrap();
+//! }
+//! ```
+//!
 //! # How it works?
 //!
 //! 1. Host B calculates the `Signature` of `foo_B` and sends it to `A`. This is
@@ -10,6 +31,13 @@
 //! 2. `Host A` calculates a diff from `B's` signature and `foo_A`, and sends it to `B`.
 //! 3. `Host B` attempts to apply the `delta` to `foo_B`. The resulting data
 //!    is probably (*) equal to foo_A.
+//! 4. `Host B` checks the result with the checksum from the handshake.
+//!
+//! # Limits
+//!
+//! Files are read by windows of [`DEFAULT_WINDOW_SIZE`], only the signature of the old file
+//! is held in memory. Old files bigger than [`MAX_FILE_SIZE`] are rejected with [`RSyncError::FileTooBig`].
+//! Blocks that cross windows of the new file are sent as literals.
 //!
 //! # What libraries to use
 //!
@@ -18,6 +46,7 @@
 //!   [librsync](https://github.com/librsync/librsync) in pure Rust
 
 pub mod constant;
+mod delta;
 pub mod error;
 mod raw;
 pub mod rsync_recipient;
@@ -28,3 +57,132 @@ pub(crate) use error::assert_rsync;
 pub use error::RSyncError;
 pub use rsync_recipient::RSyncRecipient;
 pub use rsync_sender::RSyncSender;
+
+#[cfg(test)]
+mod tests {
+    use super::DEFAULT_WINDOW_SIZE;
+    use crate::{common::get_hasher, core::*, prelude::*};
+    use log::debug;
+    use std::sync::{Arc, Mutex};
+
+    #[tokio::test]
+    async fn sync_rsync_with_progress_fn() {
+        crate::init_logger_for_test();
+
+        let run_progressing_sender = Arc::new(Mutex::new(false));
+        let run_progressing_recipient = Arc::new(Mutex::new(false));
+
+        let (_temp_dir, path_new) = file_hashing::fs::extra::generate_random_file(9421);
+        let (_temp_dir_old, path_old) = file_hashing::fs::extra::generate_random_file(1);
+
+        let mut old_data = std::fs::read(&path_new).unwrap();
+        old_data.truncate(6000);
+        old_data.extend_from_slice(b"old version of the file");
+        std::fs::write(&path_old, old_data).unwrap();
+
+        let mut sender = Sender::new("127.0.0.1".parse().unwrap(), 4621, 6721);
+        let mut recipient = Recipient::new("::0".parse().unwrap(), 4621, 6721);
+
+        {
+            let run_progressing_sender_clone = run_progressing_sender.clone();
+            sender.set_progress_fn(Some(move |progressing| {
+                debug!("progressing sender: {:?}", progressing);
+
+                if let Progressing::Done = progressing {
+                    *run_progressing_sender_clone.lock().unwrap() = true;
+                }
+            }));
+        }
+
+        {
+            let run_progressing_recipient_clone = run_progressing_recipient.clone();
+            recipient.set_progress_fn(Some(move |progressing| {
+                debug!("progressing recipient: {:?}", progressing);
+
+                if let Progressing::Done = progressing {
+                    *run_progressing_recipient_clone.lock().unwrap() = true;
+                }
+            }));
+        }
+
+        let (recv, send) = tokio::join!(
+            recipient.rsync_sync_file(path_old.path()),
+            sender.rsync_sync_file(path_new.path())
+        );
+
+        send.unwrap();
+        recv.unwrap();
+
+        let hash_new = file_hashing::get_hash_file(&path_new, &mut get_hasher()).unwrap();
+        let hash_old = file_hashing::get_hash_file(&path_old, &mut get_hasher()).unwrap();
+
+        assert_eq!(hash_new, hash_old);
+        assert!(*run_progressing_sender.lock().unwrap());
+        assert!(*run_progressing_recipient.lock().unwrap());
+    }
+
+    #[tokio::test]
+    async fn sync_rsync_by_windows_with_progress_of_file() {
+        crate::init_logger_for_test();
+
+        let size = 2 * DEFAULT_WINDOW_SIZE + 5000;
+        let (_temp_dir, path_new) = file_hashing::fs::extra::generate_random_file(size);
+        let (_temp_dir_old, path_old) = file_hashing::fs::extra::generate_random_file(1);
+
+        // Only the middle of the file is changed
+        let mut old_data = std::fs::read(&path_new).unwrap();
+        old_data[DEFAULT_WINDOW_SIZE..DEFAULT_WINDOW_SIZE + 100].fill(0);
+        std::fs::write(&path_old, old_data).unwrap();
+
+        let last_progress_sender = Arc::new(Mutex::new(None));
+        let last_progress_recipient = Arc::new(Mutex::new(None));
+
+        let mut sender = Sender::new("127.0.0.1".parse().unwrap(), 4622, 6722);
+        let mut recipient = Recipient::new("::0".parse().unwrap(), 4622, 6722);
+
+        {
+            let last_progress = last_progress_sender.clone();
+            sender.set_progress_fn(Some(move |progressing| {
+                if let Progressing::Yield {
+                    total_bytes,
+                    done_bytes,
+                    ..
+                } = progressing
+                {
+                    *last_progress.lock().unwrap() = Some((total_bytes, done_bytes));
+                }
+            }));
+        }
+
+        {
+            let last_progress = last_progress_recipient.clone();
+            recipient.set_progress_fn(Some(move |progressing| {
+                if let Progressing::Yield {
+                    total_bytes,
+                    done_bytes,
+                    ..
+                } = progressing
+                {
+                    *last_progress.lock().unwrap() = Some((total_bytes, done_bytes));
+                }
+            }));
+        }
+
+        let (recv, send) = tokio::join!(
+            recipient.rsync_sync_file(path_old.path()),
+            sender.rsync_sync_file(path_new.path())
+        );
+
+        send.unwrap();
+        recv.unwrap();
+
+        assert_eq!(
+            std::fs::read(&path_new).unwrap(),
+            std::fs::read(&path_old).unwrap()
+        );
+
+        let size = size as u64;
+        assert_eq!(*last_progress_sender.lock().unwrap(), Some((size, size)));
+        assert_eq!(*last_progress_recipient.lock().unwrap(), Some((size, size)));
+    }
+}
@@ -1 +1,305 @@
-// TODO
+//! Raw [`rsync`](crate::protocol::rsync) implementation
+
+use super::{delta, RSyncError, DEFAULT_WINDOW_SIZE, MAX_FILE_SIZE};
+use crate::{
+    common::{timeout, Hasher, DEFAULT_BUFFER_SIZE_FOR_NETWORK as NBUFFER_SIZE},
+    core::*,
+    prelude::{ConfigRecipient, ConfigSender},
+    protocol::{
+        connection::{DataConnection, TcpConnection},
+        error::ProtocolError,
+        handshake::{
+            get_handshake_from_file, recv_handshake_from_socket, send_handshake_to, Handshake,
+        },
+        raw,
+        signing::check_unsigned_allowed,
+    },
+};
+use fast_rsync::Signature;
+use log::debug;
+use std::path::{Path, PathBuf};
+use tokio::{
+    fs::{remove_file, rename, File},
+    io::{AsyncReadExt, AsyncWriteExt},
+};
+
+fn run_progress_fn(config: &Option<impl CoreConfig>, progressing: Progressing) {
+    if let Some(config) = config {
+        config.run_progress_fn(progressing);
+    }
+}
+
+/// Path for the new version of the file until it is written
+fn get_temp_path(path: &Path) -> PathBuf {
+    let mut temp_path = path.as_os_str().to_owned();
+    temp_path.push(".rsync");
+    PathBuf::from(temp_path)
+}
+
+/// Signature is held in memory. The old file must be no bigger than [`MAX_FILE_SIZE`]
+fn check_file_size(size: u64) -> Result<(), RSyncError> {
+    match size > MAX_FILE_SIZE {
+        true => Err(RSyncError::FileTooBig(size)),
+        false => Ok(()),
+    }
+}
+
+/// Send data with the size (u64 big endian) before it
+async fn send_data(connection: &mut impl DataConnection, data: &[u8]) -> Result<(), RSyncError> {
+    timeout!(
+        connection.send_data(&(data.len() as u64).to_be_bytes()),
+        |_| RSyncError::Protocol(ProtocolError::TimeoutExpired)
+    )?
+    .map_err(|e| RSyncError::Protocol(ProtocolError::FileIO(e)))?;
+
+    for chunk in data.chunks(NBUFFER_SIZE) {
+        timeout!(connection.send_data(chunk), |_| {
+            RSyncError::Protocol(ProtocolError::TimeoutExpired)
+        })?
+        .map_err(|e| RSyncError::Protocol(ProtocolError::FileIO(e)))?;
+    }
+
+    Ok(())
+}
+
+/// Receive the size of data sent by [`send_data`]
+async fn recv_size(connection: &mut impl DataConnection) -> Result<u64, RSyncError> {
+    let mut size = [0u8; 8];
+    connection
+        .recv_exact(&mut size)
+        .await
+        .map_err(|e| RSyncError::Protocol(ProtocolError::ReceivingData(e)))?;
+
+    Ok(u64::from_be_bytes(size))
+}
+
+/// Receive data sent by [`send_data`] after [`recv_size`]
+async fn recv_data(
+    connection: &mut impl DataConnection,
+    total_bytes: u64,
+) -> Result<Vec<u8>, RSyncError> {
+    let mut data = Vec::new();
+    let mut buf = vec![0u8; NBUFFER_SIZE];
+
+    while (data.len() as u64) < total_bytes {
+        let need = NBUFFER_SIZE.min((total_bytes - data.len() as u64) as usize);
+        let len = connection
+            .recv_data(&mut buf[..need])
+            .await
+            .map_err(|e| RSyncError::Protocol(ProtocolError::ReceivingData(e)))?;
+
+        if len == 0 {
+            return Err(RSyncError::Protocol(ProtocolError::ReceivingData(
+                std::io::ErrorKind::UnexpectedEof.into(),
+            )));
+        }
+
+        data.extend_from_slice(&buf[..len]);
+    }
+
+    Ok(data)
+}
+
+/// Get the signature of the recipient, calculate delta and send it
+///
+/// The file is read and diffed by windows of [`DEFAULT_WINDOW_SIZE`]. Each delta is sent
+/// with its size before it. Progress is counted in bytes of the file
+pub(crate) async fn send_delta<P>(
+    connection: &mut impl DataConnection,
+    path: P,
+    handshake_socket: Option<&mut TcpConnection>,
+    config: &Option<ConfigSender<'_>>,
+) -> Result<(), RSyncError>
+where
+    P: AsRef<Path> + Sync + Copy,
+{
+    // Metadata is not sent: see PreserveMetadata
+    let handshake = get_handshake_from_file(
+        path,
+        raw::get_hash_algorithm(config),
+        PreserveMetadata::default(),
+    )
+    .await
+    .map_err(|e| RSyncError::Protocol(ProtocolError::Handshake(e)))?;
+    send_handshake_to(&handshake, connection, handshake_socket)
+        .await
+        .map_err(|e| RSyncError::Protocol(ProtocolError::Handshake(e)))?;
+
+    let size = recv_size(connection).await?;
+    if size > delta::MAX_SIGNATURE_SIZE {
+        return Err(RSyncError::FileTooBig(size));
+    }
+    let signature = recv_data(connection, size).await?;
+    let signature = Signature::deserialize(&signature).map_err(RSyncError::Signature)?;
+    let signature = signature.index();
+    debug!("rsync send_delta. Got signature");
+
+    let mut file = File::open(path)
+        .await
+        .map_err(|e| RSyncError::Protocol(ProtocolError::FileIO(e)))?
+        .take(handshake.size);
+    let mut hasher = Hasher::new(handshake.hash_algorithm);
+    let mut buf = vec![0u8; DEFAULT_WINDOW_SIZE];
+    let mut delta = Vec::new();
+    let mut done_bytes = 0;
+
+    loop {
+        let len = delta::read_window(&mut file, &mut buf)
+            .await
+            .map_err(|e| RSyncError::Protocol(ProtocolError::FileIO(e)))?;
+        if len == 0 {
+            break;
+        }
+
+        delta.clear();
+        fast_rsync::diff(&signature, &buf[..len], &mut delta).map_err(RSyncError::Diff)?;
+        send_data(connection, &delta).await?;
+        hasher.update(&buf[..len]);
+
+        done_bytes += len as u64;
+        run_progress_fn(
+            config,
+            Progressing::Yield {
+                done_files: 0,
+                total_bytes: handshake.size,
+                done_bytes,
+                path_to_file: path.as_ref().to_path_buf(),
+            },
+        );
+    }
+    debug!(
+        "rsync send_delta. Done send {} bytes of the file",
+        done_bytes
+    );
+
+    raw::send_trailer(connection, hasher, done_bytes)
+        .await
+        .map_err(RSyncError::Protocol)?;
+
+    run_progress_fn(config, Progressing::Done);
+    Ok(())
+}
+
+/// Receive deltas of the windows, apply them to the old file `base` and write the new file
+/// to the temporary path. Then check it with the trailer
+async fn recv_new_file(
+    connection: &mut impl DataConnection,
+    base: &mut File,
+    base_size: u64,
+    path: &Path,
+    handshake: &Handshake,
+    config: &Option<ConfigRecipient<'_>>,
+) -> Result<(), RSyncError> {
+    let mut temp = File::create(get_temp_path(path))
+        .await
+        .map_err(|e| RSyncError::Protocol(ProtocolError::FileIO(e)))?;
+    let mut hasher = Hasher::new(handshake.hash_algorithm);
+    let mut window = Vec::with_capacity(DEFAULT_WINDOW_SIZE);
+    let mut done_bytes = 0;
+
+    while done_bytes < handshake.size {
+        let delta_size = recv_size(connection).await?;
+        if delta_size > delta::MAX_DELTA_SIZE {
+            debug!("delta is too big! size: {}", delta_size);
+            return Err(RSyncError::Protocol(ProtocolError::FileInvalid));
+        }
+        let delta = recv_data(connection, delta_size).await?;
+
+        window.clear();
+        let limit = DEFAULT_WINDOW_SIZE.min((handshake.size - done_bytes) as usize);
+        delta::apply(base, base_size, &delta, &mut window, limit)
+            .await
+            .map_err(RSyncError::Apply)?;
+        if window.is_empty() {
+            return Err(RSyncError::Protocol(ProtocolError::FileInvalid));
+        }
+
+        temp.write_all(&window)
+            .await
+            .map_err(|e| RSyncError::Protocol(ProtocolError::FileIO(e)))?;
+        hasher.update(&window);
+
+        done_bytes += window.len() as u64;
+        run_progress_fn(
+            config,
+            Progressing::Yield {
+                done_files: 0,
+                total_bytes: handshake.size,
+                done_bytes,
+                path_to_file: path.to_path_buf(),
+            },
+        );
+    }
+
+    temp.flush()
+        .await
+        .map_err(|e| RSyncError::Protocol(ProtocolError::FileIO(e)))?;
+
+    // Check file
+    debug!("rsync recv_delta. Checking file");
+    raw::recv_and_check_trailer(connection, hasher, done_bytes)
+        .await
+        .map_err(RSyncError::Protocol)?;
+
+    Ok(())
+}
+
+/// Send the signature of the old file, receive delta and apply it
+///
+/// The old file is read by blocks, the new file is written to a temporary file
+/// by windows of [`DEFAULT_WINDOW_SIZE`]. Progress is counted in bytes of the new file
+pub(crate) async fn recv_delta<P>(
+    connection: &mut impl DataConnection,
+    socket: Option<&mut TcpConnection>,
+    path: P,
+    config: &Option<ConfigRecipient<'_>>,
+) -> Result<(), RSyncError>
+where
+    P: AsRef<Path> + Sync + Copy,
+{
+    let handshake = recv_handshake_from_socket(connection, socket)
+        .await
+        .map_err(|e| RSyncError::Protocol(ProtocolError::Handshake(e)))?;
+    check_unsigned_allowed(config, "rsync").map_err(RSyncError::Protocol)?;
+    raw::check_hash_algorithm(config, handshake.hash_algorithm)
+        .map_err(|e| RSyncError::Protocol(ProtocolError::Handshake(e)))?;
+
+    let mut base = File::open(path)
+        .await
+        .map_err(|e| RSyncError::Protocol(ProtocolError::FileIO(e)))?;
+    let base_size = base
+        .metadata()
+        .await
+        .map_err(|e| RSyncError::Protocol(ProtocolError::FileIO(e)))?
+        .len();
+    check_file_size(base_size)?;
+
+    let signature = delta::calculate_signature(&mut base)
+        .await
+        .map_err(|e| RSyncError::Protocol(ProtocolError::FileIO(e)))?;
+    send_data(connection, &signature).await?;
+    debug!("rsync recv_delta. Done send signature");
+
+    let temp_path = get_temp_path(path.as_ref());
+    let result = recv_new_file(
+        connection,
+        &mut base,
+        base_size,
+        path.as_ref(),
+        &handshake,
+        config,
+    )
+    .await;
+
+    if let Err(e) = result {
+        let _ = remove_file(&temp_path).await;
+        return Err(e);
+    }
+
+    rename(&temp_path, path)
+        .await
+        .map_err(|e| RSyncError::Protocol(ProtocolError::FileIO(e)))?;
+
+    run_progress_fn(config, Progressing::Done);
+    Ok(())
+}
@@ -1,19 +1,35 @@
-use super::{assert_rsync, RSyncError, DEFAULT_BLOCK_SIZE, DEFAULT_CRYPTO_HASH_SIZE};
-use crate::common::DEFAULT_BUFFER_SIZE_FOR_NETWORK;
-use crate::prelude::{CoreRecipient, CoreSender, Recipient, Sender};
-use crate::protocol::error::ProtocolError;
-use crate::protocol::handshake::{send_handshake_from_file, recv_handshake_from_address};
+//! Implementation [`rsync`](crate::protocol::rsync) for trait [`CoreRecipient`]
+
+use super::{assert_rsync, raw, RSyncError};
+use crate::{
+    prelude::{CoreRecipient, Recipient},
+    protocol::udt::detail,
+};
 use async_trait::async_trait;
-use fast_rsync::SignatureOptions;
 use log::debug;
 use std::path::Path;
-use tokio::fs::File;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tokio::net::{TcpListener, TcpStream};
-use tokio_udt::UdtListener;
 
+/// [`rsync`](crate::protocol::rsync) trait for [`CoreRecipient`]
 #[async_trait(?Send)]
 pub trait RSyncRecipient<'a>: CoreRecipient<'a> {
+    /// Sync file with the file of [`Sender`](crate::sender::Sender)
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - old version of the file. It will be replaced with the new version.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use snwf::prelude::*;
+    /// # use std::path::Path;
+    /// #
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut recipient = Recipient::new("::0".parse().unwrap(), 4324, 6343);
+    ///
+    ///     recipient.rsync_sync_file(Path::new("old_file.txt"));
+    /// }
+    /// ```
     async fn rsync_sync_file<P>(&mut self, path: P) -> Result<(), RSyncError>
     where
         P: AsRef<Path> + Send + Copy + Sync;
@@ -27,53 +43,24 @@ impl<'a> RSyncRecipient<'a> for Recipient<'a> {
     {
         assert_rsync!(path.as_ref().is_file(), "path isn't file or not exists");
 
-        let config = self.get_config();
+        let mut config = self.get_config();
         debug!(
-            "run rsync_sync_file for Sender! config: {:?}, path: {:?}",
+            "run rsync_sync_file for Recipient! config: {:?}, path: {:?}",
             config,
             path.as_ref()
         );
 
-        let mut storage = vec![0u8; DEFAULT_BUFFER_SIZE_FOR_NETWORK];
-        let mut buf = vec![0u8; DEFAULT_BUFFER_SIZE_FOR_NETWORK];
-        let mut file = File::open(path)
-            .await
-            .map_err(|e| RSyncError::Protocol(ProtocolError::FileIO(e)))?;
-
-        let mut udt_listener =
-            UdtListener::bind((config.addr, config.port_for_handshake).into(), None)
-                .await
-                .map_err(|e| RSyncError::Protocol(ProtocolError::Bind(e)))?;
-        let mut tcp_listener = TcpListener::bind((config.addr, config.port_for_handshake))
-            .await
-            .map_err(|e| RSyncError::Protocol(ProtocolError::Bind(e)))?;
-
-        recv_handshake_from_address(&mut tcp_listener)
-            .await
-            .map_err(|e| RSyncError::Protocol(ProtocolError::Handshake(e)))?;
-
-        let (mut tcp_socket, addr) = tcp_listener.accept().await.unwrap();
-        loop {
-            let len = file
-                .read_buf(&mut buf)
-                .await
-                .map_err(|e| RSyncError::Protocol(ProtocolError::FileIO(e)))?;
-
-            if len == 0 {
-                break;
-            }
-
-            fast_rsync::Signature::calculate(
-                &buf[..],
-                &mut storage,
-                SignatureOptions {
-                    block_size: DEFAULT_BLOCK_SIZE,
-                    crypto_hash_size: DEFAULT_CRYPTO_HASH_SIZE,
-                },
-            );
+        let (_, negotiated, mut connection, mut socket_for_handshake) =
+            detail::accept_for_recipient(&mut config).await?;
+        self.negotiated_capabilities = Some(negotiated);
 
-            tcp_socket.write_all(&mut storage).await.unwrap();
-        }
+        raw::recv_delta(
+            &mut connection,
+            socket_for_handshake.as_mut(),
+            path,
+            &Some(config),
+        )
+        .await?;
 
         Ok(())
     }
@@ -1,18 +1,33 @@
-use super::{assert_rsync, RSyncError, DEFAULT_BLOCK_SIZE, DEFAULT_CRYPTO_HASH_SIZE};
-use crate::common::DEFAULT_BUFFER_SIZE_FOR_NETWORK;
-use crate::prelude::{CoreSender, Sender};
-use crate::protocol::error::ProtocolError;
-use crate::protocol::handshake::send_handshake_from_file;
+//! Implementation [`rsync`](crate::protocol::rsync) for trait [`CoreSender`]
+
+use super::{assert_rsync, raw, RSyncError};
+use crate::{
+    prelude::{CoreSender, Sender},
+    protocol::{raw::negotiate_for_sender, udt::detail},
+};
 use async_trait::async_trait;
-use fast_rsync::SignatureOptions;
 use log::debug;
 use std::path::Path;
-use tokio::fs::File;
-use tokio::io::AsyncReadExt;
-use tokio::net::TcpStream;
 
+/// [`rsync`](crate::protocol::rsync) trait for [`CoreSender`]
 #[async_trait(?Send)]
 pub trait RSyncSender<'a>: CoreSender<'a> {
+    /// Sync file with the file of [`Recipient`](crate::recipient::Recipient)
+    ///
+    /// Only the delta between the files is sent
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use snwf::prelude::*;
+    /// # use std::path::Path;
+    /// #
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut sender = Sender::new("127.0.0.1".parse().unwrap(), 4324, 6343);
+    ///
+    ///     sender.rsync_sync_file(Path::new("new_file.txt"));
+    /// }
+    /// ```
     async fn rsync_sync_file<P>(&mut self, path: P) -> Result<(), RSyncError>
     where
         P: AsRef<Path> + Send + Copy + Sync;
@@ -24,6 +39,22 @@ impl<'a> RSyncSender<'a> for Sender<'a> {
     where
         P: AsRef<Path> + Send + Copy + Sync,
     {
-        todo!()
+        assert_rsync!(path.as_ref().is_file(), "path isn't file or not exists");
+
+        let mut config = self.get_config();
+        debug!(
+            "run rsync_sync_file for Sender! config: {:?}, path: {:?}",
+            config,
+            path.as_ref()
+        );
+
+        let (mut udt, mut socket_for_handshake) = detail::all_connect_for_sender(&config).await?;
+        let (negotiated, mut udt) = negotiate_for_sender(&mut udt, &mut config)
+            .await
+            .map_err(RSyncError::Protocol)?;
+        self.negotiated_capabilities = Some(negotiated);
+        raw::send_delta(&mut udt, path, socket_for_handshake.as_mut(), &Some(config)).await?;
+
+        Ok(())
     }
 }
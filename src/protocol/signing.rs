@@ -0,0 +1,294 @@
+//! Signing of [`Handshake`] with [Ed25519](https://en.wikipedia.org/wiki/EdDSA#Ed25519). See [`Signer`]
+//!
+//! # Description
+//!
+//! [`Sender`](crate::sender::Sender) hashes the file before the handshake and adds
+//! the extension `2`: public key (32 bytes) + time of signing (u64 big endian, seconds
+//! since the Unix epoch) + signature (64 bytes) + hash of the file (UTF-8).
+//!
+//! Signed: context, id of [`HashAlgorithm`](crate::core::HashAlgorithm), size, original bytes
+//! of the name, nonce of the session, all extensions except the signature (sorted by id: id (u16 big endian)
+//! and data), time and hash. Every field with variable size is prefixed with its size (u64 big endian),
+//! the extensions - with their number.
+//!
+//! The nonce is chosen by [`Recipient`](crate::recipient::Recipient) at the negotiation,
+//! so the signed handshake can't be sent again in other sessions.
+//!
+//! [`Recipient`](crate::recipient::Recipient) checks the signature and the time before
+//! the answer to the handshake. After the data, the hash of the received file must be
+//! the signed one.
+
+use super::{
+    error::ProtocolError,
+    handshake::{get_hash_of_file, Handshake, HandshakeError, EXTENSION_SIGNATURE},
+};
+use crate::{
+    core::{CoreConfig, Signer, SigningKey, VerifyingKey},
+    prelude::{ConfigRecipient, ConfigSender},
+};
+use ed25519_dalek::{Signature, Signer as _, PUBLIC_KEY_LENGTH, SIGNATURE_LENGTH};
+use log::debug;
+use std::{
+    path::Path,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// Context of the signature. Signatures of other messages are not valid for the handshake
+const SIGNATURE_CONTEXT: &[u8] = b"snwf 2023-05-01 signed handshake";
+
+/// Max difference between the time of signing and the time of checking
+///
+/// Files signed long ago are not accepted. Also the clocks of both sides may differ
+const MAX_SIGNATURE_AGE: Duration = Duration::from_secs(10 * 60);
+
+/// Size of the time of signing
+const TIMESTAMP_SIZE: usize = 8;
+
+/// Checked signature of [`Handshake`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct VerifiedSignature {
+    pub(crate) signer: Signer,
+
+    /// Hash of the file. Data must have the same one
+    pub(crate) hash: String,
+}
+
+/// Add `data` prefixed with its size
+fn push_with_size(message: &mut Vec<u8>, data: &[u8]) {
+    message.extend_from_slice(&(data.len() as u64).to_be_bytes());
+    message.extend_from_slice(data);
+}
+
+/// Bytes for signing
+fn get_message(handshake: &Handshake, session_nonce: &[u8], timestamp: u64, hash: &str) -> Vec<u8> {
+    let mut message = SIGNATURE_CONTEXT.to_vec();
+    message.push(handshake.hash_algorithm.to_id());
+    message.extend_from_slice(&handshake.size.to_be_bytes());
+    // Original bytes: the name can't be changed with the extension
+    push_with_size(&mut message, handshake.get_raw_file_name());
+    push_with_size(&mut message, session_nonce);
+
+    // Stable sort: extensions with the same id keep their order
+    let mut extensions: Vec<_> = handshake
+        .extensions
+        .iter()
+        .filter(|extension| extension.id != EXTENSION_SIGNATURE)
+        .collect();
+    extensions.sort_by_key(|extension| extension.id);
+    message.extend_from_slice(&(extensions.len() as u64).to_be_bytes());
+    for extension in extensions {
+        message.extend_from_slice(&extension.id.to_be_bytes());
+        push_with_size(&mut message, &extension.data);
+    }
+
+    message.extend_from_slice(&timestamp.to_be_bytes());
+    push_with_size(&mut message, hash.as_bytes());
+
+    message
+}
+
+/// Hash the file of `handshake` and sign it for the session of `session_nonce`
+///
+/// All other extensions must be already set
+pub(crate) async fn sign_handshake(
+    handshake: &mut Handshake,
+    path: &Path,
+    signing_key: &SigningKey,
+    session_nonce: &[u8],
+) -> Result<(), HandshakeError> {
+    let hash = get_hash_of_file(path, handshake.hash_algorithm).await?;
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let signature = signing_key.sign(&get_message(handshake, session_nonce, timestamp, &hash));
+
+    let mut data =
+        Vec::with_capacity(PUBLIC_KEY_LENGTH + TIMESTAMP_SIZE + SIGNATURE_LENGTH + hash.len());
+    data.extend_from_slice(signing_key.verifying_key().as_bytes());
+    data.extend_from_slice(&timestamp.to_be_bytes());
+    data.extend_from_slice(&signature.to_bytes());
+    data.extend_from_slice(hash.as_bytes());
+    handshake.set_extension(EXTENSION_SIGNATURE, data);
+
+    Ok(())
+}
+
+/// Check the signature of `handshake` for the session of `session_nonce`
+///
+/// If there are `trusted_signers`, the handshake must be signed by one of them.
+/// Returns `None` if the handshake isn't signed and it is allowed
+pub(crate) fn check_signature(
+    handshake: &Handshake,
+    trusted_signers: &[VerifyingKey],
+    session_nonce: &[u8],
+) -> Result<Option<VerifiedSignature>, ProtocolError> {
+    let data = match handshake.get_extension(EXTENSION_SIGNATURE) {
+        Some(data) => data,
+        None if trusted_signers.is_empty() => return Ok(None),
+        None => return Err(ProtocolError::Untrusted("file isn't signed".to_string())),
+    };
+
+    let invalid = || ProtocolError::Untrusted("invalid signature".to_string());
+    if data.len() < PUBLIC_KEY_LENGTH + TIMESTAMP_SIZE + SIGNATURE_LENGTH {
+        return Err(invalid());
+    }
+
+    let (public_key, data) = data.split_at(PUBLIC_KEY_LENGTH);
+    let (timestamp, data) = data.split_at(TIMESTAMP_SIZE);
+    let (signature, hash) = data.split_at(SIGNATURE_LENGTH);
+
+    let public_key =
+        VerifyingKey::from_bytes(public_key.try_into().unwrap()).map_err(|_| invalid())?;
+    if !trusted_signers.is_empty() && !trusted_signers.contains(&public_key) {
+        debug!("unknown signer: {:?}", public_key);
+        return Err(ProtocolError::Untrusted("unknown signer".to_string()));
+    }
+
+    let timestamp = u64::from_be_bytes(timestamp.try_into().unwrap());
+    let hash = String::from_utf8(hash.to_vec()).map_err(|_| invalid())?;
+    let signature = Signature::from_bytes(signature.try_into().unwrap());
+    public_key
+        .verify_strict(
+            &get_message(handshake, session_nonce, timestamp, &hash),
+            &signature,
+        )
+        .map_err(|_| invalid())?;
+
+    let timestamp = UNIX_EPOCH + Duration::from_secs(timestamp);
+    let now = SystemTime::now();
+    let age = now
+        .duration_since(timestamp)
+        .or_else(|_| timestamp.duration_since(now))
+        .unwrap_or_default();
+    if age > MAX_SIGNATURE_AGE {
+        debug!("signature is too old. age: {:?}", age);
+        return Err(ProtocolError::Untrusted("signature is too old".to_string()));
+    }
+
+    Ok(Some(VerifiedSignature {
+        signer: Signer {
+            public_key,
+            timestamp,
+        },
+        hash,
+    }))
+}
+
+/// The received file must have the signed hash
+pub(crate) fn check_signed_hash(
+    signature: &Option<VerifiedSignature>,
+    hash: &str,
+) -> Result<(), ProtocolError> {
+    match signature {
+        Some(signature) if signature.hash != hash => {
+            debug!(
+                "hash isn't signed. hash: {}; signature: {:?}",
+                hash, signature
+            );
+            Err(ProtocolError::FileInvalid)
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Directories, streams and rsync are not signed. Reject them, if there are trusted signers
+#[cfg(feature = "udt")]
+pub(crate) fn check_unsigned_allowed(
+    config: &Option<ConfigRecipient<'_>>,
+    what: &str,
+) -> Result<(), ProtocolError> {
+    match get_trusted_signers(config).is_empty() {
+        true => Ok(()),
+        false => Err(ProtocolError::Untrusted(format!("{} isn't signed", what))),
+    }
+}
+
+/// Trusted signers of `config` or nothing
+pub(crate) fn get_trusted_signers<'a>(
+    config: &'a Option<ConfigRecipient<'_>>,
+) -> &'a [VerifyingKey] {
+    config
+        .as_ref()
+        .map(|config| config.trusted_signers.as_slice())
+        .unwrap_or_default()
+}
+
+/// Nonce of the negotiated session of `config` or nothing
+pub(crate) fn get_session_nonce(config: &Option<impl CoreConfig>) -> &[u8] {
+    config
+        .as_ref()
+        .map(|config| config.get_session_nonce())
+        .unwrap_or_default()
+}
+
+/// Signing key of `config` or nothing
+pub(crate) fn get_signing_key<'a>(config: &'a Option<ConfigSender<'_>>) -> Option<&'a SigningKey> {
+    config
+        .as_ref()
+        .and_then(|config| config.signing_key.as_ref())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        core::{Compression, HashAlgorithm},
+        protocol::handshake::EXTENSION_METADATA,
+    };
+
+    const NONCE: &[u8] = b"nonce of the session";
+
+    #[tokio::test]
+    async fn sign_and_check_handshake() {
+        crate::init_logger_for_test();
+
+        let (_temp_dir, path) = file_hashing::fs::extra::generate_random_file(1000);
+        let signing_key = SigningKey::from_bytes(&[7; 32]);
+        let other_key = SigningKey::from_bytes(&[8; 32]).verifying_key();
+
+        let mut handshake = Handshake {
+            size: 1000,
+            file_name: "file.txt".to_string(),
+            hash_algorithm: HashAlgorithm::Blake3,
+            extensions: Vec::new(),
+        };
+        assert_eq!(check_signature(&handshake, &[], NONCE).unwrap(), None);
+        assert!(check_signature(&handshake, &[other_key], NONCE).is_err());
+
+        sign_handshake(&mut handshake, path.path(), &signing_key, NONCE)
+            .await
+            .unwrap();
+
+        let signature = check_signature(&handshake, &[signing_key.verifying_key()], NONCE)
+            .unwrap()
+            .unwrap();
+        assert_eq!(signature.signer.public_key, signing_key.verifying_key());
+        assert_eq!(
+            signature.hash,
+            get_hash_of_file(path.path(), HashAlgorithm::Blake3)
+                .await
+                .unwrap()
+        );
+        assert!(check_signed_hash(&Some(signature), "other hash").is_err());
+
+        assert!(check_signature(&handshake, &[other_key], NONCE).is_err());
+
+        // Changed name
+        let mut changed = handshake.clone();
+        changed.file_name = "other.txt".to_string();
+        assert!(check_signature(&changed, &[], NONCE).is_err());
+
+        // Other session
+        assert!(check_signature(&handshake, &[], b"other session").is_err());
+
+        // Changed or added extensions
+        let mut changed = handshake.clone();
+        changed.set_compression(Compression::Zstd);
+        assert!(check_signature(&changed, &[], NONCE).is_err());
+
+        let mut changed = handshake.clone();
+        changed.set_extension(EXTENSION_METADATA, vec![0]);
+        assert!(check_signature(&changed, &[], NONCE).is_err());
+    }
+}
@@ -0,0 +1,71 @@
+//! More detailed functions for [`tcp`](crate::protocol::tcp)
+
+use super::TcpError;
+use crate::{
+    common::timeout,
+    prelude::{ConfigRecipient, ConfigSender},
+    protocol::{
+        connection::{TcpConnection, TcpConnectionListener},
+        error::ProtocolError,
+    },
+};
+use log::debug;
+
+/// Make all connections for [`Sender`](crate::sender::Sender)
+pub(crate) async fn all_connect_for_sender(
+    config: &ConfigSender<'_>,
+) -> Result<(TcpConnection, Option<TcpConnection>), TcpError> {
+    debug!("run all_connect_for_sender for tcp. Config: {:?}", config);
+
+    let tcp_connection = timeout!(
+        TcpConnection::connect((config.addr, config.port_for_send_files), config),
+        |_| TcpError::Protocol(ProtocolError::TimeoutExpired),
+        config.timeout
+    )?
+    .map_err(TcpError::Protocol)?;
+    debug!("done socket tcp connect");
+
+    let socket_for_handshake = match config.port_for_handshake {
+        Some(port_for_handshake) => {
+            let socket = timeout!(
+                TcpConnection::connect((config.addr, port_for_handshake), config),
+                |_| TcpError::Protocol(ProtocolError::TimeoutExpired),
+                config.timeout
+            )?
+            .map_err(TcpError::Protocol)?;
+            debug!("done socket handshake connect");
+
+            Some(socket)
+        }
+        None => None,
+    };
+
+    Ok((tcp_connection, socket_for_handshake))
+}
+
+/// Make bind connections for [`Recipient`](crate::recipient::Recipient)
+pub(crate) async fn all_bind_for_recipient(
+    config: &ConfigRecipient<'_>,
+) -> Result<(TcpConnectionListener, Option<TcpConnectionListener>), TcpError> {
+    debug!("run all_bind_for_recipient for tcp. Config: {:?}", config);
+
+    let tcp_listener =
+        TcpConnectionListener::bind((config.addr, config.port_for_send_files), config)
+            .await
+            .map_err(TcpError::Protocol)?;
+    debug!("done socket tcp bind");
+
+    let tcp_handshake = match config.port_for_handshake {
+        Some(port_for_handshake) => {
+            let listener = TcpConnectionListener::bind((config.addr, port_for_handshake), config)
+                .await
+                .map_err(TcpError::Protocol)?;
+            debug!("done socket handshake bind");
+
+            Some(listener)
+        }
+        None => None,
+    };
+
+    Ok((tcp_listener, tcp_handshake))
+}
@@ -0,0 +1,49 @@
+//! All error in [`tcp`](crate::protocol::tcp)
+
+use crate::protocol::error::ProtocolError;
+use thiserror::Error;
+
+/// Enum error
+#[derive(Debug, Error)]
+pub enum TcpError {
+    #[error("problem in protocol: {0}")]
+    Protocol(ProtocolError),
+
+    /// Wrong use function in [tcp](crate::protocol::tcp)
+    #[error("wrong use function: {0}")]
+    Assert(String),
+}
+
+/// [`std::assert`], but for [`TcpError`]
+///
+/// # Example
+///
+/// See unit tests
+macro_rules! assert_tcp {
+    ($for_check:expr, $($message_error:tt)*) => {
+        if $for_check == false {
+            log::error!("assert tcp! message_error: {}", format!($($message_error)*));
+            return Err(crate::protocol::tcp::TcpError::Assert(format!($($message_error)*)));
+        }
+    };
+}
+
+pub(crate) use assert_tcp;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn macro_assert_tcp() {
+        let fn_test = || -> Result<(), TcpError> {
+            assert_tcp!(false, "test message: {}", "test value");
+            Ok(())
+        };
+
+        match fn_test().err().unwrap() {
+            TcpError::Assert(message) => assert_eq!(message, "test message: test value"),
+            _ => panic!("fn_test() != TcpError::Assert"),
+        }
+    }
+}
@@ -0,0 +1,212 @@
+//! [TCP](https://en.wikipedia.org/wiki/Transmission_Control_Protocol) implementation
+//!
+//! Use it if [UDP](https://en.wikipedia.org/wiki/User_Datagram_Protocol) is filtered
+//! and [`udt`](crate::protocol::udt) does not work
+//!
+//! # Example
+//!
+//! ```no_run
+//! # use snwf::prelude::*;
+//! # use std::path::Path;
+//! #
+//! #[tokio::main]
+//! async fn main() {
+//!    let mut sender = Sender::new("127.0.0.1".parse().unwrap(), 4324, 6343);
+//!    let mut recipient = Recipient::new("::0".parse().unwrap(), 4324, 6343);
+//!
+//!    sender.set_progress_fn(
+//!        Some(move |progressing| println!("progress info: {:?}", progressing)
+//!    ));
+//!    
+//!    let (recv, send) = tokio::join!(
+//!        recipient.tcp_recv_file(Path::new("other_file.txt")),
+//!        sender.tcp_send_file(Path::new("file_for_send.txt"))
+//!    );
+//!    
+//!    send.unwrap();
+//!    recv.unwrap();
+//! }
+//! ```
+//!
+//! # How it works?
+//!
+//! 1. We agree with the recipient on capabilities
+//!    (see [`NegotiatedCapabilities`](crate::core::NegotiatedCapabilities))
+//! 2. We send a handshake that contains the
+//!    name of the original file and the file size
+//! 3. Send the file over the tcp connection
+//! 4. Send the checksum, computed while sending the file
+//!
+//! And so for **EVERY** file
+
+mod detail;
+pub mod error;
+
+pub mod tcp_recipient;
+pub mod tcp_sender;
+
+pub use error::TcpError;
+pub use tcp_recipient::TcpRecipient;
+pub use tcp_sender::TcpSender;
+
+#[cfg(test)]
+mod tests {
+    use crate::{common::get_hasher, core::*, prelude::*};
+    use log::debug;
+    use std::sync::{Arc, Mutex};
+
+    #[tokio::test]
+    async fn send_and_recv_tcp_with_progress_fn() {
+        crate::init_logger_for_test();
+
+        let run_progressing_sender = Arc::new(Mutex::new(false));
+        let run_progressing_recipient = Arc::new(Mutex::new(false));
+
+        let (temp_dir, path_input) = file_hashing::fs::extra::generate_random_file(4352);
+        let path_output = temp_dir.join("tess_file.txt");
+
+        let mut sender = Sender::new("127.0.0.1".parse().unwrap(), 4254, 6273);
+        let mut recipient = Recipient::new("127.0.0.1".parse().unwrap(), 4254, 6273);
+
+        {
+            let run_progressing_sender_clone = run_progressing_sender.clone();
+            sender.set_progress_fn(Some(move |progressing| {
+                debug!("progressing sender: {:?}", progressing);
+
+                if let Progressing::Done = progressing {
+                    *run_progressing_sender_clone.lock().unwrap() = true;
+                }
+            }));
+        }
+
+        {
+            let run_progressing_recipient_clone = run_progressing_recipient.clone();
+            recipient.set_progress_fn(Some(move |progressing| {
+                debug!("progressing recipient: {:?}", progressing);
+
+                if let Progressing::Done = progressing {
+                    *run_progressing_recipient_clone.lock().unwrap() = true;
+                }
+            }));
+        }
+
+        let (recv, send) = tokio::join!(
+            recipient.tcp_recv_file(path_output.as_path()),
+            sender.tcp_send_file(path_input.path())
+        );
+
+        send.unwrap();
+        recv.unwrap();
+
+        let hash_input = file_hashing::get_hash_file(path_input, &mut get_hasher()).unwrap();
+        let hash_output = file_hashing::get_hash_file(path_output, &mut get_hasher()).unwrap();
+
+        assert_eq!(hash_input, hash_output);
+        assert!(*run_progressing_sender.lock().unwrap());
+        assert!(*run_progressing_recipient.lock().unwrap());
+    }
+
+    #[tokio::test]
+    async fn send_and_recv_tcp_with_original_name() {
+        crate::init_logger_for_test();
+
+        let (_temp_dir, path_input) = file_hashing::fs::extra::generate_random_file(4352);
+        let (output_dir, _path_input) = file_hashing::fs::extra::generate_random_file(1);
+
+        let mut sender = Sender::new("127.0.0.1".parse().unwrap(), 3154, 5173);
+        let mut recipient = Recipient::new("127.0.0.1".parse().unwrap(), 3154, 5173);
+
+        let (recv, send) = tokio::join!(
+            recipient.tcp_recv_file_with_original_file_name(output_dir.path()),
+            sender.tcp_send_file(path_input.path())
+        );
+
+        send.unwrap();
+        recv.unwrap();
+
+        let hash_input = file_hashing::get_hash_file(&path_input, &mut get_hasher()).unwrap();
+        let hash_output = file_hashing::get_hash_file(
+            output_dir.join(path_input.file_name().unwrap()),
+            &mut get_hasher(),
+        )
+        .unwrap();
+
+        assert_eq!(hash_input, hash_output);
+    }
+
+    #[tokio::test]
+    async fn send_and_recv_tcp_single_port() {
+        crate::init_logger_for_test();
+
+        let (temp_dir, path_input) = file_hashing::fs::extra::generate_random_file(4352);
+        let path_output = temp_dir.join("tess_file.txt");
+
+        let mut sender = Sender::new_single_port("127.0.0.1".parse().unwrap(), 3185);
+        let mut recipient = Recipient::new_single_port("127.0.0.1".parse().unwrap(), 3185);
+
+        let (recv, send) = tokio::join!(
+            recipient.tcp_recv_file(path_output.as_path()),
+            sender.tcp_send_file(path_input.path())
+        );
+
+        send.unwrap();
+        recv.unwrap();
+
+        let hash_input = file_hashing::get_hash_file(path_input, &mut get_hasher()).unwrap();
+        let hash_output = file_hashing::get_hash_file(path_output, &mut get_hasher()).unwrap();
+
+        assert_eq!(hash_input, hash_output);
+    }
+
+    #[tokio::test]
+    async fn send_and_recv_tcp_with_resume() {
+        crate::init_logger_for_test();
+
+        let (temp_dir, path_input) = file_hashing::fs::extra::generate_random_file(4352);
+        let path_output = temp_dir.join("tess_file.txt");
+
+        let data = std::fs::read(path_input.path()).unwrap();
+        std::fs::write(&path_output, &data[..1000]).unwrap();
+
+        let mut sender = Sender::new_single_port("127.0.0.1".parse().unwrap(), 3204);
+        let mut recipient = Recipient::new_single_port("127.0.0.1".parse().unwrap(), 3204);
+
+        let (recv, send) = tokio::join!(
+            recipient.tcp_recv_file_with_resume(path_output.as_path()),
+            sender.tcp_send_file(path_input.path())
+        );
+
+        send.unwrap();
+        recv.unwrap();
+
+        let hash_input = file_hashing::get_hash_file(path_input, &mut get_hasher()).unwrap();
+        let hash_output = file_hashing::get_hash_file(path_output, &mut get_hasher()).unwrap();
+
+        assert_eq!(hash_input, hash_output);
+    }
+
+    #[tokio::test]
+    async fn send_and_recv_tcp_with_resume_from_other_file() {
+        crate::init_logger_for_test();
+
+        let (temp_dir, path_input) = file_hashing::fs::extra::generate_random_file(4352);
+        let path_output = temp_dir.join("tess_file.txt");
+        std::fs::write(&path_output, vec![0u8; 1000]).unwrap();
+
+        let mut sender = Sender::new_single_port("127.0.0.1".parse().unwrap(), 3205);
+        let mut recipient = Recipient::new_single_port("127.0.0.1".parse().unwrap(), 3205);
+
+        let (recv, send) = tokio::join!(
+            recipient.tcp_recv_file_with_resume(path_output.as_path()),
+            sender.tcp_send_file(path_input.path())
+        );
+
+        send.unwrap();
+        recv.unwrap();
+
+        let hash_input = file_hashing::get_hash_file(path_input, &mut get_hasher()).unwrap();
+        let hash_output = file_hashing::get_hash_file(path_output, &mut get_hasher()).unwrap();
+
+        assert_eq!(hash_input, hash_output);
+    }
+}
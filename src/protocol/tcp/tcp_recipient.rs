@@ -0,0 +1,250 @@
+//! Implementation [tcp](https://en.wikipedia.org/wiki/Transmission_Control_Protocol) for trait [`CoreRecipient`]
+
+use super::TcpError;
+use crate::{
+    common::timeout,
+    prelude::*,
+    protocol::{
+        error::ProtocolError,
+        handshake::recv_handshake_from,
+        raw,
+        tcp::{detail, error::assert_tcp},
+    },
+};
+use async_trait::async_trait;
+use log::debug;
+use std::path::Path;
+
+/// [TCP](https://en.wikipedia.org/wiki/Transmission_Control_Protocol) trait for [`CoreRecipient`]
+#[async_trait(?Send)]
+pub trait TcpRecipient<'a>: CoreRecipient<'a> {
+    /// Receive a file via [tcp](https://en.wikipedia.org/wiki/Transmission_Control_Protocol) protocol
+    ///
+    /// # Arguments
+    ///
+    /// * `output` - path to save file.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use snwf::prelude::*;
+    /// # use std::path::Path;
+    /// #
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut recipient = Recipient::new("::0".parse().unwrap(), 4324, 6343);
+    ///
+    ///     recipient.tcp_recv_file(Path::new("file.txt"));
+    /// }
+    /// ```
+    ///
+    /// **Warning:** not save original file name! If we want save it,
+    /// use [`TcpRecipient::tcp_recv_file_with_original_file_name`]
+    async fn tcp_recv_file<P>(&mut self, output: P) -> Result<(), TcpError>
+    where
+        P: AsRef<Path> + Send + Copy + Sync;
+
+    /// Receive a file via [tcp](https://en.wikipedia.org/wiki/Transmission_Control_Protocol) protocol
+    ///
+    /// **But save original name** (not save [`TcpRecipient::tcp_recv_file`])
+    ///
+    /// Unsafe names (`../x`, `/etc/x` and etc.) are handled by
+    /// [`FileNamePolicy`](crate::protocol::handshake::FileNamePolicy), names that are
+    /// not UTF-8 - by [`FileNameEncoding`](crate::protocol::handshake::FileNameEncoding)
+    ///
+    /// # Arguments
+    ///
+    /// * `output` - path to save file.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use snwf::prelude::*;
+    /// # use std::path::Path;
+    /// #
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut recipient = Recipient::new("::0".parse().unwrap(), 4324, 6343);
+    ///
+    ///     recipient.tcp_recv_file_with_original_file_name(Path::new("/home/gladi/Downloads"));
+    /// }
+    /// ```
+    async fn tcp_recv_file_with_original_file_name<P>(&mut self, output: P) -> Result<(), TcpError>
+    where
+        P: AsRef<Path> + Send + Copy + Sync;
+
+    /// Receive a file via [tcp](https://en.wikipedia.org/wiki/Transmission_Control_Protocol) protocol, **resuming an interrupted transfer**
+    ///
+    /// If `output` already has the beginning of the file, only the rest of it is received.
+    /// Otherwise, it works as [`TcpRecipient::tcp_recv_file`]
+    ///
+    /// # Arguments
+    ///
+    /// * `output` - path to save file. May exist.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use snwf::prelude::*;
+    /// # use std::path::Path;
+    /// #
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut recipient = Recipient::new("::0".parse().unwrap(), 4324, 6343);
+    ///
+    ///     recipient.tcp_recv_file_with_resume(Path::new("file.txt"));
+    /// }
+    /// ```
+    async fn tcp_recv_file_with_resume<P>(&mut self, output: P) -> Result<(), TcpError>
+    where
+        P: AsRef<Path> + Send + Copy + Sync;
+}
+
+#[async_trait(?Send)]
+impl<'a> TcpRecipient<'a> for Recipient<'a> {
+    async fn tcp_recv_file<P>(&mut self, output: P) -> Result<(), TcpError>
+    where
+        P: AsRef<Path> + Send + Copy + Sync,
+    {
+        assert_tcp!(
+            !output.as_ref().exists(),
+            "output must be no exists. output path: {}",
+            output.as_ref().display()
+        );
+
+        let mut config = self.get_config();
+        debug!("running tcp_recv_file; config: {:?}", config);
+
+        let (tcp_listener, mut tcp_handshake) = detail::all_bind_for_recipient(&config).await?;
+
+        let (mut connection, addr) = timeout!(
+            tcp_listener.accept(),
+            |_| TcpError::Protocol(ProtocolError::TimeoutExpired),
+            config.timeout
+        )?
+        .map_err(|e| TcpError::Protocol(ProtocolError::Accept(e)))?;
+        debug!("accepted connection from {}", addr);
+
+        let (negotiated, mut connection) =
+            raw::negotiate_for_recipient(&mut connection, &mut config)
+                .await
+                .map_err(TcpError::Protocol)?;
+        self.negotiated_capabilities = Some(negotiated);
+
+        let handshake = recv_handshake_from(&mut connection, tcp_handshake.as_mut())
+            .await
+            .map_err(|e| TcpError::Protocol(ProtocolError::Handshake(e)))?;
+
+        let (_, signer) = raw::recv_file(
+            &mut connection,
+            output,
+            &Some(config),
+            0,
+            handshake,
+            false,
+            addr,
+        )
+        .await
+        .map_err(TcpError::Protocol)?;
+        self.signer = signer;
+
+        Ok(())
+    }
+
+    async fn tcp_recv_file_with_original_file_name<P>(&mut self, output: P) -> Result<(), TcpError>
+    where
+        P: AsRef<Path> + Send + Copy + Sync,
+    {
+        assert_tcp!(output.as_ref().is_dir(), "output must be a folder path");
+
+        let mut config = self.get_config();
+        debug!("running tcp_recv_file; config: {:?}", config);
+
+        let (tcp_listener, mut tcp_handshake) = detail::all_bind_for_recipient(&config).await?;
+
+        let (mut connection, addr) = timeout!(
+            tcp_listener.accept(),
+            |_| TcpError::Protocol(ProtocolError::TimeoutExpired),
+            config.timeout
+        )?
+        .map_err(|e| TcpError::Protocol(ProtocolError::Accept(e)))?;
+        debug!("accepted connection from {}", addr);
+
+        let (negotiated, mut connection) =
+            raw::negotiate_for_recipient(&mut connection, &mut config)
+                .await
+                .map_err(TcpError::Protocol)?;
+        self.negotiated_capabilities = Some(negotiated);
+
+        let handshake = recv_handshake_from(&mut connection, tcp_handshake.as_mut())
+            .await
+            .map_err(|e| TcpError::Protocol(ProtocolError::Handshake(e)))?;
+
+        let path = raw::get_path_for_file(
+            &mut connection,
+            output.as_ref(),
+            &handshake,
+            config.file_name_policy,
+            config.file_name_encoding,
+        )
+        .await
+        .map_err(TcpError::Protocol)?;
+
+        let (_, signer) = raw::recv_file(
+            &mut connection,
+            path.as_path(),
+            &Some(config),
+            0,
+            handshake,
+            false,
+            addr,
+        )
+        .await
+        .map_err(TcpError::Protocol)?;
+        self.signer = signer;
+
+        Ok(())
+    }
+
+    async fn tcp_recv_file_with_resume<P>(&mut self, output: P) -> Result<(), TcpError>
+    where
+        P: AsRef<Path> + Send + Copy + Sync,
+    {
+        assert_tcp!(!output.as_ref().is_dir(), "output must be a file path");
+
+        let mut config = self.get_config();
+        debug!("running tcp_recv_file_with_resume; config: {:?}", config);
+
+        let (tcp_listener, mut tcp_handshake) = detail::all_bind_for_recipient(&config).await?;
+
+        let (mut connection, addr) = timeout!(
+            tcp_listener.accept(),
+            |_| TcpError::Protocol(ProtocolError::TimeoutExpired),
+            config.timeout
+        )?
+        .map_err(|e| TcpError::Protocol(ProtocolError::Accept(e)))?;
+        debug!("accepted connection from {}", addr);
+
+        let (negotiated, mut connection) =
+            raw::negotiate_for_recipient(&mut connection, &mut config)
+                .await
+                .map_err(TcpError::Protocol)?;
+        self.negotiated_capabilities = Some(negotiated);
+
+        let handshake = recv_handshake_from(&mut connection, tcp_handshake.as_mut())
+            .await
+            .map_err(|e| TcpError::Protocol(ProtocolError::Handshake(e)))?;
+
+        let (_, signer) = raw::recv_file(
+            &mut connection,
+            output,
+            &Some(config),
+            0,
+            handshake,
+            true,
+            addr,
+        )
+        .await
+        .map_err(TcpError::Protocol)?;
+        self.signer = signer;
+
+        Ok(())
+    }
+}
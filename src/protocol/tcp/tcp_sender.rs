@@ -0,0 +1,68 @@
+//! Implementation [tcp](https://en.wikipedia.org/wiki/Transmission_Control_Protocol) for trait [`CoreSender`]
+
+use super::TcpError;
+use crate::{
+    prelude::*,
+    protocol::{
+        raw,
+        tcp::{detail, error::assert_tcp},
+    },
+};
+use async_trait::async_trait;
+use log::debug;
+use std::fmt::Debug;
+use std::path::Path;
+
+/// [TCP](https://en.wikipedia.org/wiki/Transmission_Control_Protocol) trait for [`CoreSender`]
+#[async_trait(?Send)]
+pub trait TcpSender<'a>: CoreSender<'a> {
+    /// Send file via [tcp](https://en.wikipedia.org/wiki/Transmission_Control_Protocol) protocol
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use snwf::prelude::*;
+    /// # use std::path::Path;
+    /// #
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut sender = Sender::new("127.0.0.1".parse().unwrap(), 4324, 6343);
+    ///
+    ///     sender.tcp_send_file(Path::new("file.txt"));
+    /// }
+    async fn tcp_send_file<P>(&mut self, path: P) -> Result<(), TcpError>
+    where
+        P: AsRef<Path> + Send + Copy + Sync + Debug;
+}
+
+#[async_trait(?Send)]
+impl<'a> TcpSender<'a> for Sender<'a> {
+    async fn tcp_send_file<P>(&mut self, path: P) -> Result<(), TcpError>
+    where
+        P: AsRef<Path> + Send + Copy + Sync + Debug,
+    {
+        assert_tcp!(path.as_ref().is_file(), "path isn't file or not exists");
+        let mut config = self.get_config();
+
+        debug!(
+            "running tcp_send_file; config: {:?}; path: {:?}",
+            config, path
+        );
+
+        let (mut tcp, mut socket_for_handshake) = detail::all_connect_for_sender(&config).await?;
+        let (negotiated, mut tcp) = raw::negotiate_for_sender(&mut tcp, &mut config)
+            .await
+            .map_err(TcpError::Protocol)?;
+        self.negotiated_capabilities = Some(negotiated);
+        raw::send_file(
+            &mut tcp,
+            path,
+            socket_for_handshake.as_mut(),
+            &Some(config),
+            0,
+        )
+        .await
+        .map_err(TcpError::Protocol)?;
+
+        Ok(())
+    }
+}
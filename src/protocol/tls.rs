@@ -0,0 +1,463 @@
+//! [TLS](https://en.wikipedia.org/wiki/Transport_Layer_Security) for TCP connections
+//!
+//! Protects the handshake connection of [`udt`](crate::protocol::udt) and
+//! [`rsync`](crate::protocol::rsync) and both connections of [`tcp`](crate::protocol::tcp).
+//!
+//! [`udt`](crate::protocol::udt) and [`rsync`](crate::protocol::rsync) need the port for
+//! handshakes: with TLS settings, single-port configs and servers return an error.
+//! The TLS handshake is finished before the negotiation, so the sender learns
+//! that its certificate is not trusted from the refused negotiation.
+//!
+//! The side that listens ([`Recipient`](crate::recipient::Recipient)) is the TLS server:
+//! TLS is used if it has a certificate. The side that connects is the TLS client:
+//! TLS is used if it trusts some certificates or fingerprints.
+//!
+//! The server certificate is checked by the pinned fingerprints (SHA-256 of the certificate),
+//! if there are any. Else, by the trusted certificates and the server name.
+//!
+//! For mutual TLS, the client also sets its certificate, and the server trusts
+//! it in the same way. Then only the trusted clients can connect.
+//!
+//! # Example
+//!
+//! ```no_run
+//! # use snwf::prelude::*;
+//! # use snwf::protocol::tls::{get_fingerprint, Certificate, PrivateKey};
+//! # use std::path::Path;
+//! #
+//! #[tokio::main]
+//! async fn main() {
+//!    let certificate = Certificate(std::fs::read("cert.der").unwrap());
+//!    let private_key = PrivateKey(std::fs::read("key.der").unwrap());
+//!
+//!    let mut sender = Sender::new("127.0.0.1".parse().unwrap(), 4324, 6343);
+//!    let mut recipient = Recipient::new("::0".parse().unwrap(), 4324, 6343);
+//!
+//!    sender.add_tls_pinned_fingerprint(get_fingerprint(&certificate));
+//!    recipient.set_tls_certificate(vec![certificate], private_key);
+//!
+//!    let (recv, send) = tokio::join!(
+//!        recipient.udt_recv_file(Path::new("other_file.txt")),
+//!        sender.udt_send_file(Path::new("file_for_send.txt"))
+//!    );
+//!
+//!    send.unwrap();
+//!    recv.unwrap();
+//! }
+//! ```
+//!
+//! # What libraries to use
+//!
+//! * [`rustls`](https://github.com/rustls/rustls) - TLS
+//! * [`tokio-rustls`](https://github.com/rustls/tokio-rustls) - TLS for [tokio](https://tokio.rs/)
+
+use crate::{
+    prelude::{CoreRecipient, CoreSender, Recipient, Sender},
+    protocol::error::ProtocolError,
+};
+use rustls::{
+    client::{ServerCertVerified, ServerCertVerifier, WebPkiVerifier},
+    server::{AllowAnyAuthenticatedClient, ClientCertVerified, ClientCertVerifier},
+    CertificateError, ClientConfig, DistinguishedName, RootCertStore, ServerConfig, ServerName,
+};
+use sha2::{Digest, Sha256};
+use std::{sync::Arc, time::SystemTime};
+use tokio_rustls::{TlsAcceptor, TlsConnector};
+
+pub use rustls::{Certificate, PrivateKey};
+
+/// SHA-256 of the certificate (DER)
+pub type Fingerprint = [u8; 32];
+
+/// TLS settings of one side. Change it with [`TlsSender`] and [`TlsRecipient`]
+#[derive(Clone)]
+pub struct TlsConfig {
+    /// Certificate chain and private key of this side
+    pub(crate) certificate: Option<(Vec<Certificate>, PrivateKey)>,
+
+    /// Certificates of the other side that this side trusts
+    pub(crate) trusted_certificates: Vec<Certificate>,
+
+    /// Fingerprints of certificates of the other side that this side trusts
+    pub(crate) pinned_fingerprints: Vec<Fingerprint>,
+
+    /// Name for checking the certificate of the server
+    pub(crate) server_name: String,
+}
+
+impl Default for TlsConfig {
+    fn default() -> Self {
+        Self {
+            certificate: None,
+            trusted_certificates: Vec::new(),
+            pinned_fingerprints: Vec::new(),
+            server_name: "localhost".to_string(),
+        }
+    }
+}
+
+impl TlsConfig {
+    /// Is anything set? Then TLS must be used
+    pub(crate) fn is_set(&self) -> bool {
+        self.certificate.is_some()
+            || !self.trusted_certificates.is_empty()
+            || !self.pinned_fingerprints.is_empty()
+    }
+}
+
+/// Get [`Fingerprint`] of the certificate for pinning it
+pub fn get_fingerprint(certificate: &Certificate) -> Fingerprint {
+    Sha256::digest(&certificate.0).into()
+}
+
+/// Checks only the fingerprint of the certificate of the other side
+struct PinnedCertificateVerifier {
+    pinned_fingerprints: Vec<Fingerprint>,
+}
+
+impl PinnedCertificateVerifier {
+    fn verify(&self, end_entity: &Certificate) -> Result<(), rustls::Error> {
+        match self
+            .pinned_fingerprints
+            .contains(&get_fingerprint(end_entity))
+        {
+            true => Ok(()),
+            false => Err(rustls::Error::InvalidCertificate(
+                CertificateError::ApplicationVerificationFailure,
+            )),
+        }
+    }
+}
+
+impl ServerCertVerifier for PinnedCertificateVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &Certificate,
+        _intermediates: &[Certificate],
+        _server_name: &ServerName,
+        _scts: &mut dyn Iterator<Item = &[u8]>,
+        _ocsp_response: &[u8],
+        _now: SystemTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        self.verify(end_entity)?;
+        Ok(ServerCertVerified::assertion())
+    }
+}
+
+impl ClientCertVerifier for PinnedCertificateVerifier {
+    fn client_auth_root_subjects(&self) -> &[DistinguishedName] {
+        &[]
+    }
+
+    fn verify_client_cert(
+        &self,
+        end_entity: &Certificate,
+        _intermediates: &[Certificate],
+        _now: SystemTime,
+    ) -> Result<ClientCertVerified, rustls::Error> {
+        self.verify(end_entity)?;
+        Ok(ClientCertVerified::assertion())
+    }
+}
+
+fn get_root_store(config: &TlsConfig) -> Result<RootCertStore, ProtocolError> {
+    let mut roots = RootCertStore::empty();
+    for certificate in config.trusted_certificates.iter() {
+        roots.add(certificate).map_err(ProtocolError::Tls)?;
+    }
+
+    Ok(roots)
+}
+
+/// [`TlsConnector`] for the side that connects. `None`, if it doesn't trust anything
+pub(crate) fn get_connector(
+    config: &TlsConfig,
+) -> Result<Option<(TlsConnector, ServerName)>, ProtocolError> {
+    if config.trusted_certificates.is_empty() && config.pinned_fingerprints.is_empty() {
+        return Ok(None);
+    }
+
+    let verifier: Arc<dyn ServerCertVerifier> = match config.pinned_fingerprints.is_empty() {
+        true => Arc::new(WebPkiVerifier::new(get_root_store(config)?, None)),
+        false => Arc::new(PinnedCertificateVerifier {
+            pinned_fingerprints: config.pinned_fingerprints.clone(),
+        }),
+    };
+    let builder = ClientConfig::builder()
+        .with_safe_defaults()
+        .with_custom_certificate_verifier(verifier);
+    let client_config = match config.certificate.clone() {
+        Some((cert_chain, private_key)) => builder
+            .with_client_auth_cert(cert_chain, private_key)
+            .map_err(ProtocolError::Tls)?,
+        None => builder.with_no_client_auth(),
+    };
+
+    let server_name = ServerName::try_from(config.server_name.as_str()).map_err(|_| {
+        ProtocolError::Tls(rustls::Error::General(format!(
+            "invalid server name: {}",
+            config.server_name
+        )))
+    })?;
+
+    Ok(Some((
+        TlsConnector::from(Arc::new(client_config)),
+        server_name,
+    )))
+}
+
+/// [`TlsAcceptor`] for the side that listens. `None`, if it has no certificate
+pub(crate) fn get_acceptor(config: &TlsConfig) -> Result<Option<TlsAcceptor>, ProtocolError> {
+    let (cert_chain, private_key) = match config.certificate.clone() {
+        Some(certificate) => certificate,
+        None => return Ok(None),
+    };
+
+    let builder = ServerConfig::builder().with_safe_defaults();
+    let builder = match (
+        config.pinned_fingerprints.is_empty(),
+        config.trusted_certificates.is_empty(),
+    ) {
+        (false, _) => builder.with_client_cert_verifier(Arc::new(PinnedCertificateVerifier {
+            pinned_fingerprints: config.pinned_fingerprints.clone(),
+        })),
+        (true, false) => builder.with_client_cert_verifier(
+            AllowAnyAuthenticatedClient::new(get_root_store(config)?).boxed(),
+        ),
+        (true, true) => builder.with_no_client_auth(),
+    };
+    let server_config = builder
+        .with_single_cert(cert_chain, private_key)
+        .map_err(ProtocolError::Tls)?;
+
+    Ok(Some(TlsAcceptor::from(Arc::new(server_config))))
+}
+
+/// TLS trait for [`CoreSender`]
+pub trait TlsSender<'a>: CoreSender<'a> {
+    /// Trust the certificate of [`Recipient`](crate::recipient::Recipient)
+    /// or of its certificate authority
+    fn add_tls_trusted_certificate(&mut self, certificate: Certificate);
+
+    /// Trust only the certificate of [`Recipient`](crate::recipient::Recipient)
+    /// with this fingerprint. Trusted certificates and the server name are not checked then
+    ///
+    /// See [`get_fingerprint`]
+    fn add_tls_pinned_fingerprint(&mut self, fingerprint: Fingerprint);
+
+    /// Set name for checking the certificate of [`Recipient`](crate::recipient::Recipient).
+    /// Default: `localhost`
+    fn set_tls_server_name(&mut self, server_name: &str);
+
+    /// Set certificate of [`Sender`](crate::sender::Sender) for mutual TLS
+    fn set_tls_certificate(&mut self, cert_chain: Vec<Certificate>, private_key: PrivateKey);
+}
+
+/// TLS trait for [`CoreRecipient`]
+pub trait TlsRecipient<'a>: CoreRecipient<'a> {
+    /// Set certificate for TLS. TLS is used only with it
+    ///
+    /// # Arguments
+    ///
+    /// * `cert_chain` - certificate chain. The first is the certificate of [`Recipient`]
+    /// * `private_key` - private key of the certificate
+    fn set_tls_certificate(&mut self, cert_chain: Vec<Certificate>, private_key: PrivateKey);
+
+    /// Require mutual TLS: trust the certificate of [`Sender`] or of its certificate authority
+    fn add_tls_trusted_certificate(&mut self, certificate: Certificate);
+
+    /// Require mutual TLS: trust only the certificate of [`Sender`] with this fingerprint
+    ///
+    /// See [`get_fingerprint`]
+    fn add_tls_pinned_fingerprint(&mut self, fingerprint: Fingerprint);
+
+    /// Set name for checking the certificate of the other side, if [`Recipient`] connects to it.
+    /// Default: `localhost`
+    fn set_tls_server_name(&mut self, server_name: &str);
+}
+
+impl<'a> TlsSender<'a> for Sender<'a> {
+    fn add_tls_trusted_certificate(&mut self, certificate: Certificate) {
+        self.config.tls.trusted_certificates.push(certificate);
+    }
+
+    fn add_tls_pinned_fingerprint(&mut self, fingerprint: Fingerprint) {
+        self.config.tls.pinned_fingerprints.push(fingerprint);
+    }
+
+    fn set_tls_server_name(&mut self, server_name: &str) {
+        self.config.tls.server_name = server_name.to_string();
+    }
+
+    fn set_tls_certificate(&mut self, cert_chain: Vec<Certificate>, private_key: PrivateKey) {
+        self.config.tls.certificate = Some((cert_chain, private_key));
+    }
+}
+
+impl<'a> TlsRecipient<'a> for Recipient<'a> {
+    fn set_tls_certificate(&mut self, cert_chain: Vec<Certificate>, private_key: PrivateKey) {
+        self.config.tls.certificate = Some((cert_chain, private_key));
+    }
+
+    fn add_tls_trusted_certificate(&mut self, certificate: Certificate) {
+        self.config.tls.trusted_certificates.push(certificate);
+    }
+
+    fn add_tls_pinned_fingerprint(&mut self, fingerprint: Fingerprint) {
+        self.config.tls.pinned_fingerprints.push(fingerprint);
+    }
+
+    fn set_tls_server_name(&mut self, server_name: &str) {
+        self.config.tls.server_name = server_name.to_string();
+    }
+}
+
+#[cfg(all(test, feature = "udt", feature = "tcp"))]
+mod tests {
+    use super::*;
+    use crate::{prelude::*, protocol::udt::UdtError};
+
+    fn generate_certificate() -> (Certificate, PrivateKey) {
+        let certificate = rcgen::generate_simple_self_signed(vec!["localhost".into()]).unwrap();
+
+        (
+            Certificate(certificate.serialize_der().unwrap()),
+            PrivateKey(certificate.serialize_private_key_der()),
+        )
+    }
+
+    #[tokio::test]
+    async fn send_and_recv_udt_with_pinned_fingerprint() {
+        crate::init_logger_for_test();
+
+        let (temp_dir, path_input) = file_hashing::fs::extra::generate_random_file(4352);
+        let path_output = temp_dir.join("output.txt");
+        let (certificate, private_key) = generate_certificate();
+
+        let mut sender = Sender::new("127.0.0.1".parse().unwrap(), 3344, 5363);
+        let mut recipient = Recipient::new("::0".parse().unwrap(), 3344, 5363);
+        sender.add_tls_pinned_fingerprint(get_fingerprint(&certificate));
+        recipient.set_tls_certificate(vec![certificate], private_key);
+
+        let (recv, send) = tokio::join!(
+            recipient.udt_recv_file(path_output.as_path()),
+            sender.udt_send_file(path_input.path())
+        );
+
+        send.unwrap();
+        recv.unwrap();
+        assert_eq!(
+            std::fs::read(path_input.path()).unwrap(),
+            std::fs::read(&path_output).unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn send_and_recv_tcp_with_trusted_certificate() {
+        crate::init_logger_for_test();
+
+        let (temp_dir, path_input) = file_hashing::fs::extra::generate_random_file(43526);
+        let path_output = temp_dir.join("output.txt");
+        let (certificate, private_key) = generate_certificate();
+
+        let mut sender = Sender::new("127.0.0.1".parse().unwrap(), 3345, 5364);
+        let mut recipient = Recipient::new("127.0.0.1".parse().unwrap(), 3345, 5364);
+        sender.add_tls_trusted_certificate(certificate.clone());
+        recipient.set_tls_certificate(vec![certificate], private_key);
+
+        let (recv, send) = tokio::join!(
+            recipient.tcp_recv_file(path_output.as_path()),
+            sender.tcp_send_file(path_input.path())
+        );
+
+        send.unwrap();
+        recv.unwrap();
+        assert_eq!(
+            std::fs::read(path_input.path()).unwrap(),
+            std::fs::read(&path_output).unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn send_udt_with_other_certificate() {
+        crate::init_logger_for_test();
+
+        let (temp_dir, path_input) = file_hashing::fs::extra::generate_random_file(4352);
+        let path_output = temp_dir.join("output.txt");
+        let (certificate, private_key) = generate_certificate();
+        let (other_certificate, _) = generate_certificate();
+
+        let mut sender = Sender::new("127.0.0.1".parse().unwrap(), 3346, 5365);
+        let mut recipient = Recipient::new("::0".parse().unwrap(), 3346, 5365);
+        sender.add_tls_pinned_fingerprint(get_fingerprint(&other_certificate));
+        recipient.set_tls_certificate(vec![certificate], private_key);
+
+        let (recv, send) = tokio::join!(
+            recipient.udt_recv_file(path_output.as_path()),
+            sender.udt_send_file(path_input.path())
+        );
+
+        assert!(send.is_err());
+        assert!(recv.is_err());
+        assert!(!path_output.exists());
+    }
+
+    #[tokio::test]
+    async fn udt_tls_without_handshake_port() {
+        crate::init_logger_for_test();
+
+        let (temp_dir, path_input) = file_hashing::fs::extra::generate_random_file(100);
+        let path_output = temp_dir.join("output.txt");
+        let (certificate, _) = generate_certificate();
+
+        let mut sender = Sender::new_single_port("127.0.0.1".parse().unwrap(), 3365);
+        let mut recipient = Recipient::new_single_port("::0".parse().unwrap(), 3365);
+        sender.add_tls_pinned_fingerprint(get_fingerprint(&certificate));
+        recipient.add_tls_pinned_fingerprint(get_fingerprint(&certificate));
+
+        assert!(matches!(
+            sender.udt_send_file(path_input.path()).await,
+            Err(UdtError::Assert(_))
+        ));
+        assert!(matches!(
+            recipient.udt_recv_file(path_output.as_path()).await,
+            Err(UdtError::Assert(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn send_udt_with_mutual_tls() {
+        crate::init_logger_for_test();
+
+        let (temp_dir, path_input) = file_hashing::fs::extra::generate_random_file(4352);
+        let (certificate, private_key) = generate_certificate();
+        let (sender_certificate, sender_private_key) = generate_certificate();
+
+        // Without the certificate of the sender, then with it
+        for (i, with_certificate) in [false, true].into_iter().enumerate() {
+            let path_output = temp_dir.join(format!("output_{}.txt", i));
+            let port = 3347 + i as u16;
+
+            let mut sender = Sender::new("127.0.0.1".parse().unwrap(), port, port + 2019);
+            let mut recipient = Recipient::new("::0".parse().unwrap(), port, port + 2019);
+            sender.add_tls_pinned_fingerprint(get_fingerprint(&certificate));
+            if with_certificate {
+                sender.set_tls_certificate(
+                    vec![sender_certificate.clone()],
+                    sender_private_key.clone(),
+                );
+            }
+            recipient.set_tls_certificate(vec![certificate.clone()], private_key.clone());
+            recipient.add_tls_pinned_fingerprint(get_fingerprint(&sender_certificate));
+
+            let (recv, send) = tokio::join!(
+                recipient.udt_recv_file(path_output.as_path()),
+                sender.udt_send_file(path_input.path())
+            );
+
+            assert_eq!(send.is_ok(), with_certificate);
+            assert_eq!(recv.is_ok(), with_certificate);
+            assert_eq!(path_output.exists(), with_certificate);
+        }
+    }
+}
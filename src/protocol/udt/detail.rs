@@ -1,55 +1,243 @@
 //! More detailed functions for [`udt`](crate::protocol::udt)
 
-use super::UdtError;
+use super::{error::assert_udt, ServerEvent, UdtError};
 use crate::{
     common::timeout,
-    prelude::{ConfigRecipient, ConfigSender},
-    protocol::error::ProtocolError,
+    core::{CoreConfig, NegotiatedCapabilities},
+    prelude::ConfigRecipient,
+    protocol::{
+        connection::{DataConnection, SecureConnection, TcpConnection, TcpConnectionListener},
+        error::ProtocolError,
+        handshake::accept_handshake_socket,
+        raw,
+    },
+};
+use log::{debug, warn};
+use std::{fmt::Debug, future::Future, net::SocketAddr, path::PathBuf};
+use tokio::{
+    sync::mpsc::UnboundedSender,
+    task::{JoinSet, LocalSet},
 };
-use log::debug;
-use tokio::net::{TcpListener, TcpStream};
 use tokio_udt::{UdtConnection, UdtListener};
 
+/// TLS protects only the connection for handshakes. Without it, TLS settings can't be used
+#[cfg_attr(not(feature = "tls"), allow(unused_variables))]
+fn check_tls(config: &impl CoreConfig) -> Result<(), UdtError> {
+    #[cfg(feature = "tls")]
+    assert_udt!(
+        config.get_port_for_handshake().is_some() || !config.get_tls().is_set(),
+        "TLS needs the port for handshakes"
+    );
+
+    Ok(())
+}
+
 /// Make all connections for [`Sender`](crate::sender::Sender)
+///
+/// Also for [`Recipient`](crate::recipient::Recipient) that fetches a file from
+/// [`SenderServer`](crate::protocol::udt::SenderServer)
 pub(crate) async fn all_connect_for_sender(
-    config: &ConfigSender<'_>,
-) -> Result<(UdtConnection, TcpStream), UdtError> {
+    config: &(impl CoreConfig + Debug),
+) -> Result<(UdtConnection, Option<TcpConnection>), UdtError> {
     debug!("run all_connect_for_sender for udt. Config: {:?}", config);
+    check_tls(config)?;
 
     let udt_connection = timeout!(
-        UdtConnection::connect((config.addr, config.port_for_send_files), None),
+        UdtConnection::connect((config.get_addr(), config.get_port_for_send_files()), None),
         |_| UdtError::Protocol(ProtocolError::TimeoutExpired),
-        config.timeout
+        config.get_timeout()
     )?
     .map_err(|e| UdtError::Protocol(ProtocolError::Connect(e)))?;
     debug!("done socket udt connect");
 
-    let socket_for_handshake = timeout!(
-        TcpStream::connect((config.addr, config.port_for_handshake)),
-        |_| UdtError::Protocol(ProtocolError::TimeoutExpired),
-        config.timeout
-    )?
-    .map_err(|e| UdtError::Protocol(ProtocolError::Connect(e)))?;
-    debug!("done socket handshake connect");
+    let socket_for_handshake = match config.get_port_for_handshake() {
+        Some(port_for_handshake) => {
+            let mut socket = timeout!(
+                TcpConnection::connect((config.get_addr(), port_for_handshake), config),
+                |_| UdtError::Protocol(ProtocolError::TimeoutExpired),
+                config.get_timeout()
+            )?
+            .map_err(UdtError::Protocol)?;
+
+            // Recipient checks TLS before the negotiation
+            timeout!(
+                socket.finish_tls_handshake(),
+                |_| UdtError::Protocol(ProtocolError::TimeoutExpired),
+                config.get_timeout()
+            )?
+            .map_err(|e| UdtError::Protocol(ProtocolError::Connect(e)))?;
+            debug!("done socket handshake connect");
+
+            Some(socket)
+        }
+        None => None,
+    };
 
     Ok((udt_connection, socket_for_handshake))
 }
 
 /// Make bind connections for [`Recipient`](crate::recipient::Recipient)
+///
+/// Also for [`SenderServer`](crate::protocol::udt::SenderServer)
 pub(crate) async fn all_bind_for_recipient(
-    config: &ConfigRecipient<'_>,
-) -> Result<(UdtListener, TcpListener), UdtError> {
+    config: &(impl CoreConfig + Debug),
+) -> Result<(UdtListener, Option<TcpConnectionListener>), UdtError> {
     debug!("run all_bind_for_recipient for udt. Config: {:?}", config);
+    check_tls(config)?;
 
-    let udt_listener = UdtListener::bind((config.addr, config.port_for_send_files).into(), None)
-        .await
-        .map_err(|e| UdtError::Protocol(ProtocolError::Bind(e)))?;
+    let udt_listener = UdtListener::bind(
+        (config.get_addr(), config.get_port_for_send_files()).into(),
+        None,
+    )
+    .await
+    .map_err(|e| UdtError::Protocol(ProtocolError::Bind(e)))?;
     debug!("done socket udt bind");
 
-    let tcp_handshake = TcpListener::bind((config.addr, config.port_for_handshake))
-        .await
-        .map_err(|e| UdtError::Protocol(ProtocolError::Bind(e)))?;
-    debug!("done socket handshake bind");
+    let tcp_handshake = match config.get_port_for_handshake() {
+        Some(port_for_handshake) => {
+            let listener =
+                TcpConnectionListener::bind((config.get_addr(), port_for_handshake), config)
+                    .await
+                    .map_err(UdtError::Protocol)?;
+            debug!("done socket handshake bind");
+
+            Some(listener)
+        }
+        None => None,
+    };
 
     Ok((udt_listener, tcp_handshake))
 }
+
+/// Bind, accept the connections of [`Sender`](crate::sender::Sender) and negotiate
+///
+/// TLS of the connection for handshakes is checked before the negotiation. If it fails,
+/// the negotiation is refused: the sender doesn't wait for the answer that never comes.
+///
+/// Returns address of the sender, negotiated capabilities, the connection for data
+/// and the connection for handshakes
+pub(crate) async fn accept_for_recipient(
+    config: &mut ConfigRecipient<'_>,
+) -> Result<
+    (
+        SocketAddr,
+        NegotiatedCapabilities,
+        SecureConnection<UdtConnection>,
+        Option<TcpConnection>,
+    ),
+    UdtError,
+> {
+    let (udt_listener, mut tcp_handshake) = all_bind_for_recipient(config).await?;
+
+    let (addr, connection) = timeout!(
+        udt_listener.accept(),
+        |_| UdtError::Protocol(ProtocolError::TimeoutExpired),
+        config.get_timeout()
+    )?
+    .map_err(|e| UdtError::Protocol(ProtocolError::Accept(e)))?;
+    debug!("accepted connection from {}", addr);
+
+    let mut socket_for_handshake = accept_handshake_socket(tcp_handshake.as_mut())
+        .await
+        .map_err(|e| UdtError::Protocol(ProtocolError::Handshake(e)))?;
+
+    if let Some(socket) = socket_for_handshake.as_mut() {
+        let finished = timeout!(
+            socket.finish_tls_handshake(),
+            |_| UdtError::Protocol(ProtocolError::TimeoutExpired),
+            config.get_timeout()
+        )?;
+
+        if let Err(e) = finished {
+            let reason = format!("TLS of the connection for handshakes failed: {}", e);
+            raw::refuse_negotiation(connection, reason)
+                .await
+                .map_err(UdtError::Protocol)?;
+            return Err(UdtError::Protocol(ProtocolError::Accept(e)));
+        }
+    }
+
+    let (negotiated, connection) = raw::negotiate_for_recipient(connection, config)
+        .await
+        .map_err(UdtError::Protocol)?;
+
+    // Secure connection has handshakes in-band
+    if connection.is_secure() {
+        socket_for_handshake = None;
+    }
+
+    Ok((addr, negotiated, connection, socket_for_handshake))
+}
+
+/// Accept connections until `shutdown` is done and run `transfer` for each of them
+/// on a local tokio task. The result of `transfer` is sent to `events`
+///
+/// Handshakes are only in-band: a separate handshake port can't tell which connection
+/// the handshake belongs to, if many peers connect at the same time.
+/// A failed accept is only logged: it doesn't stop the server and the running transfers.
+/// After `shutdown`, the running transfers are finished
+pub(crate) async fn serve<F, T, R>(
+    config: &(impl CoreConfig + Debug),
+    shutdown: F,
+    events: &UnboundedSender<ServerEvent>,
+    transfer: T,
+) -> Result<(), UdtError>
+where
+    F: Future<Output = ()>,
+    T: Fn(UdtConnection, SocketAddr) -> R,
+    R: Future<Output = Result<PathBuf, UdtError>> + 'static,
+{
+    assert_udt!(
+        config.get_port_for_handshake().is_none(),
+        "server needs in-band handshakes: use new_single_port"
+    );
+    let (udt_listener, _) = all_bind_for_recipient(config).await?;
+
+    LocalSet::new()
+        .run_until(async {
+            let mut transfers = JoinSet::new();
+            tokio::pin!(shutdown);
+
+            loop {
+                let (addr, connection) = tokio::select! {
+                    _ = &mut shutdown => break,
+                    accepted = udt_listener.accept() => match accepted {
+                        Ok(accepted) => accepted,
+                        Err(e) => {
+                            warn!("failed to accept connection: {}", e);
+                            continue;
+                        }
+                    },
+                };
+                debug!("accepted connection from {}", addr);
+
+                let future = transfer(connection, addr);
+                let events = events.clone();
+
+                transfers.spawn_local(async move {
+                    let event = match future.await {
+                        Ok(path) => ServerEvent::Completed {
+                            peer_addr: addr,
+                            path,
+                        },
+                        Err(error) => ServerEvent::Failed {
+                            peer_addr: addr,
+                            error,
+                        },
+                    };
+
+                    debug!("transfer is done: {:?}", event);
+                    let _ = events.send(event);
+                });
+
+                // Forget finished transfers
+                while transfers.try_join_next().is_some() {}
+            }
+
+            debug!("shutdown. Waiting for {} transfers", transfers.len());
+            while transfers.join_next().await.is_some() {}
+
+            Ok(())
+        })
+        .await
+}
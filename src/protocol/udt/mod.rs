@@ -27,30 +27,72 @@
 //!
 //! # How it works?
 //!
-//! 1. We send a handshake that contains the checksum, the
-//! name of the original file and the file size
-//! 2. Running the udt implementation
+//! 1. We agree with the recipient on capabilities
+//!    (see [`NegotiatedCapabilities`](crate::core::NegotiatedCapabilities))
+//! 2. We send a handshake that contains the
+//!    name of the original file and the file size
+//! 3. Running the udt implementation
+//! 4. Send the checksum, computed while sending the file
 //!
 //! And so for **EVERY** file
 //!
+//! For a directory ([`UdtSender::udt_send_dir`]), the manifest with all files
+//! is sent instead of handshakes. Then all files are sent over one udt connection.
+//!
+//! Many files ([`UdtSender::udt_send_files`]) are also sent over one udt connection:
+//! the handshake before each file, the result of checking after it.
+//!
+//! To receive files from many senders, use [`RecipientServer`].
+//!
+//! Pull mode: [`SenderServer`] exports a folder, [`UdtRecipient::udt_fetch_file`]
+//! asks it for a file.
+//!
+//! # Single port
+//!
+//! If only one port is open, use `new_single_port`. Then the handshake is sent
+//! over the udt connection before the file:
+//!
+//! ```no_run
+//! # use snwf::prelude::*;
+//! # use std::path::Path;
+//! #
+//! #[tokio::main]
+//! async fn main() {
+//!    let mut sender = Sender::new_single_port("127.0.0.1".parse().unwrap(), 4324);
+//!    let mut recipient = Recipient::new_single_port("::0".parse().unwrap(), 4324);
+//!
+//!    let (recv, send) = tokio::join!(
+//!        recipient.udt_recv_file(Path::new("other_file.txt")),
+//!        sender.udt_send_file(Path::new("file_for_send.txt"))
+//!    );
+//!
+//!    send.unwrap();
+//!    recv.unwrap();
+//! }
+//! ```
+//!
 //! # What libraries to use
 //!
 //! * [`tokio-udt`](https://github.com/Distributed-EPFL/tokio-udt) - implementation udt for [tokio](https://tokio.rs/)
 
-mod detail;
+pub(crate) mod detail;
 pub mod error;
-mod raw;
 
+pub mod recipient_server;
+pub mod sender_server;
 pub mod udt_recipient;
 pub mod udt_sender;
 
 pub use error::UdtError;
+pub use recipient_server::{RecipientServer, ServerEvent};
+pub use sender_server::SenderServer;
 pub use udt_recipient::UdtRecipient;
 pub use udt_sender::UdtSender;
 
 #[cfg(test)]
 mod tests {
-    use crate::{common::get_hasher, core::*, prelude::*};
+    use super::{RecipientServer, SenderServer, ServerEvent, UdtError};
+    use crate::{common::get_hasher, core::*, prelude::*, protocol::error::ProtocolError};
     use log::debug;
     use std::sync::{Arc, Mutex};
 
@@ -161,4 +203,1007 @@ mod tests {
 
         assert_eq!(hash_input, hash_output);
     }
+
+    #[tokio::test]
+    async fn send_and_recv_udt_single_port() {
+        crate::init_logger_for_test();
+
+        let (temp_dir, path_input) = file_hashing::fs::extra::generate_random_file(4352);
+        let path_output = temp_dir.join("tess_file.txt");
+
+        let mut sender = Sender::new_single_port("127.0.0.1".parse().unwrap(), 3184);
+        let mut recipient = Recipient::new_single_port("::0".parse().unwrap(), 3184);
+
+        let (recv, send) = tokio::join!(
+            recipient.udt_recv_file(path_output.as_path()),
+            sender.udt_send_file(path_input.path())
+        );
+
+        send.unwrap();
+        recv.unwrap();
+
+        let hash_input = file_hashing::get_hash_file(path_input, &mut get_hasher()).unwrap();
+        let hash_output = file_hashing::get_hash_file(path_output, &mut get_hasher()).unwrap();
+
+        assert_eq!(hash_input, hash_output);
+    }
+
+    #[tokio::test]
+    async fn send_and_recv_udt_with_resume() {
+        crate::init_logger_for_test();
+
+        let (temp_dir, path_input) = file_hashing::fs::extra::generate_random_file(4352);
+        let path_output = temp_dir.join("tess_file.txt");
+
+        let data = std::fs::read(path_input.path()).unwrap();
+        std::fs::write(&path_output, &data[..1000]).unwrap();
+
+        let mut sender = Sender::new_single_port("127.0.0.1".parse().unwrap(), 3194);
+        let mut recipient = Recipient::new_single_port("::0".parse().unwrap(), 3194);
+
+        let (recv, send) = tokio::join!(
+            recipient.udt_recv_file_with_resume(path_output.as_path()),
+            sender.udt_send_file(path_input.path())
+        );
+
+        send.unwrap();
+        recv.unwrap();
+
+        let hash_input = file_hashing::get_hash_file(path_input, &mut get_hasher()).unwrap();
+        let hash_output = file_hashing::get_hash_file(path_output, &mut get_hasher()).unwrap();
+
+        assert_eq!(hash_input, hash_output);
+    }
+
+    #[tokio::test]
+    async fn send_and_recv_udt_with_resume_from_other_file() {
+        crate::init_logger_for_test();
+
+        let (temp_dir, path_input) = file_hashing::fs::extra::generate_random_file(4352);
+        let path_output = temp_dir.join("tess_file.txt");
+        std::fs::write(&path_output, vec![0u8; 1000]).unwrap();
+
+        let mut sender = Sender::new_single_port("127.0.0.1".parse().unwrap(), 3195);
+        let mut recipient = Recipient::new_single_port("::0".parse().unwrap(), 3195);
+
+        let (recv, send) = tokio::join!(
+            recipient.udt_recv_file_with_resume(path_output.as_path()),
+            sender.udt_send_file(path_input.path())
+        );
+
+        send.unwrap();
+        recv.unwrap();
+
+        let hash_input = file_hashing::get_hash_file(path_input, &mut get_hasher()).unwrap();
+        let hash_output = file_hashing::get_hash_file(path_output, &mut get_hasher()).unwrap();
+
+        assert_eq!(hash_input, hash_output);
+    }
+
+    #[tokio::test]
+    async fn send_and_recv_udt_dir() {
+        crate::init_logger_for_test();
+
+        let (input_dir, path_input) = file_hashing::fs::extra::generate_random_file(4352);
+        std::fs::create_dir_all(input_dir.join("dir/empty")).unwrap();
+        std::fs::write(input_dir.join("dir/file.txt"), vec![42u8; 10_000]).unwrap();
+        std::fs::write(input_dir.join("dir/empty_file.txt"), b"").unwrap();
+
+        let output_dir = assert_fs::TempDir::new().unwrap();
+        let path_output = output_dir.path().join("output");
+
+        let max_done_files = Arc::new(Mutex::new(0));
+        let mut sender = Sender::new("127.0.0.1".parse().unwrap(), 3214, 5233);
+        let mut recipient = Recipient::new("::0".parse().unwrap(), 3214, 5233);
+
+        {
+            let max_done_files_clone = max_done_files.clone();
+
+            recipient.set_progress_fn(Some(move |progressing| {
+                if let Progressing::Yield { done_files, .. } = progressing {
+                    let mut max_done_files = max_done_files_clone.lock().unwrap();
+                    *max_done_files = done_files.max(*max_done_files);
+                }
+            }));
+        }
+
+        let (recv, send) = tokio::join!(
+            recipient.udt_recv_dir(path_output.as_path()),
+            sender.udt_send_dir(input_dir.path())
+        );
+
+        send.unwrap();
+        recv.unwrap();
+
+        let file_name = path_input.file_name().unwrap().to_str().unwrap();
+        for entry in ["dir/file.txt", "dir/empty_file.txt", file_name] {
+            let hash_input =
+                file_hashing::get_hash_file(input_dir.join(entry), &mut get_hasher()).unwrap();
+            let hash_output =
+                file_hashing::get_hash_file(path_output.join(entry), &mut get_hasher()).unwrap();
+
+            assert_eq!(hash_input, hash_output);
+        }
+
+        assert!(path_output.join("dir/empty").is_dir());
+        assert_eq!(*max_done_files.lock().unwrap(), 3);
+    }
+
+    #[tokio::test]
+    async fn send_and_recv_udt_files() {
+        crate::init_logger_for_test();
+
+        let (input_dir, path_input) = file_hashing::fs::extra::generate_random_file(4352);
+        let path_other_input = input_dir.join("other_file.txt");
+        std::fs::write(&path_other_input, vec![42u8; 10_000]).unwrap();
+        let path_not_exists = input_dir.join("not_exists.txt");
+
+        let output_dir = assert_fs::TempDir::new().unwrap();
+
+        let mut sender = Sender::new_single_port("127.0.0.1".parse().unwrap(), 3224);
+        let mut recipient = Recipient::new_single_port("::0".parse().unwrap(), 3224);
+
+        let (recv, send) = tokio::join!(
+            recipient.udt_recv_files(output_dir.path()),
+            sender.udt_send_files([
+                path_input.path(),
+                path_not_exists.as_path(),
+                path_other_input.as_path()
+            ])
+        );
+
+        let send = send.unwrap();
+        let recv = recv.unwrap();
+
+        assert_eq!(send.len(), 3);
+        assert!(send[0].1.is_ok());
+        assert!(send[1].1.is_err());
+        assert!(send[2].1.is_ok());
+        assert_eq!(recv.len(), 2);
+        assert!(recv.iter().all(|(_path, result)| result.is_ok()));
+
+        for path in [path_input.path(), path_other_input.as_path()] {
+            let hash_input = file_hashing::get_hash_file(path, &mut get_hasher()).unwrap();
+            let hash_output = file_hashing::get_hash_file(
+                output_dir.join(path.file_name().unwrap()),
+                &mut get_hasher(),
+            )
+            .unwrap();
+
+            assert_eq!(hash_input, hash_output);
+        }
+    }
+
+    #[tokio::test]
+    async fn send_and_recv_udt_stream() {
+        crate::init_logger_for_test();
+
+        let data: Vec<u8> = (0..20_000).map(|i| (i % 251) as u8).collect();
+        let output_dir = assert_fs::TempDir::new().unwrap();
+        let path_output = output_dir.join("stream.bin");
+
+        let mut sender = Sender::new("127.0.0.1".parse().unwrap(), 3234, 5253);
+        let mut recipient = Recipient::new("::0".parse().unwrap(), 3234, 5253);
+
+        let (recv, send) = tokio::join!(
+            recipient.udt_recv_stream(path_output.as_path()),
+            sender.udt_send_stream(&data[..], "stream.bin", None)
+        );
+
+        send.unwrap();
+        recv.unwrap();
+
+        assert_eq!(std::fs::read(path_output).unwrap(), data);
+    }
+
+    #[tokio::test]
+    async fn send_and_recv_udt_to_writer() {
+        crate::init_logger_for_test();
+
+        let data: Vec<u8> = (0..20_000).map(|i| (i % 251) as u8).collect();
+        let mut output = Vec::new();
+
+        let mut sender = Sender::new_single_port("127.0.0.1".parse().unwrap(), 3244);
+        let mut recipient = Recipient::new_single_port("::0".parse().unwrap(), 3244);
+
+        let (recv, send) = tokio::join!(
+            recipient.udt_recv_to_writer(&mut output),
+            sender.udt_send_stream(&data[..], "stream.bin", Some(data.len() as u64))
+        );
+
+        send.unwrap();
+        recv.unwrap();
+
+        assert_eq!(output, data);
+    }
+
+    #[tokio::test]
+    async fn send_and_recv_udt_with_hash_algorithm() {
+        crate::init_logger_for_test();
+
+        for (port, hash_algorithm) in [
+            (3254, HashAlgorithm::Blake3),
+            (3255, HashAlgorithm::Sha256),
+            (3256, HashAlgorithm::Xxh3),
+        ] {
+            let (temp_dir, path_input) = file_hashing::fs::extra::generate_random_file(4352);
+            let path_output = temp_dir.join("tess_file.txt");
+
+            let mut sender = Sender::new_single_port("127.0.0.1".parse().unwrap(), port);
+            let mut recipient = Recipient::new_single_port("::0".parse().unwrap(), port);
+            sender.set_hash_algorithm(hash_algorithm);
+
+            let (recv, send) = tokio::join!(
+                recipient.udt_recv_file(path_output.as_path()),
+                sender.udt_send_file(path_input.path())
+            );
+
+            send.unwrap();
+            recv.unwrap();
+
+            let hash_input = file_hashing::get_hash_file(path_input, &mut get_hasher()).unwrap();
+            let hash_output = file_hashing::get_hash_file(path_output, &mut get_hasher()).unwrap();
+
+            assert_eq!(hash_input, hash_output);
+        }
+    }
+
+    #[tokio::test]
+    async fn send_udt_without_hash_algorithm() {
+        crate::init_logger_for_test();
+
+        let (temp_dir, path_input) = file_hashing::fs::extra::generate_random_file(4352);
+        let path_output = temp_dir.join("tess_file.txt");
+
+        let mut sender = Sender::new_single_port("127.0.0.1".parse().unwrap(), 3257);
+        let mut recipient = Recipient::new_single_port("::0".parse().unwrap(), 3257);
+        sender.set_hash_algorithm(HashAlgorithm::None);
+
+        let (recv, send) = tokio::join!(
+            recipient.udt_recv_file(path_output.as_path()),
+            sender.udt_send_file(path_input.path())
+        );
+        assert!(recv.is_err());
+        assert!(send.is_err());
+
+        recipient.set_hash_algorithm(HashAlgorithm::None);
+        let (recv, send) = tokio::join!(
+            recipient.udt_recv_file(path_output.as_path()),
+            sender.udt_send_file(path_input.path())
+        );
+
+        send.unwrap();
+        recv.unwrap();
+    }
+
+    #[tokio::test]
+    async fn send_and_recv_udt_with_negotiated_capabilities() {
+        crate::init_logger_for_test();
+
+        let (temp_dir, path_input) = file_hashing::fs::extra::generate_random_file(10000);
+        let path_output = temp_dir.join("tess_file.txt");
+
+        let mut sender = Sender::new("127.0.0.1".parse().unwrap(), 3264, 5283);
+        let mut recipient = Recipient::new("::0".parse().unwrap(), 3264, 5283);
+        sender.set_hash_algorithm(HashAlgorithm::Sha256);
+        recipient.set_max_chunk_size(1000);
+        assert!(sender.get_negotiated_capabilities().is_none());
+
+        let (recv, send) = tokio::join!(
+            recipient.udt_recv_file(path_output.as_path()),
+            sender.udt_send_file(path_input.path())
+        );
+
+        send.unwrap();
+        recv.unwrap();
+
+        let negotiated = sender.get_negotiated_capabilities().unwrap();
+        assert_eq!(negotiated, recipient.get_negotiated_capabilities().unwrap());
+        assert_eq!(negotiated.hash_algorithm, HashAlgorithm::Sha256);
+        assert_eq!(negotiated.compression, Compression::None);
+        assert_eq!(negotiated.max_chunk_size, 1000);
+        assert!(negotiated.resume);
+        assert!(!negotiated.encryption);
+
+        let hash_input = file_hashing::get_hash_file(path_input, &mut get_hasher()).unwrap();
+        let hash_output = file_hashing::get_hash_file(path_output, &mut get_hasher()).unwrap();
+
+        assert_eq!(hash_input, hash_output);
+    }
+
+    #[tokio::test]
+    async fn send_and_recv_udt_with_compression() {
+        crate::init_logger_for_test();
+
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        let path_input = temp_dir.join("file.log");
+        std::fs::write(
+            &path_input,
+            b"2023-05-01 12:00:00 INFO request done\n".repeat(1000),
+        )
+        .unwrap();
+        let size = std::fs::metadata(&path_input).unwrap().len();
+
+        for (i, compression) in Compression::supported().into_iter().enumerate() {
+            let path_output = temp_dir.join(format!("output_{}.log", i));
+            let port = 3314 + i as u16;
+
+            let mut sender = Sender::new_single_port("127.0.0.1".parse().unwrap(), port);
+            let mut recipient = Recipient::new_single_port("::0".parse().unwrap(), port);
+            sender.set_compression(compression);
+
+            let done_bytes = Arc::new(Mutex::new(Vec::new()));
+            {
+                let done_bytes = done_bytes.clone();
+                recipient.set_progress_fn(Some(move |progressing| {
+                    if let Progressing::Yield {
+                        done_bytes: done,
+                        total_bytes,
+                        ..
+                    } = progressing
+                    {
+                        done_bytes.lock().unwrap().push((done, total_bytes));
+                    }
+                }));
+            }
+
+            let (recv, send) = tokio::join!(
+                recipient.udt_recv_file(path_output.as_path()),
+                sender.udt_send_file(path_input.as_path())
+            );
+
+            send.unwrap();
+            recv.unwrap();
+
+            let negotiated = sender.get_negotiated_capabilities().unwrap();
+            assert_eq!(negotiated.compression, compression);
+            assert_eq!(
+                recipient.get_negotiated_capabilities().unwrap().compression,
+                compression
+            );
+
+            // Progress is for the uncompressed data
+            assert_eq!(done_bytes.lock().unwrap().last(), Some(&(size, size)));
+            assert_eq!(
+                std::fs::read(&path_input).unwrap(),
+                std::fs::read(&path_output).unwrap()
+            );
+        }
+    }
+
+    #[cfg(feature = "encryption")]
+    #[tokio::test]
+    async fn send_and_recv_udt_with_encryption() {
+        crate::init_logger_for_test();
+
+        let (temp_dir, path_input) = file_hashing::fs::extra::generate_random_file(200 * 1024);
+
+        for (i, encryption) in [
+            Encryption::Ephemeral,
+            Encryption::PreSharedKey(b"secret key".to_vec()),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let path_output = temp_dir.join(format!("output_{}.txt", i));
+            let port = 3324 + i as u16;
+
+            // Handshake goes over the encrypted connection, not over the handshake port
+            let mut sender = Sender::new("127.0.0.1".parse().unwrap(), port, port + 2019);
+            let mut recipient = Recipient::new("::0".parse().unwrap(), port, port + 2019);
+            sender.set_encryption(encryption.clone());
+            recipient.set_encryption(encryption);
+
+            let (recv, send) = tokio::join!(
+                recipient.udt_recv_file(path_output.as_path()),
+                sender.udt_send_file(path_input.path())
+            );
+
+            send.unwrap();
+            recv.unwrap();
+
+            assert!(sender.get_negotiated_capabilities().unwrap().encryption);
+            assert!(recipient.get_negotiated_capabilities().unwrap().encryption);
+            assert_eq!(
+                std::fs::read(path_input.path()).unwrap(),
+                std::fs::read(&path_output).unwrap()
+            );
+        }
+    }
+
+    #[cfg(feature = "encryption")]
+    #[tokio::test]
+    async fn send_udt_with_other_key() {
+        crate::init_logger_for_test();
+
+        let (temp_dir, path_input) = file_hashing::fs::extra::generate_random_file(4352);
+        let path_output = temp_dir.join("output.txt");
+
+        let mut sender = Sender::new_single_port("127.0.0.1".parse().unwrap(), 3334);
+        let mut recipient = Recipient::new_single_port("::0".parse().unwrap(), 3334);
+        sender.set_encryption(Encryption::PreSharedKey(b"secret key".to_vec()));
+        recipient.set_encryption(Encryption::PreSharedKey(b"other key".to_vec()));
+
+        let (recv, send) = tokio::join!(
+            recipient.udt_recv_file(path_output.as_path()),
+            sender.udt_send_file(path_input.path())
+        );
+
+        assert!(matches!(
+            send,
+            Err(UdtError::Protocol(ProtocolError::AuthenticationFailed))
+        ));
+        assert!(matches!(
+            recv,
+            Err(UdtError::Protocol(ProtocolError::AuthenticationFailed))
+        ));
+        assert!(!path_output.exists());
+    }
+
+    #[cfg(feature = "encryption")]
+    #[tokio::test]
+    async fn send_udt_without_required_encryption() {
+        crate::init_logger_for_test();
+
+        let (temp_dir, path_input) = file_hashing::fs::extra::generate_random_file(4352);
+        let path_output = temp_dir.join("output.txt");
+
+        let mut sender = Sender::new_single_port("127.0.0.1".parse().unwrap(), 3335);
+        let mut recipient = Recipient::new_single_port("::0".parse().unwrap(), 3335);
+        recipient.set_encryption(Encryption::Ephemeral);
+
+        let (recv, send) = tokio::join!(
+            recipient.udt_recv_file(path_output.as_path()),
+            sender.udt_send_file(path_input.path())
+        );
+
+        assert!(matches!(
+            send,
+            Err(UdtError::Protocol(ProtocolError::Handshake(
+                crate::protocol::handshake::HandshakeError::NegotiationFailed(_)
+            )))
+        ));
+        assert!(recv.is_err());
+        assert!(!path_output.exists());
+    }
+
+    #[tokio::test]
+    async fn send_and_recv_udt_with_authentication() {
+        crate::init_logger_for_test();
+
+        let (temp_dir, path_input) = file_hashing::fs::extra::generate_random_file(200 * 1024);
+        let path_output = temp_dir.join("output.txt");
+
+        // Handshake goes over the authenticated connection, not over the handshake port
+        let mut sender = Sender::new("127.0.0.1".parse().unwrap(), 3354, 5373);
+        let mut recipient = Recipient::new("::0".parse().unwrap(), 3354, 5373);
+        sender.set_auth_key(Some(b"shared secret".to_vec()));
+        recipient.set_auth_key(Some(b"shared secret".to_vec()));
+
+        let (recv, send) = tokio::join!(
+            recipient.udt_recv_file(path_output.as_path()),
+            sender.udt_send_file(path_input.path())
+        );
+
+        send.unwrap();
+        recv.unwrap();
+
+        assert!(sender.get_negotiated_capabilities().unwrap().authentication);
+        assert!(
+            recipient
+                .get_negotiated_capabilities()
+                .unwrap()
+                .authentication
+        );
+        assert_eq!(
+            std::fs::read(path_input.path()).unwrap(),
+            std::fs::read(&path_output).unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn send_udt_unauthorized() {
+        crate::init_logger_for_test();
+
+        let (temp_dir, path_input) = file_hashing::fs::extra::generate_random_file(4352);
+
+        // Other secret, no secret on the sender, no secret on the recipient
+        for (i, (sender_key, recipient_key)) in [
+            (
+                Some(b"shared secret".to_vec()),
+                Some(b"other secret".to_vec()),
+            ),
+            (None, Some(b"shared secret".to_vec())),
+            (Some(b"shared secret".to_vec()), None),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let path_output = temp_dir.join(format!("output_{}.txt", i));
+            let port = 3355 + i as u16;
+
+            let mut sender = Sender::new_single_port("127.0.0.1".parse().unwrap(), port);
+            let mut recipient = Recipient::new_single_port("::0".parse().unwrap(), port);
+            sender.set_auth_key(sender_key);
+            recipient.set_auth_key(recipient_key);
+
+            let (recv, send) = tokio::join!(
+                recipient.udt_recv_file(path_output.as_path()),
+                sender.udt_send_file(path_input.path())
+            );
+
+            assert!(matches!(
+                send,
+                Err(UdtError::Protocol(ProtocolError::Unauthorized))
+            ));
+            assert!(matches!(
+                recv,
+                Err(UdtError::Protocol(ProtocolError::Unauthorized))
+            ));
+            assert!(!path_output.exists());
+        }
+    }
+
+    #[tokio::test]
+    async fn send_and_recv_udt_signed() {
+        crate::init_logger_for_test();
+
+        let (temp_dir, path_input) = file_hashing::fs::extra::generate_random_file(200 * 1024);
+        let path_output = temp_dir.join("output.txt");
+        let signing_key = SigningKey::from_bytes(&[7; 32]);
+
+        let mut sender = Sender::new("127.0.0.1".parse().unwrap(), 3358, 5377);
+        let mut recipient = Recipient::new("::0".parse().unwrap(), 3358, 5377);
+        sender.set_signing_key(Some(signing_key.clone()));
+        recipient.add_trusted_signer(SigningKey::from_bytes(&[8; 32]).verifying_key());
+        recipient.add_trusted_signer(signing_key.verifying_key());
+
+        let signer_in_decision = Arc::new(Mutex::new(None));
+        let signer_in_decision_clone = signer_in_decision.clone();
+        recipient.set_decision_fn(Some(move |incoming: &IncomingFile| {
+            *signer_in_decision_clone.lock().unwrap() = incoming.signer.clone();
+            Decision::Accept
+        }));
+
+        let (recv, send) = tokio::join!(
+            recipient.udt_recv_file(path_output.as_path()),
+            sender.udt_send_file(path_input.path())
+        );
+
+        send.unwrap();
+        recv.unwrap();
+
+        let signer = recipient.get_signer().unwrap();
+        assert_eq!(signer.public_key, signing_key.verifying_key());
+        assert_eq!(signer_in_decision.lock().unwrap().clone(), Some(signer));
+        assert_eq!(
+            std::fs::read(path_input.path()).unwrap(),
+            std::fs::read(&path_output).unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn send_udt_untrusted() {
+        crate::init_logger_for_test();
+
+        let (temp_dir, path_input) = file_hashing::fs::extra::generate_random_file(4352);
+
+        // Unsigned, unknown signer
+        for (i, signing_key) in [None, Some(SigningKey::from_bytes(&[9; 32]))]
+            .into_iter()
+            .enumerate()
+        {
+            let path_output = temp_dir.join(format!("output_{}.txt", i));
+            let port = 3359 + i as u16;
+
+            let mut sender = Sender::new_single_port("127.0.0.1".parse().unwrap(), port);
+            let mut recipient = Recipient::new_single_port("::0".parse().unwrap(), port);
+            sender.set_signing_key(signing_key);
+            recipient.add_trusted_signer(SigningKey::from_bytes(&[7; 32]).verifying_key());
+
+            let (recv, send) = tokio::join!(
+                recipient.udt_recv_file(path_output.as_path()),
+                sender.udt_send_file(path_input.path())
+            );
+
+            assert!(matches!(
+                send,
+                Err(UdtError::Protocol(ProtocolError::Rejected(_)))
+            ));
+            assert!(matches!(
+                recv,
+                Err(UdtError::Protocol(ProtocolError::Untrusted(_)))
+            ));
+            assert!(recipient.get_signer().is_none());
+            assert!(!path_output.exists());
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn send_and_recv_udt_non_utf8_name() {
+        use crate::protocol::handshake::FileNameEncoding;
+        use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+        crate::init_logger_for_test();
+
+        let (input_dir, _path_input) = file_hashing::fs::extra::generate_random_file(1);
+        let raw = b"file_\xff.txt";
+        let path_input = input_dir.join(OsStr::from_bytes(raw));
+        std::fs::write(&path_input, vec![7u8; 4352]).unwrap();
+
+        for (i, (encoding, file_name)) in [
+            (FileNameEncoding::Utf8, OsStr::new("file_\u{FFFD}.txt")),
+            (FileNameEncoding::Exact, OsStr::from_bytes(raw)),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let (output_dir, _path) = file_hashing::fs::extra::generate_random_file(1);
+            let port = 3361 + i as u16;
+
+            let mut sender = Sender::new_single_port("127.0.0.1".parse().unwrap(), port);
+            let mut recipient = Recipient::new_single_port("::0".parse().unwrap(), port);
+            recipient.set_file_name_encoding(encoding);
+
+            let (recv, send) = tokio::join!(
+                recipient.udt_recv_file_with_original_file_name(output_dir.path()),
+                sender.udt_send_file(path_input.as_path())
+            );
+
+            send.unwrap();
+            recv.unwrap();
+
+            assert_eq!(
+                std::fs::read(output_dir.join(file_name)).unwrap(),
+                std::fs::read(&path_input).unwrap()
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn send_and_recv_udt_with_metadata() {
+        crate::init_logger_for_test();
+
+        let (temp_dir, path_input) = file_hashing::fs::extra::generate_random_file(4352);
+        let modified = std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1 << 30);
+        std::fs::File::options()
+            .write(true)
+            .open(path_input.path())
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            std::fs::set_permissions(path_input.path(), std::fs::Permissions::from_mode(0o750))
+                .unwrap();
+        }
+
+        for (i, preserve) in [PreserveMetadata::default(), PreserveMetadata::all()]
+            .into_iter()
+            .enumerate()
+        {
+            let path_output = temp_dir.join(format!("output_{}.txt", i));
+            let port = 3363 + i as u16;
+
+            let mut sender = Sender::new_single_port("127.0.0.1".parse().unwrap(), port);
+            let mut recipient = Recipient::new_single_port("::0".parse().unwrap(), port);
+            // Own files: the rights of root are not needed
+            recipient.set_preserve_metadata(preserve);
+
+            let (recv, send) = tokio::join!(
+                recipient.udt_recv_file(path_output.as_path()),
+                sender.udt_send_file(path_input.path())
+            );
+
+            send.unwrap();
+            recv.unwrap();
+
+            let metadata = std::fs::metadata(&path_output).unwrap();
+            assert_eq!(metadata.modified().unwrap() == modified, preserve.times);
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+
+                assert_eq!(
+                    metadata.permissions().mode() & 0o777 == 0o750,
+                    preserve.mode
+                );
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn send_udt_rejected_by_recipient() {
+        crate::init_logger_for_test();
+
+        let (temp_dir, path_input) = file_hashing::fs::extra::generate_random_file(4352);
+        let path_output = temp_dir.join("tess_file.txt");
+
+        let mut sender = Sender::new("127.0.0.1".parse().unwrap(), 3274, 5293);
+        let mut recipient = Recipient::new("::0".parse().unwrap(), 3274, 5293);
+        recipient.set_decision_fn(Some(|incoming: &IncomingFile| {
+            debug!("incoming file: {:?}", incoming);
+
+            match incoming.size > 1000 {
+                true => Decision::Reject("quota exceeded".to_string()),
+                false => Decision::Accept,
+            }
+        }));
+
+        let (recv, send) = tokio::join!(
+            recipient.udt_recv_file(path_output.as_path()),
+            sender.udt_send_file(path_input.path())
+        );
+
+        match send.err().unwrap() {
+            UdtError::Protocol(ProtocolError::Rejected(reason)) => {
+                assert_eq!(reason, "quota exceeded")
+            }
+            e => panic!("{:?} != ProtocolError::Rejected", e),
+        }
+        assert!(matches!(
+            recv,
+            Err(UdtError::Protocol(ProtocolError::Rejected(_)))
+        ));
+        assert!(!path_output.exists());
+    }
+
+    #[tokio::test]
+    async fn send_udt_redirected_by_recipient() {
+        crate::init_logger_for_test();
+
+        let (temp_dir, path_input) = file_hashing::fs::extra::generate_random_file(4352);
+        let path_output = temp_dir.join("tess_file.txt");
+        let path_redirect = temp_dir.join("redirected.txt");
+
+        let mut sender = Sender::new_single_port("127.0.0.1".parse().unwrap(), 3275);
+        let mut recipient = Recipient::new_single_port("::0".parse().unwrap(), 3275);
+        {
+            let path_redirect = path_redirect.clone();
+            recipient.set_decision_fn(Some(move |_incoming: &IncomingFile| {
+                Decision::Redirect(path_redirect.clone())
+            }));
+        }
+
+        let (recv, send) = tokio::join!(
+            recipient.udt_recv_file(path_output.as_path()),
+            sender.udt_send_file(path_input.path())
+        );
+
+        send.unwrap();
+        recv.unwrap();
+        assert!(!path_output.exists());
+
+        let hash_input = file_hashing::get_hash_file(path_input, &mut get_hasher()).unwrap();
+        let hash_output = file_hashing::get_hash_file(path_redirect, &mut get_hasher()).unwrap();
+
+        assert_eq!(hash_input, hash_output);
+    }
+
+    #[tokio::test]
+    async fn udt_dir_and_stream_rejected_by_recipient() {
+        crate::init_logger_for_test();
+
+        let input_dir = assert_fs::TempDir::new().unwrap();
+        std::fs::write(input_dir.join("small.txt"), b"test data").unwrap();
+        std::fs::write(input_dir.join("big.txt"), vec![42u8; 10_000]).unwrap();
+        let output_dir = assert_fs::TempDir::new().unwrap();
+        let path_output = output_dir.join("output");
+        let path_stream = output_dir.join("stream.bin");
+
+        let asked = Arc::new(Mutex::new(Vec::new()));
+        let decision_fn = {
+            let asked = asked.clone();
+            move |incoming: &IncomingFile| {
+                asked.lock().unwrap().push(incoming.file_name.clone());
+
+                match incoming.size > 1000 {
+                    true => Decision::Reject("quota exceeded".to_string()),
+                    false => Decision::Accept,
+                }
+            }
+        };
+
+        let mut sender = Sender::new_single_port("127.0.0.1".parse().unwrap(), 3367);
+        let mut recipient = Recipient::new_single_port("::0".parse().unwrap(), 3367);
+        recipient.set_decision_fn(Some(decision_fn.clone()));
+
+        let (recv, send) = tokio::join!(
+            recipient.udt_recv_dir(path_output.as_path()),
+            sender.udt_send_dir(input_dir.path())
+        );
+
+        match send.err().unwrap() {
+            UdtError::Protocol(ProtocolError::Rejected(reason)) => {
+                assert_eq!(reason, "quota exceeded")
+            }
+            e => panic!("{:?} != ProtocolError::Rejected", e),
+        }
+        assert!(matches!(
+            recv,
+            Err(UdtError::Protocol(ProtocolError::Rejected(_)))
+        ));
+        assert!(!path_output.exists());
+        assert!(asked.lock().unwrap().contains(&"big.txt".to_string()));
+
+        let data = vec![42u8; 10_000];
+        let mut sender = Sender::new_single_port("127.0.0.1".parse().unwrap(), 3368);
+        let mut recipient = Recipient::new_single_port("::0".parse().unwrap(), 3368);
+        recipient.set_decision_fn(Some(decision_fn));
+
+        let (recv, send) = tokio::join!(
+            recipient.udt_recv_stream(path_stream.as_path()),
+            sender.udt_send_stream(&data[..], "stream.bin", Some(data.len() as u64))
+        );
+
+        assert!(matches!(
+            send,
+            Err(UdtError::Protocol(ProtocolError::Rejected(_)))
+        ));
+        assert!(matches!(
+            recv,
+            Err(UdtError::Protocol(ProtocolError::Rejected(_)))
+        ));
+        assert!(!path_stream.exists());
+        assert_eq!(asked.lock().unwrap().last().unwrap(), "stream.bin");
+    }
+
+    #[tokio::test]
+    async fn recipient_server_with_many_senders() {
+        crate::init_logger_for_test();
+
+        let (temp_dir, path_input) = file_hashing::fs::extra::generate_random_file(4352);
+        let output = assert_fs::TempDir::new().unwrap();
+        let path_other_input = temp_dir.join("other_file.txt");
+        std::fs::write(&path_other_input, b"test data").unwrap();
+
+        let recipient = Recipient::new_single_port("::0".parse().unwrap(), 3284);
+        let (server, mut events) = RecipientServer::new(recipient.get_config(), output.path());
+        let (shutdown, shutdown_signal) = tokio::sync::oneshot::channel::<()>();
+
+        let mut first_sender = Sender::new_single_port("127.0.0.1".parse().unwrap(), 3284);
+        let mut second_sender = Sender::new_single_port("127.0.0.1".parse().unwrap(), 3284);
+
+        let senders = async {
+            let (first, second) = tokio::join!(
+                first_sender.udt_send_file(path_input.path()),
+                second_sender.udt_send_file(path_other_input.as_path())
+            );
+            first.unwrap();
+            second.unwrap();
+
+            let mut paths = Vec::new();
+            for _ in 0..2 {
+                match events.recv().await.unwrap() {
+                    ServerEvent::Completed { path, .. } => paths.push(path),
+                    ServerEvent::Failed { error, .. } => panic!("transfer failed: {:?}", error),
+                }
+            }
+
+            // The file is not overwritten
+            std::fs::write(&path_other_input, b"other data").unwrap();
+            let result = first_sender.udt_send_file(path_other_input.as_path()).await;
+            assert!(matches!(
+                result,
+                Err(UdtError::Protocol(ProtocolError::Rejected(_)))
+            ));
+            assert!(matches!(
+                events.recv().await.unwrap(),
+                ServerEvent::Failed { .. }
+            ));
+
+            shutdown.send(()).unwrap();
+            paths
+        };
+
+        let (result, mut paths) = tokio::join!(
+            server.run(async {
+                shutdown_signal.await.unwrap();
+            }),
+            senders
+        );
+        result.unwrap();
+
+        let mut expected = vec![
+            output.join("other_file.txt"),
+            output.join(path_input.path().file_name().unwrap()),
+        ];
+        expected.sort();
+        paths.sort();
+        assert_eq!(paths, expected);
+        assert_eq!(
+            std::fs::read(output.join("other_file.txt")).unwrap(),
+            b"test data"
+        );
+
+        let hash_input = file_hashing::get_hash_file(path_input.path(), &mut get_hasher()).unwrap();
+        let hash_output = file_hashing::get_hash_file(
+            output.join(path_input.path().file_name().unwrap()),
+            &mut get_hasher(),
+        )
+        .unwrap();
+        assert_eq!(hash_input, hash_output);
+
+        // Server is stopped
+        assert!(first_sender.udt_send_file(path_input.path()).await.is_err());
+
+        // Handshakes must be in-band
+        let recipient = Recipient::new("::0".parse().unwrap(), 3284, 3285);
+        let (server, _events) = RecipientServer::new(recipient.get_config(), output.path());
+        assert!(matches!(
+            server.run(async {}).await,
+            Err(UdtError::Assert(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn fetch_udt_from_sender_server() {
+        crate::init_logger_for_test();
+
+        let root = assert_fs::TempDir::new().unwrap();
+        std::fs::create_dir(root.join("dir")).unwrap();
+        std::fs::write(root.join("dir").join("file.txt"), b"test data").unwrap();
+        let output = assert_fs::TempDir::new().unwrap();
+        let path_output = output.join("file.txt");
+        let path_outside = output.join("outside.txt");
+        let path_missing = output.join("missing.txt");
+
+        let sender = Sender::new_single_port("::0".parse().unwrap(), 3294);
+        let (server, mut events) = SenderServer::new(sender.get_config(), root.path());
+        let (shutdown, shutdown_signal) = tokio::sync::oneshot::channel::<()>();
+
+        let mut recipient = Recipient::new_single_port("127.0.0.1".parse().unwrap(), 3294);
+
+        let fetches = async {
+            recipient
+                .udt_fetch_file("dir/file.txt", path_output.as_path())
+                .await
+                .unwrap();
+            match events.recv().await.unwrap() {
+                ServerEvent::Completed { path, .. } => {
+                    assert!(path.ends_with("dir/file.txt"));
+                }
+                ServerEvent::Failed { error, .. } => panic!("transfer failed: {:?}", error),
+            }
+
+            for (remote_path, local_path) in [
+                ("../outside.txt", path_outside.as_path()),
+                ("dir/missing.txt", path_missing.as_path()),
+            ] {
+                let result = recipient.udt_fetch_file(remote_path, local_path).await;
+                assert!(matches!(
+                    result,
+                    Err(UdtError::Protocol(ProtocolError::Rejected(_)))
+                ));
+                assert!(matches!(
+                    events.recv().await.unwrap(),
+                    ServerEvent::Failed { .. }
+                ));
+            }
+
+            shutdown.send(()).unwrap();
+        };
+
+        let (result, _) = tokio::join!(
+            server.run(async {
+                shutdown_signal.await.unwrap();
+            }),
+            fetches
+        );
+        result.unwrap();
+
+        assert_eq!(std::fs::read(&path_output).unwrap(), b"test data");
+        assert!(!path_outside.exists());
+        assert!(!path_missing.exists());
+
+        // The server has no separate handshake port
+        let mut recipient = Recipient::new("127.0.0.1".parse().unwrap(), 3294, 3295);
+        let result = recipient
+            .udt_fetch_file("dir/file.txt", path_missing.as_path())
+            .await;
+        assert!(matches!(result, Err(UdtError::Assert(_))));
+    }
 }
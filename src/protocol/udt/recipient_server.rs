@@ -0,0 +1,191 @@
+//! Long-running [udt](https://en.wikipedia.org/wiki/UDP-based_Data_Transfer_Protocol) server for [`Recipient`](crate::recipient::Recipient)
+
+use super::UdtError;
+use crate::{
+    prelude::*,
+    protocol::{
+        connection::DataConnection,
+        error::ProtocolError,
+        handshake::{recv_handshake_in_band, send_message, HandshakeAnswer},
+        raw,
+        udt::{detail, error::assert_udt},
+    },
+};
+use log::debug;
+use std::{
+    future::Future,
+    io::ErrorKind,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use tokio_udt::UdtConnection;
+
+/// Result of one transfer of [`RecipientServer`] or [`SenderServer`](super::SenderServer)
+#[derive(Debug)]
+pub enum ServerEvent {
+    /// File is received and checked, or sent
+    Completed {
+        /// Address of the peer
+        peer_addr: SocketAddr,
+
+        /// Path of the received or sent file
+        path: PathBuf,
+    },
+
+    /// Transfer failed. Other transfers are not stopped
+    Failed {
+        /// Address of the peer
+        peer_addr: SocketAddr,
+
+        error: UdtError,
+    },
+}
+
+/// Receives files from many [`Sender`](crate::sender::Sender) until shutdown
+///
+/// Each file is sent by [`UdtSender::udt_send_file`](crate::protocol::udt::UdtSender::udt_send_file)
+/// and saved to `output` with the original name. Transfers run concurrently on local tokio tasks,
+/// so [`RecipientServer::run`] must be awaited, not spawned.
+///
+/// The config must be made by `new_single_port`: handshakes are in-band.
+/// A file is never overwritten: if `output` has a file with the same name,
+/// the transfer is rejected.
+///
+/// # Example
+/// ```no_run
+/// # use snwf::prelude::*;
+/// # use snwf::protocol::udt::{RecipientServer, ServerEvent};
+/// #
+/// #[tokio::main]
+/// async fn main() {
+///     let recipient = Recipient::new_single_port("::0".parse().unwrap(), 4324);
+///     let (server, mut events) =
+///         RecipientServer::new(recipient.get_config(), "/home/gladi/Downloads");
+///
+///     let shutdown = async {
+///         tokio::signal::ctrl_c().await.unwrap();
+///     };
+///     let print_events = async {
+///         while let Some(event) = events.recv().await {
+///             println!("{:?}", event);
+///         }
+///     };
+///
+///     let (result, _) = tokio::join!(server.run(shutdown), print_events);
+///     result.unwrap();
+/// }
+/// ```
+pub struct RecipientServer {
+    config: ConfigRecipient<'static>,
+    output: PathBuf,
+    events: UnboundedSender<ServerEvent>,
+}
+
+impl RecipientServer {
+    /// New server. Returns it and the receiver of [`ServerEvent`]
+    ///
+    /// * `config` - config of [`Recipient`](crate::recipient::Recipient).
+    /// * `output` - path to the folder for files.
+    pub fn new<P: AsRef<Path>>(
+        config: ConfigRecipient<'static>,
+        output: P,
+    ) -> (Self, UnboundedReceiver<ServerEvent>) {
+        let (events, receiver) = unbounded_channel();
+
+        let server = Self {
+            config,
+            output: output.as_ref().to_path_buf(),
+            events,
+        };
+
+        (server, receiver)
+    }
+
+    /// Accept transfers until `shutdown` is done
+    ///
+    /// After `shutdown`, new connections are not accepted, but the running transfers are finished
+    pub async fn run<F>(self, shutdown: F) -> Result<(), UdtError>
+    where
+        F: Future<Output = ()>,
+    {
+        assert_udt!(self.output.is_dir(), "output must be a folder path");
+        debug!("running RecipientServer; config: {:?}", self.config);
+
+        detail::serve(&self.config, shutdown, &self.events, |connection, addr| {
+            recv_file(connection, self.config.clone(), self.output.clone(), addr)
+        })
+        .await
+    }
+}
+
+/// Create the empty file of `path`, so other transfers can't write to it.
+/// If the file exists, [`Sender`](crate::sender::Sender) gets [`HandshakeAnswer::Rejected`]
+async fn reserve_file(connection: &mut impl DataConnection, path: &Path) -> Result<(), UdtError> {
+    let result = tokio::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(path)
+        .await;
+
+    match result {
+        Ok(_) => Ok(()),
+        Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+            let reason = format!(
+                "file already exists: {}",
+                path.file_name().unwrap_or_default().to_string_lossy()
+            );
+            send_message(&HandshakeAnswer::Rejected(reason.clone()), connection)
+                .await
+                .map_err(|e| UdtError::Protocol(ProtocolError::Handshake(e)))?;
+            Err(UdtError::Protocol(ProtocolError::Rejected(reason)))
+        }
+        Err(e) => Err(UdtError::Protocol(ProtocolError::FileIO(e))),
+    }
+}
+
+/// Receive one file to `output`. Returns path of the file
+async fn recv_file(
+    mut connection: UdtConnection,
+    mut config: ConfigRecipient<'static>,
+    output: PathBuf,
+    addr: SocketAddr,
+) -> Result<PathBuf, UdtError> {
+    let (_, mut connection) = raw::negotiate_for_recipient(&mut connection, &mut config)
+        .await
+        .map_err(UdtError::Protocol)?;
+
+    let handshake = recv_handshake_in_band(&mut connection)
+        .await
+        .map_err(|e| UdtError::Protocol(ProtocolError::Handshake(e)))?;
+
+    let path = raw::get_path_for_file(
+        &mut connection,
+        &output,
+        &handshake,
+        config.file_name_policy,
+        config.file_name_encoding,
+    )
+    .await
+    .map_err(UdtError::Protocol)?;
+    reserve_file(&mut connection, path.as_path()).await?;
+
+    let result = raw::recv_file(
+        &mut connection,
+        path.as_path(),
+        &Some(config),
+        0,
+        handshake,
+        false,
+        addr,
+    )
+    .await
+    .map(|(path, _signer)| path);
+
+    // The reserved file is not needed, if the transfer failed or the file is redirected
+    if !matches!(&result, Ok(received) if received == &path) {
+        let _ = tokio::fs::remove_file(&path).await;
+    }
+
+    result.map_err(UdtError::Protocol)
+}
@@ -0,0 +1,151 @@
+//! Long-running [udt](https://en.wikipedia.org/wiki/UDP-based_Data_Transfer_Protocol) server for [`Sender`](crate::sender::Sender). Pull mode
+
+use super::{ServerEvent, UdtError};
+use crate::{
+    common::timeout,
+    prelude::*,
+    protocol::{
+        connection::TcpConnection,
+        error::ProtocolError,
+        handshake::{recv_message, send_message, FetchAnswer, FetchRequest},
+        manifest::get_exported_path,
+        raw,
+        udt::{detail, error::assert_udt},
+    },
+};
+use log::debug;
+use std::{
+    future::Future,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use tokio_udt::UdtConnection;
+
+/// Gives files of the exported root to [`Recipient`](crate::recipient::Recipient) until shutdown
+///
+/// Files are requested by [`UdtRecipient::udt_fetch_file`](crate::protocol::udt::UdtRecipient::udt_fetch_file).
+/// Only files inside the root are given, also through symbolic links.
+/// Transfers run concurrently on local tokio tasks, so [`SenderServer::run`] must be awaited, not spawned.
+/// The config must be made by `new_single_port`: handshakes are in-band.
+///
+/// # Example
+/// ```no_run
+/// # use snwf::prelude::*;
+/// # use snwf::protocol::udt::SenderServer;
+/// #
+/// #[tokio::main]
+/// async fn main() {
+///     let sender = Sender::new_single_port("::0".parse().unwrap(), 4324);
+///     let (server, _events) = SenderServer::new(sender.get_config(), "/home/gladi/Documents");
+///
+///     let shutdown = async {
+///         tokio::signal::ctrl_c().await.unwrap();
+///     };
+///
+///     server.run(shutdown).await.unwrap();
+/// }
+/// ```
+pub struct SenderServer {
+    config: ConfigSender<'static>,
+    root: PathBuf,
+    events: UnboundedSender<ServerEvent>,
+}
+
+impl SenderServer {
+    /// New server. Returns it and the receiver of [`ServerEvent`]
+    ///
+    /// * `config` - config of [`Sender`](crate::sender::Sender). Address and ports for bind.
+    /// * `root` - exported folder.
+    pub fn new<P: AsRef<Path>>(
+        config: ConfigSender<'static>,
+        root: P,
+    ) -> (Self, UnboundedReceiver<ServerEvent>) {
+        let (events, receiver) = unbounded_channel();
+
+        let server = Self {
+            config,
+            root: root.as_ref().to_path_buf(),
+            events,
+        };
+
+        (server, receiver)
+    }
+
+    /// Accept requests until `shutdown` is done
+    ///
+    /// After `shutdown`, new connections are not accepted, but the running transfers are finished
+    pub async fn run<F>(self, shutdown: F) -> Result<(), UdtError>
+    where
+        F: Future<Output = ()>,
+    {
+        assert_udt!(self.root.is_dir(), "root must be a folder path");
+        debug!("running SenderServer; config: {:?}", self.config);
+
+        // Sync, so the sockets are bound at the first poll
+        let root = std::fs::canonicalize(&self.root)
+            .map_err(|e| UdtError::Protocol(ProtocolError::FileIO(e)))?;
+
+        detail::serve(&self.config, shutdown, &self.events, |connection, addr| {
+            send_file(connection, self.config.clone(), root.clone(), addr)
+        })
+        .await
+    }
+}
+
+/// Path of the file `remote_path` in `root`. Returns the reason, if the file can't be given
+async fn get_exported_file(root: &Path, remote_path: &str) -> Result<PathBuf, String> {
+    let path = get_exported_path(root, remote_path).await?;
+
+    if !path.is_file() {
+        return Err(format!("not a file: {}", remote_path));
+    }
+
+    Ok(path)
+}
+
+/// Negotiate, get [`FetchRequest`] and send the file. Returns path of the file
+///
+/// The request comes after the negotiation: it is encrypted and authenticated, if it is agreed
+async fn send_file(
+    mut connection: UdtConnection,
+    mut config: ConfigSender<'static>,
+    root: PathBuf,
+    addr: SocketAddr,
+) -> Result<PathBuf, UdtError> {
+    let (_, mut connection) = raw::negotiate_for_sender(&mut connection, &mut config)
+        .await
+        .map_err(UdtError::Protocol)?;
+
+    let request: FetchRequest = timeout!(
+        recv_message(&mut connection),
+        |_| UdtError::Protocol(ProtocolError::TimeoutExpired),
+        config.timeout
+    )?
+    .map_err(|e| UdtError::Protocol(ProtocolError::Handshake(e)))?;
+    debug!("fetch request from {}: {:?}", addr, request);
+
+    let answer = get_exported_file(&root, &request.path).await;
+
+    let message = match &answer {
+        Ok(_) => FetchAnswer::Found,
+        Err(reason) => FetchAnswer::NotFound(reason.clone()),
+    };
+    send_message(&message, &mut connection)
+        .await
+        .map_err(|e| UdtError::Protocol(ProtocolError::Handshake(e)))?;
+
+    let path = answer.map_err(|reason| UdtError::Protocol(ProtocolError::Rejected(reason)))?;
+
+    raw::send_file(
+        &mut connection,
+        path.as_path(),
+        None::<&mut TcpConnection>,
+        &Some(config),
+        0,
+    )
+    .await
+    .map_err(UdtError::Protocol)?;
+
+    Ok(path)
+}
@@ -5,14 +5,22 @@ use crate::{
     common::timeout,
     prelude::*,
     protocol::{
-        error::ProtocolError,
-        handshake::recv_handshake_from_address,
-        udt::{detail, error::assert_udt, raw},
+        error::{FilesResult, ProtocolError},
+        handshake::{
+            recv_handshake_from_socket, recv_message, recv_message_from, send_message, FetchAnswer,
+            FetchRequest, StreamHandshake,
+        },
+        raw,
+        udt::{detail, error::assert_udt},
     },
 };
 use async_trait::async_trait;
 use log::debug;
-use std::path::Path;
+use std::{net::SocketAddr, path::Path};
+use tokio::{
+    fs::File,
+    io::{AsyncWrite, BufWriter},
+};
 
 /// [UDT](https://en.wikipedia.org/wiki/UDP-based_Data_Transfer_Protocol) trait for [`CoreRecipient`]
 #[async_trait(?Send)]
@@ -46,6 +54,10 @@ pub trait UdtRecipient<'a>: CoreRecipient<'a> {
     ///
     /// **But save original name** (not save [`UdtRecipient::udt_recv_file`])
     ///
+    /// Unsafe names (`../x`, `/etc/x` and etc.) are handled by
+    /// [`FileNamePolicy`](crate::protocol::handshake::FileNamePolicy), names that are
+    /// not UTF-8 - by [`FileNameEncoding`](crate::protocol::handshake::FileNameEncoding)
+    ///
     /// # Arguments
     ///
     /// * `output` - path to save file.
@@ -65,6 +77,157 @@ pub trait UdtRecipient<'a>: CoreRecipient<'a> {
     async fn udt_recv_file_with_original_file_name<P>(&mut self, output: P) -> Result<(), UdtError>
     where
         P: AsRef<Path> + Send + Copy + Sync;
+
+    /// Receive a file via [udt](https://en.wikipedia.org/wiki/UDP-based_Data_Transfer_Protocol) protocol, **resuming an interrupted transfer**
+    ///
+    /// If `output` already has the beginning of the file, only the rest of it is received.
+    /// Otherwise, it works as [`UdtRecipient::udt_recv_file`]
+    ///
+    /// # Arguments
+    ///
+    /// * `output` - path to save file. May exist.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use snwf::prelude::*;
+    /// # use std::path::Path;
+    /// #
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut recipient = Recipient::new("::0".parse().unwrap(), 4324, 6343);
+    ///
+    ///     recipient.udt_recv_file_with_resume(Path::new("file.txt"));
+    /// }
+    /// ```
+    async fn udt_recv_file_with_resume<P>(&mut self, output: P) -> Result<(), UdtError>
+    where
+        P: AsRef<Path> + Send + Copy + Sync;
+
+    /// Receive a directory via [udt](https://en.wikipedia.org/wiki/UDP-based_Data_Transfer_Protocol) protocol
+    ///
+    /// Recreates the tree of [`UdtSender::udt_send_dir`](crate::protocol::udt::UdtSender::udt_send_dir) in `output`
+    ///
+    /// # Arguments
+    ///
+    /// * `output` - path to the folder. Will be created if not exists.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use snwf::prelude::*;
+    /// # use std::path::Path;
+    /// #
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut recipient = Recipient::new("::0".parse().unwrap(), 4324, 6343);
+    ///
+    ///     recipient.udt_recv_dir(Path::new("/home/gladi/Downloads/Documents"));
+    /// }
+    /// ```
+    async fn udt_recv_dir<P>(&mut self, output: P) -> Result<(), UdtError>
+    where
+        P: AsRef<Path> + Send + Copy + Sync;
+
+    /// Receive many files over one [udt](https://en.wikipedia.org/wiki/UDP-based_Data_Transfer_Protocol) connection
+    ///
+    /// Files are sent by [`UdtSender::udt_send_files`](crate::protocol::udt::UdtSender::udt_send_files)
+    /// and saved with original names. Returns the result for every file
+    ///
+    /// # Arguments
+    ///
+    /// * `output` - path to the folder.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use snwf::prelude::*;
+    /// # use std::path::Path;
+    /// #
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut recipient = Recipient::new("::0".parse().unwrap(), 4324, 6343);
+    ///
+    ///     recipient.udt_recv_files(Path::new("/home/gladi/Downloads"));
+    /// }
+    /// ```
+    async fn udt_recv_files<P>(&mut self, output: P) -> Result<FilesResult, UdtError>
+    where
+        P: AsRef<Path> + Send + Copy + Sync;
+
+    /// Receive data sent by [`UdtSender::udt_send_stream`](crate::protocol::udt::UdtSender::udt_send_stream)
+    ///
+    /// The hash is computed while receiving
+    ///
+    /// # Arguments
+    ///
+    /// * `output` - path to save data.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use snwf::prelude::*;
+    /// # use std::path::Path;
+    /// #
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut recipient = Recipient::new("::0".parse().unwrap(), 4324, 6343);
+    ///
+    ///     recipient.udt_recv_stream(Path::new("dump.sql"));
+    /// }
+    /// ```
+    async fn udt_recv_stream<P>(&mut self, output: P) -> Result<(), UdtError>
+    where
+        P: AsRef<Path> + Send + Copy + Sync;
+
+    /// Receive data sent by [`UdtSender::udt_send_stream`](crate::protocol::udt::UdtSender::udt_send_stream) to `writer`
+    ///
+    /// For pipes, buffers in memory, decompressors. The hash is computed while receiving,
+    /// so **the data is written to `writer` before it is checked!**
+    ///
+    /// # Arguments
+    ///
+    /// * `writer` - where to write data.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use snwf::prelude::*;
+    /// #
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut recipient = Recipient::new("::0".parse().unwrap(), 4324, 6343);
+    ///
+    ///     recipient.udt_recv_to_writer(&mut tokio::io::stdout());
+    /// }
+    /// ```
+    async fn udt_recv_to_writer<W>(&mut self, writer: &mut W) -> Result<(), UdtError>
+    where
+        W: AsyncWrite + Unpin;
+
+    /// Fetch a file from [`SenderServer`](crate::protocol::udt::SenderServer) via [udt](https://en.wikipedia.org/wiki/UDP-based_Data_Transfer_Protocol) protocol
+    ///
+    /// Connects to the address and port of the config. The config must be made by
+    /// `new_single_port`: [`SenderServer`](crate::protocol::udt::SenderServer) has only in-band handshakes
+    ///
+    /// # Arguments
+    ///
+    /// * `remote_path` - path in the exported root. Separator: `/`.
+    /// * `local_path` - path to save file.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use snwf::prelude::*;
+    /// # use std::path::Path;
+    /// #
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut recipient = Recipient::new_single_port("127.0.0.1".parse().unwrap(), 4324);
+    ///
+    ///     recipient
+    ///         .udt_fetch_file("backups/db.sql", Path::new("db.sql"))
+    ///         .await
+    ///         .unwrap();
+    /// }
+    /// ```
+    async fn udt_fetch_file<P>(&mut self, remote_path: &str, local_path: P) -> Result<(), UdtError>
+    where
+        P: AsRef<Path> + Send + Copy + Sync;
 }
 
 #[async_trait(?Send)]
@@ -79,28 +242,29 @@ impl<'a> UdtRecipient<'a> for Recipient<'a> {
             output.as_ref().display()
         );
 
-        let config = self.get_config();
+        let mut config = self.get_config();
         debug!("running udt_recv_file; config: {:?}", config);
 
-        let (udt_listener, mut tcp_handshake) = detail::all_bind_for_recipient(&config).await?;
+        let (addr, negotiated, mut connection, mut socket_for_handshake) =
+            detail::accept_for_recipient(&mut config).await?;
+        self.negotiated_capabilities = Some(negotiated);
 
-        let (addr, mut connection) = timeout!(
-            udt_listener.accept(),
-            |_| UdtError::Protocol(ProtocolError::TimeoutExpired),
-            config.timeout
-        )?
-        .map_err(|e| UdtError::Protocol(ProtocolError::Accept(e)))?;
-        debug!("accepted connection from {}", addr);
+        let handshake = recv_handshake_from_socket(&mut connection, socket_for_handshake.as_mut())
+            .await
+            .map_err(|e| UdtError::Protocol(ProtocolError::Handshake(e)))?;
 
-        raw::recv_file(
+        let (_, signer) = raw::recv_file(
             &mut connection,
-            &mut tcp_handshake,
             output,
             &Some(config),
             0,
-            None,
+            handshake,
+            false,
+            addr,
         )
-        .await?;
+        .await
+        .map_err(UdtError::Protocol)?;
+        self.signer = signer;
 
         Ok(())
     }
@@ -111,32 +275,265 @@ impl<'a> UdtRecipient<'a> for Recipient<'a> {
     {
         assert_udt!(output.as_ref().is_dir(), "output must be a folder path");
 
-        let config = self.get_config();
+        let mut config = self.get_config();
         debug!("running udt_recv_file; config: {:?}", config);
 
-        let (udt_listener, mut tcp_handshake) = detail::all_bind_for_recipient(&config).await?;
+        let (addr, negotiated, mut connection, mut socket_for_handshake) =
+            detail::accept_for_recipient(&mut config).await?;
+        self.negotiated_capabilities = Some(negotiated);
+
+        let handshake = recv_handshake_from_socket(&mut connection, socket_for_handshake.as_mut())
+            .await
+            .map_err(|e| UdtError::Protocol(ProtocolError::Handshake(e)))?;
+
+        let path = raw::get_path_for_file(
+            &mut connection,
+            output.as_ref(),
+            &handshake,
+            config.file_name_policy,
+            config.file_name_encoding,
+        )
+        .await
+        .map_err(UdtError::Protocol)?;
+
+        let (_, signer) = raw::recv_file(
+            &mut connection,
+            path.as_path(),
+            &Some(config),
+            0,
+            handshake,
+            false,
+            addr,
+        )
+        .await
+        .map_err(UdtError::Protocol)?;
+        self.signer = signer;
+
+        Ok(())
+    }
+
+    async fn udt_recv_file_with_resume<P>(&mut self, output: P) -> Result<(), UdtError>
+    where
+        P: AsRef<Path> + Send + Copy + Sync,
+    {
+        assert_udt!(!output.as_ref().is_dir(), "output must be a file path");
+
+        let mut config = self.get_config();
+        debug!("running udt_recv_file_with_resume; config: {:?}", config);
+
+        let (addr, negotiated, mut connection, mut socket_for_handshake) =
+            detail::accept_for_recipient(&mut config).await?;
+        self.negotiated_capabilities = Some(negotiated);
+
+        let handshake = recv_handshake_from_socket(&mut connection, socket_for_handshake.as_mut())
+            .await
+            .map_err(|e| UdtError::Protocol(ProtocolError::Handshake(e)))?;
+
+        let (_, signer) = raw::recv_file(
+            &mut connection,
+            output,
+            &Some(config),
+            0,
+            handshake,
+            true,
+            addr,
+        )
+        .await
+        .map_err(UdtError::Protocol)?;
+        self.signer = signer;
+
+        Ok(())
+    }
+
+    async fn udt_recv_dir<P>(&mut self, output: P) -> Result<(), UdtError>
+    where
+        P: AsRef<Path> + Send + Copy + Sync,
+    {
+        assert_udt!(!output.as_ref().is_file(), "output must be a folder path");
+
+        let mut config = self.get_config();
+        debug!("running udt_recv_dir; config: {:?}", config);
+
+        let (addr, negotiated, mut connection, mut socket_for_handshake) =
+            detail::accept_for_recipient(&mut config).await?;
+        self.negotiated_capabilities = Some(negotiated);
+
+        raw::recv_dir(
+            &mut connection,
+            output,
+            socket_for_handshake.as_mut(),
+            &Some(config),
+            addr,
+        )
+        .await
+        .map_err(UdtError::Protocol)?;
+
+        Ok(())
+    }
+
+    async fn udt_recv_files<P>(&mut self, output: P) -> Result<FilesResult, UdtError>
+    where
+        P: AsRef<Path> + Send + Copy + Sync,
+    {
+        assert_udt!(output.as_ref().is_dir(), "output must be a folder path");
+
+        let mut config = self.get_config();
+        debug!("running udt_recv_files; config: {:?}", config);
+
+        let (addr, negotiated, mut connection, mut socket_for_handshake) =
+            detail::accept_for_recipient(&mut config).await?;
+        self.negotiated_capabilities = Some(negotiated);
+
+        let results = raw::recv_files(
+            &mut connection,
+            output,
+            socket_for_handshake.as_mut(),
+            &Some(config),
+            addr,
+        )
+        .await
+        .map_err(UdtError::Protocol)?;
+
+        Ok(results)
+    }
+
+    async fn udt_recv_stream<P>(&mut self, output: P) -> Result<(), UdtError>
+    where
+        P: AsRef<Path> + Send + Copy + Sync,
+    {
+        assert_udt!(
+            !output.as_ref().exists(),
+            "output must be no exists. output path: {}",
+            output.as_ref().display()
+        );
+
+        let mut config = self.get_config();
+        debug!("running udt_recv_stream; config: {:?}", config);
+
+        let (addr, negotiated, mut connection, mut socket_for_handshake) =
+            detail::accept_for_recipient(&mut config).await?;
+        self.negotiated_capabilities = Some(negotiated);
+
+        let handshake: StreamHandshake = timeout!(
+            recv_message_from(&mut connection, socket_for_handshake.as_mut()),
+            |_| UdtError::Protocol(ProtocolError::TimeoutExpired),
+            config.timeout
+        )?
+        .map_err(|e| UdtError::Protocol(ProtocolError::Handshake(e)))?;
+
+        let config = Some(config);
+        raw::answer_stream_handshake(&mut connection, &handshake, &config, addr)
+            .await
+            .map_err(UdtError::Protocol)?;
+
+        let mut file = BufWriter::new(
+            File::create(output)
+                .await
+                .map_err(|e| UdtError::Protocol(ProtocolError::FileIO(e)))?,
+        );
+
+        raw::recv_stream(&mut connection, &mut file, &handshake, &config)
+            .await
+            .map_err(UdtError::Protocol)?;
+
+        Ok(())
+    }
+
+    async fn udt_recv_to_writer<W>(&mut self, writer: &mut W) -> Result<(), UdtError>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        let mut config = self.get_config();
+        debug!("running udt_recv_to_writer; config: {:?}", config);
+
+        let (addr, negotiated, mut connection, mut socket_for_handshake) =
+            detail::accept_for_recipient(&mut config).await?;
+        self.negotiated_capabilities = Some(negotiated);
 
-        let (addr, mut connection) = timeout!(
-            udt_listener.accept(),
+        let handshake: StreamHandshake = timeout!(
+            recv_message_from(&mut connection, socket_for_handshake.as_mut()),
             |_| UdtError::Protocol(ProtocolError::TimeoutExpired),
             config.timeout
         )?
-        .map_err(|e| UdtError::Protocol(ProtocolError::Accept(e)))?;
-        debug!("accepted connection from {}", addr);
+        .map_err(|e| UdtError::Protocol(ProtocolError::Handshake(e)))?;
+
+        let config = Some(config);
+        raw::answer_stream_handshake(&mut connection, &handshake, &config, addr)
+            .await
+            .map_err(UdtError::Protocol)?;
+
+        raw::recv_stream(&mut connection, writer, &handshake, &config)
+            .await
+            .map_err(UdtError::Protocol)?;
+
+        Ok(())
+    }
+
+    async fn udt_fetch_file<P>(&mut self, remote_path: &str, local_path: P) -> Result<(), UdtError>
+    where
+        P: AsRef<Path> + Send + Copy + Sync,
+    {
+        assert_udt!(
+            self.config.port_for_handshake.is_none(),
+            "fetch needs in-band handshakes: use new_single_port"
+        );
+        assert_udt!(
+            !local_path.as_ref().exists(),
+            "local_path must be no exists. local_path: {}",
+            local_path.as_ref().display()
+        );
+
+        let mut config = self.get_config();
+        debug!(
+            "running udt_fetch_file; config: {:?}; remote_path: {}",
+            config, remote_path
+        );
+
+        let (mut connection, mut socket_for_handshake) =
+            detail::all_connect_for_sender(&config).await?;
+
+        // The request is encrypted and authenticated, if it is agreed
+        let (negotiated, mut connection) =
+            raw::negotiate_for_recipient(&mut connection, &mut config)
+                .await
+                .map_err(UdtError::Protocol)?;
+        self.negotiated_capabilities = Some(negotiated);
+
+        let request = FetchRequest {
+            path: remote_path.to_string(),
+        };
+        send_message(&request, &mut connection)
+            .await
+            .map_err(|e| UdtError::Protocol(ProtocolError::Handshake(e)))?;
+
+        let answer: FetchAnswer = timeout!(
+            recv_message(&mut connection),
+            |_| UdtError::Protocol(ProtocolError::TimeoutExpired),
+            config.timeout
+        )?
+        .map_err(|e| UdtError::Protocol(ProtocolError::Handshake(e)))?;
+
+        if let FetchAnswer::NotFound(reason) = answer {
+            return Err(UdtError::Protocol(ProtocolError::Rejected(reason)));
+        }
 
-        let handshake = recv_handshake_from_address(&mut tcp_handshake)
+        let handshake = recv_handshake_from_socket(&mut connection, socket_for_handshake.as_mut())
             .await
             .map_err(|e| UdtError::Protocol(ProtocolError::Handshake(e)))?;
 
-        raw::recv_file(
+        let addr = SocketAddr::new(config.addr, config.port_for_send_files);
+        let (_, signer) = raw::recv_file(
             &mut connection,
-            &mut tcp_handshake,
-            Path::new(&output.as_ref().join(handshake.file_name.clone())),
+            local_path,
             &Some(config),
             0,
-            Some(handshake),
+            handshake,
+            false,
+            addr,
         )
-        .await?;
+        .await
+        .map_err(UdtError::Protocol)?;
+        self.signer = signer;
 
         Ok(())
     }
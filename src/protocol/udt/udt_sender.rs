@@ -3,12 +3,19 @@
 use super::UdtError;
 use crate::{
     prelude::*,
-    protocol::udt::{detail, error::assert_udt, raw},
+    protocol::{
+        error::{FilesResult, ProtocolError},
+        handshake::{send_message_to, StreamHandshake},
+        manifest::get_manifest_from_dir,
+        raw,
+        udt::{detail, error::assert_udt},
+    },
 };
 use async_trait::async_trait;
 use log::debug;
 use std::fmt::Debug;
 use std::path::Path;
+use tokio::io::AsyncRead;
 
 /// [UDT](https://en.wikipedia.org/wiki/UDP-based_Data_Transfer_Protocol) trait for [`CoreSender`]
 #[async_trait(?Send)]
@@ -29,6 +36,85 @@ pub trait UdtSender<'a>: CoreSender<'a> {
     async fn udt_send_file<P>(&mut self, path: P) -> Result<(), UdtError>
     where
         P: AsRef<Path> + Send + Copy + Sync + Debug;
+
+    /// Send directory recursively via [udt](https://en.wikipedia.org/wiki/UDP-based_Data_Transfer_Protocol) protocol
+    ///
+    /// Symbolic links are skipped
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use snwf::prelude::*;
+    /// # use std::path::Path;
+    /// #
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut sender = Sender::new("127.0.0.1".parse().unwrap(), 4324, 6343);
+    ///
+    ///     sender.udt_send_dir(Path::new("/home/gladi/Documents"));
+    /// }
+    /// ```
+    async fn udt_send_dir<P>(&mut self, path: P) -> Result<(), UdtError>
+    where
+        P: AsRef<Path> + Send + Copy + Sync + Debug;
+
+    /// Send many files over one [udt](https://en.wikipedia.org/wiki/UDP-based_Data_Transfer_Protocol) connection
+    ///
+    /// Returns the result for every file. A bad file does not stop the transfer of other files
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use snwf::prelude::*;
+    /// # use std::path::Path;
+    /// #
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut sender = Sender::new("127.0.0.1".parse().unwrap(), 4324, 6343);
+    ///
+    ///     let results = sender
+    ///         .udt_send_files([Path::new("file1.txt"), Path::new("file2.txt")])
+    ///         .await
+    ///         .unwrap();
+    ///
+    ///     for (path, result) in results {
+    ///         println!("{}: {:?}", path.display(), result);
+    ///     }
+    /// }
+    /// ```
+    async fn udt_send_files<I, P>(&mut self, paths: I) -> Result<FilesResult, UdtError>
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<Path>;
+
+    /// Send data from `reader` via [udt](https://en.wikipedia.org/wiki/UDP-based_Data_Transfer_Protocol) protocol
+    ///
+    /// For data without a file: database dumps, generated archives, stdin.
+    /// The hash is computed while sending and sent after the data
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - source of the data.
+    /// * `name` - name of the data for [`Recipient`](crate::recipient::Recipient).
+    /// * `size_hint` - size of the data, if known. Only for progress.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use snwf::prelude::*;
+    /// #
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut sender = Sender::new("127.0.0.1".parse().unwrap(), 4324, 6343);
+    ///
+    ///     sender.udt_send_stream(tokio::io::stdin(), "stdin.txt", None);
+    /// }
+    /// ```
+    async fn udt_send_stream<R>(
+        &mut self,
+        reader: R,
+        name: &str,
+        size_hint: Option<u64>,
+    ) -> Result<(), UdtError>
+    where
+        R: AsyncRead + Unpin;
 }
 
 #[async_trait(?Send)]
@@ -38,7 +124,7 @@ impl<'a> UdtSender<'a> for Sender<'a> {
         P: AsRef<Path> + Send + Copy + Sync + Debug,
     {
         assert_udt!(path.as_ref().is_file(), "path isn't file or not exists");
-        let config = self.get_config();
+        let mut config = self.get_config();
 
         debug!(
             "running udt_send_file; config: {:?}; path: {:?}",
@@ -46,7 +132,117 @@ impl<'a> UdtSender<'a> for Sender<'a> {
         );
 
         let (mut udt, mut socket_for_handshake) = detail::all_connect_for_sender(&config).await?;
-        raw::send_file(&mut udt, path, &mut socket_for_handshake, &Some(config), 0).await?;
+        let (negotiated, mut udt) = raw::negotiate_for_sender(&mut udt, &mut config)
+            .await
+            .map_err(UdtError::Protocol)?;
+        self.negotiated_capabilities = Some(negotiated);
+        raw::send_file(
+            &mut udt,
+            path,
+            socket_for_handshake.as_mut(),
+            &Some(config),
+            0,
+        )
+        .await
+        .map_err(UdtError::Protocol)?;
+
+        Ok(())
+    }
+
+    async fn udt_send_dir<P>(&mut self, path: P) -> Result<(), UdtError>
+    where
+        P: AsRef<Path> + Send + Copy + Sync + Debug,
+    {
+        assert_udt!(path.as_ref().is_dir(), "path isn't directory or not exists");
+        let mut config = self.get_config();
+
+        debug!(
+            "running udt_send_dir; config: {:?}; path: {:?}",
+            config, path
+        );
+
+        // Recipient waits for the manifest with timeout. The hash algorithm is not changed
+        // by the negotiation: sender offers only its own
+        let manifest = get_manifest_from_dir(path, config.hash_algorithm)
+            .await
+            .map_err(|e| UdtError::Protocol(ProtocolError::Handshake(e)))?;
+
+        let (mut udt, mut socket_for_handshake) = detail::all_connect_for_sender(&config).await?;
+        let (negotiated, mut udt) = raw::negotiate_for_sender(&mut udt, &mut config)
+            .await
+            .map_err(UdtError::Protocol)?;
+        self.negotiated_capabilities = Some(negotiated);
+        raw::send_dir(
+            &mut udt,
+            path,
+            &manifest,
+            socket_for_handshake.as_mut(),
+            &Some(config),
+        )
+        .await
+        .map_err(UdtError::Protocol)?;
+
+        Ok(())
+    }
+
+    async fn udt_send_files<I, P>(&mut self, paths: I) -> Result<FilesResult, UdtError>
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<Path>,
+    {
+        let mut config = self.get_config();
+        debug!("running udt_send_files; config: {:?}", config);
+
+        let (mut udt, mut socket_for_handshake) = detail::all_connect_for_sender(&config).await?;
+        let (negotiated, mut udt) = raw::negotiate_for_sender(&mut udt, &mut config)
+            .await
+            .map_err(UdtError::Protocol)?;
+        self.negotiated_capabilities = Some(negotiated);
+        let results = raw::send_files(
+            &mut udt,
+            paths,
+            socket_for_handshake.as_mut(),
+            &Some(config),
+        )
+        .await
+        .map_err(UdtError::Protocol)?;
+
+        Ok(results)
+    }
+
+    async fn udt_send_stream<R>(
+        &mut self,
+        mut reader: R,
+        name: &str,
+        size_hint: Option<u64>,
+    ) -> Result<(), UdtError>
+    where
+        R: AsyncRead + Unpin,
+    {
+        let mut config = self.get_config();
+        debug!(
+            "running udt_send_stream; config: {:?}; name: {}",
+            config, name
+        );
+
+        let (mut udt, mut socket_for_handshake) = detail::all_connect_for_sender(&config).await?;
+        let (negotiated, mut udt) = raw::negotiate_for_sender(&mut udt, &mut config)
+            .await
+            .map_err(UdtError::Protocol)?;
+        self.negotiated_capabilities = Some(negotiated);
+
+        let handshake = StreamHandshake {
+            file_name: name.to_string(),
+            size_hint,
+            hash_algorithm: config.hash_algorithm,
+        };
+        send_message_to(&handshake, &mut udt, socket_for_handshake.as_mut())
+            .await
+            .map_err(|e| UdtError::Protocol(ProtocolError::Handshake(e)))?;
+
+        raw::send_stream(&mut udt, &mut reader, &handshake, &Some(config))
+            .await
+            .map_err(UdtError::Protocol)?;
 
         Ok(())
     }
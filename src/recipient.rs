@@ -2,7 +2,12 @@
 
 use crate::common::{generate_config, generate_new_for_config};
 use crate::core::*;
-use std::sync::{Arc, Mutex};
+use crate::protocol::handshake::{FileNameEncoding, FileNamePolicy};
+use std::{
+    cell::RefCell,
+    rc::Rc,
+    sync::{Arc, Mutex},
+};
 
 generate_config!(ConfigRecipient, Recipient);
 
@@ -14,6 +19,69 @@ pub trait CoreRecipient<'a> {
 
     /// Set ['ProgressFnT']
     fn set_progress_fn(&mut self, progress_fn: Option<impl FnMut(Progressing) + 'a>);
+
+    /// Set callback to accept or reject incoming files. See [`Decision`]
+    ///
+    /// It is called after the handshake, before the data. If `None`, all files are accepted.
+    /// For directories, it is called for every file: one rejected file rejects the directory
+    fn set_decision_fn(&mut self, decision_fn: Option<impl FnMut(&IncomingFile) -> Decision + 'a>);
+
+    /// Set [`HashAlgorithm`]. Default: [`HashAlgorithm::Blake2b`]
+    ///
+    /// Files are checked with the algorithm of [`Sender`](crate::sender::Sender).
+    /// [`HashAlgorithm::None`] from it is accepted only if it is set here too
+    fn set_hash_algorithm(&mut self, hash_algorithm: HashAlgorithm);
+
+    /// Set max size of one chunk of data. Default: 4096
+    ///
+    /// The smaller of the values of both sides is used
+    fn set_max_chunk_size(&mut self, max_chunk_size: usize);
+
+    /// Set [`FileNamePolicy`] for unsafe file names from [`Sender`](crate::sender::Sender).
+    /// Default: [`FileNamePolicy::Reject`]
+    ///
+    /// It is used when the original name is saved and for directories
+    fn set_file_name_policy(&mut self, file_name_policy: FileNamePolicy);
+
+    /// Set [`FileNameEncoding`] for file names that are not UTF-8.
+    /// Default: [`FileNameEncoding::Utf8`]
+    ///
+    /// It is used when the original name is saved
+    fn set_file_name_encoding(&mut self, file_name_encoding: FileNameEncoding);
+
+    /// Set [`PreserveMetadata`]: which metadata of [`Sender`](crate::sender::Sender)
+    /// is applied to received files. Default: nothing
+    ///
+    /// It is applied after the hash check passes
+    fn set_preserve_metadata(&mut self, preserve_metadata: PreserveMetadata);
+
+    /// Set shared secret for authentication of the other side. Default: `None`
+    ///
+    /// If it is set, [`Sender`](crate::sender::Sender) must have the same secret.
+    /// Otherwise, the transfer fails with [`ProtocolError::Unauthorized`](crate::protocol::error::ProtocolError::Unauthorized).
+    /// See [`auth`](crate::protocol::auth)
+    fn set_auth_key(&mut self, auth_key: Option<Vec<u8>>);
+
+    /// Add public key of a trusted [`Signer`]. Default: no trusted signers, all files are accepted
+    ///
+    /// If there are trusted signers, files of other senders are rejected
+    fn add_trusted_signer(&mut self, public_key: VerifyingKey);
+
+    /// Set [`Encryption`] of the connection. Default: [`Encryption::None`]
+    ///
+    /// If it is set, [`Sender`](crate::sender::Sender) must also use encryption
+    #[cfg(feature = "encryption")]
+    fn set_encryption(&mut self, encryption: Encryption);
+
+    /// Get [`NegotiatedCapabilities`] of the last transfer
+    ///
+    /// `None` if there was no transfer
+    fn get_negotiated_capabilities(&self) -> Option<NegotiatedCapabilities>;
+
+    /// Get verified [`Signer`] of the last received file
+    ///
+    /// `None` if there was no transfer or the file isn't signed
+    fn get_signer(&self) -> Option<Signer>;
 }
 
 /// Main implementation for [`CoreRecipient`]
@@ -22,11 +90,13 @@ pub trait CoreRecipient<'a> {
 ///
 /// Only stores connection information. No protocol implementation!
 pub struct Recipient<'a> {
-    config: ConfigRecipient<'a>,
+    pub(crate) config: ConfigRecipient<'a>,
+    pub(crate) negotiated_capabilities: Option<NegotiatedCapabilities>,
+    pub(crate) signer: Option<Signer>,
 }
 
 impl Recipient<'static> {
-    generate_new_for_config!(ConfigRecipient);
+    generate_new_for_config!(ConfigRecipient, signer: None);
 }
 
 impl<'a> CoreRecipient<'a> for Recipient<'a> {
@@ -36,10 +106,58 @@ impl<'a> CoreRecipient<'a> for Recipient<'a> {
     }
 
     /// Set ['ProgressFnT']
+    // `ProgressFn` is public and stays `Arc<Mutex<..>>`, even if the callback isn't `Send`
+    #[allow(clippy::arc_with_non_send_sync)]
     fn set_progress_fn(&mut self, progress_fn: Option<impl FnMut(Progressing) + 'a>) {
         self.config.progress_fn =
             progress_fn.map(|i| -> ProgressFn { Arc::new(Mutex::new(Box::new(i))) });
     }
+
+    fn set_decision_fn(&mut self, decision_fn: Option<impl FnMut(&IncomingFile) -> Decision + 'a>) {
+        self.config.decision_fn =
+            decision_fn.map(|i| -> DecisionFn { Rc::new(RefCell::new(Box::new(i))) });
+    }
+
+    fn set_hash_algorithm(&mut self, hash_algorithm: HashAlgorithm) {
+        self.config.hash_algorithm = hash_algorithm;
+    }
+
+    fn set_max_chunk_size(&mut self, max_chunk_size: usize) {
+        self.config.max_chunk_size = max_chunk_size;
+    }
+
+    fn set_file_name_policy(&mut self, file_name_policy: FileNamePolicy) {
+        self.config.file_name_policy = file_name_policy;
+    }
+
+    fn set_file_name_encoding(&mut self, file_name_encoding: FileNameEncoding) {
+        self.config.file_name_encoding = file_name_encoding;
+    }
+
+    fn set_preserve_metadata(&mut self, preserve_metadata: PreserveMetadata) {
+        self.config.preserve_metadata = preserve_metadata;
+    }
+
+    fn set_auth_key(&mut self, auth_key: Option<Vec<u8>>) {
+        self.config.auth_key = auth_key;
+    }
+
+    fn add_trusted_signer(&mut self, public_key: VerifyingKey) {
+        self.config.trusted_signers.push(public_key);
+    }
+
+    #[cfg(feature = "encryption")]
+    fn set_encryption(&mut self, encryption: Encryption) {
+        self.config.encryption = encryption;
+    }
+
+    fn get_negotiated_capabilities(&self) -> Option<NegotiatedCapabilities> {
+        self.negotiated_capabilities.clone()
+    }
+
+    fn get_signer(&self) -> Option<Signer> {
+        self.signer.clone()
+    }
 }
 
 #[cfg(test)]
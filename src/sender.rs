@@ -14,6 +14,44 @@ pub trait CoreSender<'a> {
 
     /// Set ['ProgressFnT']
     fn set_progress_fn(&mut self, progress_fn: Option<impl FnMut(Progressing) + 'a>);
+
+    /// Set [`HashAlgorithm`] for checking files. Default: [`HashAlgorithm::Blake2b`]
+    ///
+    /// It is sent in the handshake, so [`Recipient`](crate::recipient::Recipient) uses the same one
+    fn set_hash_algorithm(&mut self, hash_algorithm: HashAlgorithm);
+
+    /// Set max size of one chunk of data. Default: 4096
+    ///
+    /// The smaller of the values of both sides is used
+    fn set_max_chunk_size(&mut self, max_chunk_size: usize);
+
+    /// Set [`Compression`] of data. Default: [`Compression::None`]
+    ///
+    /// If [`Recipient`](crate::recipient::Recipient) doesn't support it, data is sent as is
+    fn set_compression(&mut self, compression: Compression);
+
+    /// Set shared secret for authentication of the other side. Default: `None`
+    ///
+    /// If it is set, [`Recipient`](crate::recipient::Recipient) must have the same secret.
+    /// Otherwise, the transfer fails with [`ProtocolError::Unauthorized`](crate::protocol::error::ProtocolError::Unauthorized).
+    /// See [`auth`](crate::protocol::auth)
+    fn set_auth_key(&mut self, auth_key: Option<Vec<u8>>);
+
+    /// Set key for signing files. Default: `None`
+    ///
+    /// The handshake of every file is signed. See [`Signer`]
+    fn set_signing_key(&mut self, signing_key: Option<SigningKey>);
+
+    /// Set [`Encryption`] of the connection. Default: [`Encryption::None`]
+    ///
+    /// If it is set, [`Recipient`](crate::recipient::Recipient) must also use encryption
+    #[cfg(feature = "encryption")]
+    fn set_encryption(&mut self, encryption: Encryption);
+
+    /// Get [`NegotiatedCapabilities`] of the last transfer
+    ///
+    /// `None` if there was no transfer
+    fn get_negotiated_capabilities(&self) -> Option<NegotiatedCapabilities>;
 }
 
 /// Main implementation for [`CoreSender`]
@@ -22,7 +60,8 @@ pub trait CoreSender<'a> {
 ///
 /// Only stores connection information. No protocol implementation!
 pub struct Sender<'a> {
-    config: ConfigSender<'a>,
+    pub(crate) config: ConfigSender<'a>,
+    pub(crate) negotiated_capabilities: Option<NegotiatedCapabilities>,
 }
 
 impl<'a> Sender<'a> {
@@ -36,10 +75,41 @@ impl<'a> CoreSender<'a> for Sender<'a> {
     }
 
     /// Set ['ProgressFnT']
+    // `ProgressFn` is public and stays `Arc<Mutex<..>>`, even if the callback isn't `Send`
+    #[allow(clippy::arc_with_non_send_sync)]
     fn set_progress_fn(&mut self, progress_fn: Option<impl FnMut(Progressing) + 'a>) {
         self.config.progress_fn =
             progress_fn.map(|i| -> ProgressFn { Arc::new(Mutex::new(Box::new(i))) });
     }
+
+    fn set_hash_algorithm(&mut self, hash_algorithm: HashAlgorithm) {
+        self.config.hash_algorithm = hash_algorithm;
+    }
+
+    fn set_max_chunk_size(&mut self, max_chunk_size: usize) {
+        self.config.max_chunk_size = max_chunk_size;
+    }
+
+    fn set_compression(&mut self, compression: Compression) {
+        self.config.compression = compression;
+    }
+
+    fn set_auth_key(&mut self, auth_key: Option<Vec<u8>>) {
+        self.config.auth_key = auth_key;
+    }
+
+    fn set_signing_key(&mut self, signing_key: Option<SigningKey>) {
+        self.config.signing_key = signing_key;
+    }
+
+    #[cfg(feature = "encryption")]
+    fn set_encryption(&mut self, encryption: Encryption) {
+        self.config.encryption = encryption;
+    }
+
+    fn get_negotiated_capabilities(&self) -> Option<NegotiatedCapabilities> {
+        self.negotiated_capabilities.clone()
+    }
 }
 
 #[cfg(test)]